@@ -0,0 +1,724 @@
+// Copyright 2021 Bandot Developers.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+use ink_lang as ink;
+
+pub use self::a_token::AToken;
+
+#[ink::contract]
+pub mod a_token {
+    #[cfg(not(feature = "ink-as-dependency"))]
+    use ink_storage::{
+        collections::HashMap as StorageHashMap,
+        lazy::Lazy,
+    };
+    use lending_math::{
+        ray_div,
+        ray_mul,
+    };
+
+    /// An interest-bearing token representing deposits in the lending pool.
+    ///
+    /// Balances are stored scaled down by the reserve's liquidity index at the time
+    /// they were minted, so that they grow with the index as interest accrues. The
+    /// current index is read from the pool whenever a balance is converted.
+    #[ink(storage)]
+    pub struct AToken {
+        /// The lending pool allowed to mint and burn tokens.
+        pool: AccountId,
+        /// The `Erc20` contract of the underlying reserve asset.
+        underlying_asset: AccountId,
+        /// Total scaled token supply.
+        total_supply: Lazy<Balance>,
+        /// Mapping from owner to its scaled balance.
+        balances: StorageHashMap<AccountId, Balance>,
+        /// Mapping of the token amount which an account is allowed to withdraw
+        /// from another account.
+        allowances: StorageHashMap<(AccountId, AccountId), Balance>,
+    }
+
+    /// Event emitted when a token transfer occurs.
+    #[ink(event)]
+    pub struct Transfer {
+        #[ink(topic)]
+        from: Option<AccountId>,
+        #[ink(topic)]
+        to: Option<AccountId>,
+        #[ink(topic)]
+        value: Balance,
+    }
+
+    /// Event emitted when an approval occurs that `spender` is allowed to withdraw
+    /// up to the amount of `value` tokens from `owner`.
+    #[ink(event)]
+    pub struct Approval {
+        #[ink(topic)]
+        owner: AccountId,
+        #[ink(topic)]
+        spender: AccountId,
+        #[ink(topic)]
+        value: Balance,
+    }
+
+    /// Event emitted when the pool mints `value` tokens to `user` at liquidity `index`.
+    #[ink(event)]
+    pub struct Mint {
+        #[ink(topic)]
+        user: AccountId,
+        value: Balance,
+        index: Balance,
+    }
+
+    /// Event emitted when the pool burns `value` tokens of `user` at liquidity `index`.
+    #[ink(event)]
+    pub struct Burn {
+        #[ink(topic)]
+        user: AccountId,
+        value: Balance,
+        index: Balance,
+    }
+
+    /// The aToken error types.
+    #[derive(Debug, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+    pub enum Error {
+        /// Returned if not enough balance to fulfill a request is available.
+        InsufficientBalance,
+        /// Returned if not enough allowance to fulfill a request is available.
+        InsufficientAllowance,
+        /// Returned if the caller is not the lending pool.
+        CallerNotPool,
+        /// Returned if the amount is too small to be represented as a scaled balance.
+        InvalidAmount,
+        /// Returned if the lending pool needs the tokens to back the sender's debt.
+        TransferNotAllowed,
+    }
+
+    /// The aToken result type.
+    pub type Result<T> = core::result::Result<T, Error>;
+
+    impl AToken {
+        /// Creates a new aToken for `underlying_asset` managed by `pool`.
+        #[ink(constructor)]
+        pub fn new(pool: AccountId, underlying_asset: AccountId) -> Self {
+            Self {
+                pool,
+                underlying_asset,
+                total_supply: Lazy::new(0),
+                balances: StorageHashMap::new(),
+                allowances: StorageHashMap::new(),
+            }
+        }
+
+        /// Returns the lending pool allowed to mint and burn tokens.
+        #[ink(message)]
+        pub fn pool(&self) -> AccountId {
+            self.pool
+        }
+
+        /// Returns the `Erc20` contract of the underlying reserve asset.
+        #[ink(message)]
+        pub fn underlying_asset(&self) -> AccountId {
+            self.underlying_asset
+        }
+
+        /// Returns the liquidity index of the reserve compounded up to now, as
+        /// reported by the pool.
+        #[ink(message)]
+        pub fn liquidity_index(&self) -> Balance {
+            ext::reserve_normalized_income(self.pool, self.underlying_asset)
+        }
+
+        /// Returns the total token supply, including accrued interest.
+        #[ink(message)]
+        pub fn total_supply(&self) -> Balance {
+            ray_mul(*self.total_supply, self.liquidity_index())
+        }
+
+        /// Returns the total scaled token supply.
+        #[ink(message)]
+        pub fn scaled_total_supply(&self) -> Balance {
+            *self.total_supply
+        }
+
+        /// Returns the account balance for the specified `owner`, including
+        /// accrued interest.
+        ///
+        /// Returns `0` if the account is non-existent.
+        #[ink(message)]
+        pub fn balance_of(&self, owner: AccountId) -> Balance {
+            ray_mul(self.scaled_balance_of(owner), self.liquidity_index())
+        }
+
+        /// Returns the scaled account balance for the specified `owner`.
+        ///
+        /// Returns `0` if the account is non-existent.
+        #[ink(message)]
+        pub fn scaled_balance_of(&self, owner: AccountId) -> Balance {
+            self.balances.get(&owner).copied().unwrap_or(0)
+        }
+
+        /// Returns the amount which `spender` is still allowed to withdraw from `owner`.
+        ///
+        /// Returns `0` if no allowance has been set `0`.
+        #[ink(message)]
+        pub fn allowance(&self, owner: AccountId, spender: AccountId) -> Balance {
+            self.allowances.get(&(owner, spender)).copied().unwrap_or(0)
+        }
+
+        /// Transfers `value` amount of tokens from the caller's account to account `to`.
+        ///
+        /// On success a `Transfer` event is emitted.
+        ///
+        /// # Errors
+        ///
+        /// Returns `InsufficientBalance` error if there are not enough tokens on
+        /// the caller's account balance.
+        ///
+        /// Returns `TransferNotAllowed` error if the lending pool needs the tokens
+        /// to back the caller's debt.
+        #[ink(message)]
+        pub fn transfer(&mut self, to: AccountId, value: Balance) -> Result<()> {
+            let from = self.env().caller();
+            self.transfer_from_to(from, to, value)
+        }
+
+        /// Allows `spender` to withdraw from the caller's account multiple times, up to
+        /// the `value` amount.
+        ///
+        /// If this function is called again it overwrites the current allowance with `value`.
+        ///
+        /// An `Approval` event is emitted.
+        #[ink(message)]
+        pub fn approve(&mut self, spender: AccountId, value: Balance) -> Result<()> {
+            let owner = self.env().caller();
+            self.allowances.insert((owner, spender), value);
+            self.env().emit_event(Approval {
+                owner,
+                spender,
+                value,
+            });
+            Ok(())
+        }
+
+        /// Transfers `value` tokens on the behalf of `from` to the account `to`.
+        ///
+        /// On success a `Transfer` event is emitted.
+        ///
+        /// # Errors
+        ///
+        /// Returns `InsufficientAllowance` error if there are not enough tokens allowed
+        /// for the caller to withdraw from `from`.
+        ///
+        /// Returns `InsufficientBalance` error if there are not enough tokens on
+        /// the the account balance of `from`.
+        ///
+        /// Returns `TransferNotAllowed` error if the lending pool needs the tokens
+        /// to back the debt of `from`.
+        #[ink(message)]
+        pub fn transfer_from(
+            &mut self,
+            from: AccountId,
+            to: AccountId,
+            value: Balance,
+        ) -> Result<()> {
+            let caller = self.env().caller();
+            let allowance = self.allowance(from, caller);
+            if allowance < value {
+                return Err(Error::InsufficientAllowance)
+            }
+            self.transfer_from_to(from, to, value)?;
+            self.allowances.insert((from, caller), allowance - value);
+            Ok(())
+        }
+
+        /// Mints `value` tokens to `user` at the liquidity `index` of the reserve.
+        ///
+        /// On success a `Transfer` and a `Mint` event are emitted.
+        ///
+        /// # Errors
+        ///
+        /// Returns `CallerNotPool` error if the caller is not the lending pool.
+        ///
+        /// Returns `InvalidAmount` error if `value` scales down to zero.
+        #[ink(message)]
+        pub fn mint(
+            &mut self,
+            user: AccountId,
+            value: Balance,
+            index: Balance,
+        ) -> Result<()> {
+            self.ensure_pool()?;
+            let scaled = ray_div(value, index);
+            if scaled == 0 {
+                return Err(Error::InvalidAmount)
+            }
+            let balance = self.scaled_balance_of(user);
+            self.balances.insert(user, balance + scaled);
+            *self.total_supply += scaled;
+            self.env().emit_event(Transfer {
+                from: None,
+                to: Some(user),
+                value,
+            });
+            self.env().emit_event(Mint { user, value, index });
+            Ok(())
+        }
+
+        /// Burns `value` tokens of `user` at the liquidity `index` of the reserve.
+        ///
+        /// If `value` is all `user` holds at `index`, its whole scaled balance is
+        /// burned.
+        ///
+        /// On success a `Transfer` and a `Burn` event are emitted.
+        ///
+        /// # Errors
+        ///
+        /// Returns `CallerNotPool` error if the caller is not the lending pool.
+        ///
+        /// Returns `InvalidAmount` error if `value` scales down to zero.
+        ///
+        /// Returns `InsufficientBalance` error if `user` holds less than `value`.
+        #[ink(message)]
+        pub fn burn(
+            &mut self,
+            user: AccountId,
+            value: Balance,
+            index: Balance,
+        ) -> Result<()> {
+            self.ensure_pool()?;
+            let balance = self.scaled_balance_of(user);
+            let held = ray_mul(balance, index);
+            if held < value {
+                return Err(Error::InsufficientBalance)
+            }
+            // Burning all `user` holds leaves no dust behind after rounding.
+            let scaled = if value == held {
+                balance
+            } else {
+                core::cmp::min(ray_div(value, index), balance)
+            };
+            if scaled == 0 {
+                return Err(Error::InvalidAmount)
+            }
+            self.balances.insert(user, balance - scaled);
+            *self.total_supply -= scaled;
+            self.env().emit_event(Transfer {
+                from: Some(user),
+                to: None,
+                value,
+            });
+            self.env().emit_event(Burn { user, value, index });
+            Ok(())
+        }
+
+        /// Transfers `value` amount of tokens from the caller's account to account `to`.
+        ///
+        /// The scaled amount moved is `value` divided by the current liquidity index,
+        /// while the `Transfer` event reports `value` itself. The lending pool is
+        /// asked first whether `from` may let go of the tokens, given its scaled
+        /// balance, so that the pool does not have to call back into this token.
+        ///
+        /// On success a `Transfer` event is emitted.
+        ///
+        /// # Errors
+        ///
+        /// Returns `InsufficientBalance` error if there are not enough tokens on
+        /// the caller's account balance.
+        ///
+        /// Returns `TransferNotAllowed` error if the lending pool needs the tokens
+        /// to back the debt of `from`.
+        fn transfer_from_to(
+            &mut self,
+            from: AccountId,
+            to: AccountId,
+            value: Balance,
+        ) -> Result<()> {
+            let index = self.liquidity_index();
+            let from_balance = self.scaled_balance_of(from);
+            if ray_mul(from_balance, index) < value {
+                return Err(Error::InsufficientBalance)
+            }
+            if !ext::can_transfer_deposit(
+                self.pool,
+                self.underlying_asset,
+                from,
+                value,
+                from_balance,
+            ) {
+                return Err(Error::TransferNotAllowed)
+            }
+            // Rounding may scale `value` slightly above the stored balance.
+            let scaled = core::cmp::min(ray_div(value, index), from_balance);
+            self.balances.insert(from, from_balance - scaled);
+            let to_balance = self.scaled_balance_of(to);
+            self.balances.insert(to, to_balance + scaled);
+            self.env().emit_event(Transfer {
+                from: Some(from),
+                to: Some(to),
+                value,
+            });
+            Ok(())
+        }
+
+        /// Returns `CallerNotPool` error if the caller is not the lending pool.
+        fn ensure_pool(&self) -> Result<()> {
+            if self.env().caller() != self.pool {
+                return Err(Error::CallerNotPool)
+            }
+            Ok(())
+        }
+    }
+
+    /// Calls into the `LendingPool` contract.
+    #[cfg(not(test))]
+    mod ext {
+        use super::{
+            AccountId,
+            Balance,
+        };
+        use ink_env::call::{
+            build_call,
+            utils::ReturnType,
+            ExecutionInput,
+            Selector,
+        };
+
+        /// The selector of `LendingPool::can_transfer_deposit`, which is fixed so
+        /// that the token can call it without depending on the pool crate.
+        const CAN_TRANSFER_DEPOSIT_SELECTOR: [u8; 4] = [0xA7, 0x04, 0x00, 0x01];
+
+        /// The selector of `LendingPool::reserve_normalized_income`, fixed for the
+        /// same reason.
+        const RESERVE_NORMALIZED_INCOME_SELECTOR: [u8; 4] = [0xA7, 0x04, 0x00, 0x02];
+
+        /// Returns `true` if `pool` lets `from` transfer `value` of its deposit in
+        /// `asset`, whose scaled balance is `scaled_balance`, and `false` if it
+        /// refuses or cannot be asked.
+        pub fn can_transfer_deposit(
+            pool: AccountId,
+            asset: AccountId,
+            from: AccountId,
+            value: Balance,
+            scaled_balance: Balance,
+        ) -> bool {
+            build_call::<ink_env::DefaultEnvironment>()
+                .callee(pool)
+                .exec_input(
+                    ExecutionInput::new(Selector::new(CAN_TRANSFER_DEPOSIT_SELECTOR))
+                        .push_arg(asset)
+                        .push_arg(from)
+                        .push_arg(value)
+                        .push_arg(scaled_balance),
+                )
+                .returns::<ReturnType<bool>>()
+                .fire()
+                .unwrap_or(false)
+        }
+
+        /// Returns the liquidity index of `asset` compounded up to now by `pool`.
+        ///
+        /// # Panics
+        ///
+        /// If the pool cannot be asked, since no balance can be told without it.
+        pub fn reserve_normalized_income(pool: AccountId, asset: AccountId) -> Balance {
+            build_call::<ink_env::DefaultEnvironment>()
+                .callee(pool)
+                .exec_input(
+                    ExecutionInput::new(Selector::new(
+                        RESERVE_NORMALIZED_INCOME_SELECTOR,
+                    ))
+                    .push_arg(asset),
+                )
+                .returns::<ReturnType<Balance>>()
+                .fire()
+                .expect("pool did not report the liquidity index")
+        }
+    }
+
+    /// In-memory stand-in for the `LendingPool` contract.
+    ///
+    /// The off-chain environment cannot dispatch calls to other contracts, so
+    /// unit tests keep the liquidity index and the accounts the pool refuses to
+    /// let go of their tokens here instead.
+    #[cfg(test)]
+    mod ext {
+        use super::{
+            AccountId,
+            Balance,
+        };
+        use lending_math::RAY;
+        use std::{
+            cell::{
+                Cell,
+                RefCell,
+            },
+            collections::HashSet,
+        };
+
+        thread_local! {
+            static LOCKED: RefCell<HashSet<AccountId>> = RefCell::new(HashSet::new());
+            static INDEX: Cell<Balance> = Cell::new(RAY);
+        }
+
+        /// Sets the liquidity index the pool reports, one ray unless set.
+        pub fn set_liquidity_index(index: Balance) {
+            INDEX.with(|current| current.set(index))
+        }
+
+        pub fn reserve_normalized_income(_pool: AccountId, _asset: AccountId) -> Balance {
+            INDEX.with(|current| current.get())
+        }
+
+        /// Makes the pool refuse all transfers from `owner`.
+        pub fn lock(owner: AccountId) {
+            LOCKED.with(|locked| {
+                locked.borrow_mut().insert(owner);
+            })
+        }
+
+        pub fn can_transfer_deposit(
+            _pool: AccountId,
+            _asset: AccountId,
+            from: AccountId,
+            _value: Balance,
+            _scaled_balance: Balance,
+        ) -> bool {
+            !LOCKED.with(|locked| locked.borrow().contains(&from))
+        }
+    }
+
+    /// Unit tests.
+    #[cfg(test)]
+    mod tests {
+        /// Imports all the definitions from the outer scope so we can use them here.
+        use super::*;
+
+        type Event = <AToken as ::ink_lang::BaseEvent>::Type;
+
+        use ink_lang as ink;
+        use lending_math::RAY;
+
+        const UNDERLYING: [u8; 32] = [0xAA; 32];
+
+        fn default_accounts(
+        ) -> ink_env::test::DefaultAccounts<ink_env::DefaultEnvironment> {
+            ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts")
+        }
+
+        /// Pushes a new execution context so that `caller` calls the token.
+        fn set_caller(caller: AccountId) {
+            let callee = ink_env::account_id::<ink_env::DefaultEnvironment>()
+                .unwrap_or([0x0; 32].into());
+            // Create call.
+            let mut data =
+                ink_env::test::CallData::new(ink_env::call::Selector::new([0x00; 4]));
+            data.push_arg(&caller);
+            ink_env::test::push_execution_context::<ink_env::DefaultEnvironment>(
+                caller, callee, 1000000, 1000000, data,
+            );
+        }
+
+        fn assert_transfer_event(
+            event: &ink_env::test::EmittedEvent,
+            expected_from: Option<AccountId>,
+            expected_to: Option<AccountId>,
+            expected_value: Balance,
+        ) {
+            let decoded_event = <Event as scale::Decode>::decode(&mut &event.data[..])
+                .expect("encountered invalid contract event data buffer");
+            if let Event::Transfer(Transfer { from, to, value }) = decoded_event {
+                assert_eq!(from, expected_from, "encountered invalid Transfer.from");
+                assert_eq!(to, expected_to, "encountered invalid Transfer.to");
+                assert_eq!(value, expected_value, "encountered invalid Transfer.value");
+            } else {
+                panic!("encountered unexpected event kind: expected a Transfer event")
+            }
+        }
+
+        /// Creates an aToken whose pool is Alice.
+        fn new_token() -> AToken {
+            AToken::new(default_accounts().alice, UNDERLYING.into())
+        }
+
+        #[ink::test]
+        fn new_works() {
+            let token = new_token();
+            assert_eq!(token.pool(), default_accounts().alice);
+            assert_eq!(token.underlying_asset(), AccountId::from(UNDERLYING));
+            assert_eq!(token.liquidity_index(), RAY);
+            assert_eq!(token.total_supply(), 0);
+        }
+
+        #[ink::test]
+        fn mint_works() {
+            let mut token = new_token();
+            let accounts = default_accounts();
+
+            assert_eq!(token.mint(accounts.bob, 100, RAY), Ok(()));
+            assert_eq!(token.balance_of(accounts.bob), 100);
+            assert_eq!(token.total_supply(), 100);
+
+            let emitted_events = ink_env::test::recorded_events().collect::<Vec<_>>();
+            assert_eq!(emitted_events.len(), 2);
+            assert_transfer_event(&emitted_events[0], None, Some(accounts.bob), 100);
+        }
+
+        #[ink::test]
+        fn only_pool_can_mint_and_burn() {
+            let mut token = new_token();
+            let accounts = default_accounts();
+            assert_eq!(token.mint(accounts.bob, 100, RAY), Ok(()));
+
+            set_caller(accounts.bob);
+            assert_eq!(
+                token.mint(accounts.bob, 100, RAY),
+                Err(Error::CallerNotPool)
+            );
+            assert_eq!(
+                token.burn(accounts.bob, 100, RAY),
+                Err(Error::CallerNotPool)
+            );
+            assert_eq!(token.balance_of(accounts.bob), 100);
+        }
+
+        #[ink::test]
+        fn balance_grows_with_liquidity_index() {
+            let mut token = new_token();
+            let accounts = default_accounts();
+            assert_eq!(token.mint(accounts.bob, 100, RAY), Ok(()));
+
+            // 10% interest accrues without any action on the token.
+            let index = RAY + RAY / 10;
+            ext::set_liquidity_index(index);
+            assert_eq!(token.liquidity_index(), index);
+            assert_eq!(token.balance_of(accounts.bob), 110);
+            assert_eq!(token.total_supply(), 110);
+
+            assert_eq!(token.mint(accounts.charlie, 110, index), Ok(()));
+            assert_eq!(token.balance_of(accounts.bob), 110);
+            assert_eq!(token.balance_of(accounts.charlie), 110);
+            assert_eq!(token.scaled_balance_of(accounts.bob), 100);
+            assert_eq!(token.scaled_balance_of(accounts.charlie), 100);
+            assert_eq!(token.total_supply(), 220);
+        }
+
+        #[ink::test]
+        fn burn_works() {
+            let mut token = new_token();
+            let accounts = default_accounts();
+            assert_eq!(token.mint(accounts.bob, 100, RAY), Ok(()));
+
+            let index = 2 * RAY;
+            ext::set_liquidity_index(index);
+            assert_eq!(
+                token.burn(accounts.bob, 201, index),
+                Err(Error::InsufficientBalance)
+            );
+            assert_eq!(token.burn(accounts.bob, 150, index), Ok(()));
+            assert_eq!(token.balance_of(accounts.bob), 50);
+            assert_eq!(token.total_supply(), 50);
+
+            let emitted_events = ink_env::test::recorded_events().collect::<Vec<_>>();
+            assert_eq!(emitted_events.len(), 4);
+            assert_transfer_event(&emitted_events[2], Some(accounts.bob), None, 150);
+
+            assert_eq!(token.burn(accounts.bob, 50, index), Ok(()));
+            assert_eq!(token.scaled_balance_of(accounts.bob), 0);
+            assert_eq!(token.scaled_total_supply(), 0);
+        }
+
+        #[ink::test]
+        fn dust_amounts_are_rejected() {
+            let mut token = new_token();
+            let accounts = default_accounts();
+            assert_eq!(
+                token.mint(accounts.bob, 1, 3 * RAY),
+                Err(Error::InvalidAmount)
+            );
+        }
+
+        #[ink::test]
+        fn transfer_emits_underlying_value() {
+            let mut token = new_token();
+            let accounts = default_accounts();
+            assert_eq!(token.mint(accounts.alice, 100, RAY), Ok(()));
+            assert_eq!(token.mint(accounts.bob, 2, 2 * RAY), Ok(()));
+            ext::set_liquidity_index(2 * RAY);
+
+            // Alice's 100 scaled tokens are now worth 200.
+            assert_eq!(
+                token.transfer(accounts.bob, 201),
+                Err(Error::InsufficientBalance)
+            );
+            assert_eq!(token.transfer(accounts.bob, 50), Ok(()));
+            assert_eq!(token.balance_of(accounts.alice), 150);
+            assert_eq!(token.balance_of(accounts.bob), 52);
+            assert_eq!(token.scaled_balance_of(accounts.alice), 75);
+
+            let emitted_events = ink_env::test::recorded_events().collect::<Vec<_>>();
+            assert_eq!(emitted_events.len(), 5);
+            assert_transfer_event(
+                &emitted_events[4],
+                Some(accounts.alice),
+                Some(accounts.bob),
+                50,
+            );
+        }
+
+        #[ink::test]
+        fn pool_can_refuse_transfers() {
+            let mut token = new_token();
+            let accounts = default_accounts();
+            assert_eq!(token.mint(accounts.alice, 100, RAY), Ok(()));
+            assert_eq!(token.approve(accounts.bob, 100), Ok(()));
+
+            ext::lock(accounts.alice);
+            assert_eq!(
+                token.transfer(accounts.bob, 50),
+                Err(Error::TransferNotAllowed)
+            );
+            set_caller(accounts.bob);
+            assert_eq!(
+                token.transfer_from(accounts.alice, accounts.bob, 50),
+                Err(Error::TransferNotAllowed)
+            );
+            assert_eq!(token.allowance(accounts.alice, accounts.bob), 100);
+            assert_eq!(token.balance_of(accounts.alice), 100);
+        }
+
+        #[ink::test]
+        fn transfer_from_works() {
+            let mut token = new_token();
+            let accounts = default_accounts();
+            assert_eq!(token.mint(accounts.alice, 100, RAY), Ok(()));
+            assert_eq!(token.approve(accounts.bob, 30), Ok(()));
+
+            set_caller(accounts.bob);
+            assert_eq!(
+                token.transfer_from(accounts.alice, accounts.eve, 31),
+                Err(Error::InsufficientAllowance)
+            );
+            assert_eq!(
+                token.transfer_from(accounts.alice, accounts.eve, 30),
+                Ok(())
+            );
+            assert_eq!(token.balance_of(accounts.eve), 30);
+            assert_eq!(token.allowance(accounts.alice, accounts.bob), 0);
+        }
+    }
+}
@@ -0,0 +1,1339 @@
+// Copyright 2021 Bandot Developers.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+use ink_lang as ink;
+
+pub use self::governor::{
+    Governor,
+    Proposal,
+    ProposalCall,
+    ProposalState,
+    VoteType,
+};
+
+#[ink::contract]
+pub mod governor {
+    use ink_env::call::{
+        ExecutionInput,
+        Selector,
+    };
+    use ink_prelude::{
+        string::String,
+        vec::Vec,
+    };
+    #[cfg(not(feature = "ink-as-dependency"))]
+    use ink_storage::collections::HashMap as StorageHashMap;
+    use ink_storage::traits::{
+        PackedLayout,
+        SpreadLayout,
+    };
+
+    /// Identifier of a proposal.
+    pub type ProposalId = u32;
+
+    /// A call to another contract made when a proposal is executed.
+    ///
+    /// The called message either returns nothing or a `Result`. A call that
+    /// returns an `Err` fails the execution like one that traps.
+    #[derive(
+        Debug,
+        Clone,
+        PartialEq,
+        Eq,
+        scale::Encode,
+        scale::Decode,
+        SpreadLayout,
+        PackedLayout,
+    )]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink_storage::traits::StorageLayout)
+    )]
+    pub struct ProposalCall {
+        /// The contract to call.
+        pub callee: AccountId,
+        /// The selector of the message to call.
+        pub selector: [u8; 4],
+        /// The SCALE encoded arguments of the message.
+        pub input: Vec<u8>,
+    }
+
+    impl ProposalCall {
+        /// Returns the input the callee receives: the selector followed by the
+        /// encoded arguments.
+        fn execution_input(&self) -> ExecutionInput<impl scale::Encode + '_> {
+            ExecutionInput::new(Selector::new(self.selector))
+                .push_arg(CallInput(&self.input))
+        }
+    }
+
+    /// Arguments that are already SCALE encoded.
+    struct CallInput<'a>(&'a [u8]);
+
+    impl<'a> scale::Encode for CallInput<'a> {
+        fn encode_to<T: scale::Output + ?Sized>(&self, dest: &mut T) {
+            dest.write(self.0);
+        }
+    }
+
+    /// A proposal and the votes cast on it so far.
+    #[derive(
+        Debug,
+        Clone,
+        PartialEq,
+        Eq,
+        scale::Encode,
+        scale::Decode,
+        SpreadLayout,
+        PackedLayout,
+    )]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink_storage::traits::StorageLayout)
+    )]
+    pub struct Proposal {
+        /// The account that created the proposal.
+        pub proposer: AccountId,
+        /// The calls made in order when the proposal is executed.
+        pub calls: Vec<ProposalCall>,
+        /// The block at whose end voting power is measured. Voting starts right
+        /// after it.
+        pub start_block: BlockNumber,
+        /// The last block in which votes can be cast.
+        pub end_block: BlockNumber,
+        /// The votes in favour of the proposal.
+        pub for_votes: Balance,
+        /// The votes against the proposal.
+        pub against_votes: Balance,
+        /// The votes counting towards the quorum only.
+        pub abstain_votes: Balance,
+        /// The time from which the proposal can be executed, once queued.
+        pub eta: Option<Timestamp>,
+        /// Whether the proposal was canceled by its proposer.
+        pub canceled: bool,
+        /// Whether the proposal was executed.
+        pub executed: bool,
+    }
+
+    /// The choices a voter has on a proposal.
+    #[derive(
+        Debug,
+        Copy,
+        Clone,
+        PartialEq,
+        Eq,
+        scale::Encode,
+        scale::Decode,
+        SpreadLayout,
+        PackedLayout,
+    )]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink_storage::traits::StorageLayout)
+    )]
+    pub enum VoteType {
+        /// Votes against the proposal.
+        Against,
+        /// Votes in favour of the proposal.
+        For,
+        /// Counts towards the quorum without taking sides.
+        Abstain,
+    }
+
+    /// The stages a proposal goes through.
+    #[derive(Debug, Copy, Clone, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+    pub enum ProposalState {
+        /// Created but voting has not started yet.
+        Pending,
+        /// Open for votes.
+        Active,
+        /// Withdrawn by the proposer.
+        Canceled,
+        /// Voting ended without a quorum or a majority in favour.
+        Defeated,
+        /// Voting ended with a quorum and a majority in favour.
+        Succeeded,
+        /// Waiting in the timelock to be executed.
+        Queued,
+        /// Not executed within the grace period after its timelock expired.
+        Expired,
+        /// Executed.
+        Executed,
+    }
+
+    /// Lets the holders of a token vote on proposals that call other contracts,
+    /// and runs passed proposals through a timelock.
+    ///
+    /// Voting power is the delegated votes of an account in the `Erc20` token at
+    /// the start of voting. The governor itself is meant to own the protocol
+    /// contracts, so that their settings can only be changed by a proposal.
+    #[ink(storage)]
+    pub struct Governor {
+        /// The `Erc20` token whose votes are counted.
+        token: AccountId,
+        /// The blocks between the creation of a proposal and the start of voting.
+        voting_delay: BlockNumber,
+        /// The blocks voting stays open for.
+        voting_period: BlockNumber,
+        /// The votes an account needs to create a proposal.
+        proposal_threshold: Balance,
+        /// The percentage of the total supply that must vote for or abstain.
+        quorum: Balance,
+        /// The milliseconds a queued proposal waits before it can be executed.
+        timelock_delay: Timestamp,
+        /// The milliseconds a proposal stays executable after its timelock expired.
+        grace_period: Timestamp,
+        /// All proposals by identifier.
+        proposals: StorageHashMap<ProposalId, Proposal>,
+        /// The vote cast by each account on each proposal.
+        receipts: StorageHashMap<(ProposalId, AccountId), VoteType>,
+        /// The identifier of the next proposal.
+        next_proposal_id: ProposalId,
+    }
+
+    /// Event emitted when a proposal is created.
+    #[ink(event)]
+    pub struct ProposalCreated {
+        #[ink(topic)]
+        proposal_id: ProposalId,
+        #[ink(topic)]
+        proposer: AccountId,
+        calls: Vec<ProposalCall>,
+        start_block: BlockNumber,
+        end_block: BlockNumber,
+        description: String,
+    }
+
+    /// Event emitted when an account votes on a proposal.
+    #[ink(event)]
+    pub struct VoteCast {
+        #[ink(topic)]
+        voter: AccountId,
+        #[ink(topic)]
+        proposal_id: ProposalId,
+        support: VoteType,
+        weight: Balance,
+    }
+
+    /// Event emitted when a proposal is canceled.
+    #[ink(event)]
+    pub struct ProposalCanceled {
+        #[ink(topic)]
+        proposal_id: ProposalId,
+    }
+
+    /// Event emitted when a passed proposal is queued in the timelock.
+    #[ink(event)]
+    pub struct ProposalQueued {
+        #[ink(topic)]
+        proposal_id: ProposalId,
+        eta: Timestamp,
+    }
+
+    /// Event emitted when a proposal is executed.
+    #[ink(event)]
+    pub struct ProposalExecuted {
+        #[ink(topic)]
+        proposal_id: ProposalId,
+    }
+
+    /// The governor error types.
+    #[derive(Debug, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+    pub enum Error {
+        /// Returned if no proposal has the given identifier.
+        ProposalNotFound,
+        /// Returned if a proposal makes no calls.
+        EmptyProposal,
+        /// Returned if the proposer has fewer votes than the proposal threshold.
+        BelowProposalThreshold,
+        /// Returned if a vote is cast on a proposal that is not active.
+        ProposalNotActive,
+        /// Returned if the voter already voted on the proposal.
+        AlreadyVoted,
+        /// Returned if a proposal that has not succeeded is queued.
+        ProposalNotSucceeded,
+        /// Returned if a proposal that is not queued is executed.
+        ProposalNotQueued,
+        /// Returned if a proposal is executed before its timelock expired.
+        TimelockNotExpired,
+        /// Returned if the caller is not the proposer.
+        CallerNotProposer,
+        /// Returned if a proposal is canceled after it was executed or canceled.
+        ProposalClosed,
+    }
+
+    /// The governor result type.
+    pub type Result<T> = core::result::Result<T, Error>;
+
+    impl Governor {
+        /// Creates a new governor counting the votes of `token`.
+        ///
+        /// # Panics
+        ///
+        /// If `voting_period` is zero or `quorum` is above 100.
+        #[ink(constructor)]
+        pub fn new(
+            token: AccountId,
+            voting_delay: BlockNumber,
+            voting_period: BlockNumber,
+            proposal_threshold: Balance,
+            quorum: Balance,
+            timelock_delay: Timestamp,
+            grace_period: Timestamp,
+        ) -> Self {
+            assert!(voting_period > 0, "voting period must not be zero");
+            assert!(quorum <= 100, "quorum must not be above 100");
+            Self {
+                token,
+                voting_delay,
+                voting_period,
+                proposal_threshold,
+                quorum,
+                timelock_delay,
+                grace_period,
+                proposals: StorageHashMap::new(),
+                receipts: StorageHashMap::new(),
+                next_proposal_id: 0,
+            }
+        }
+
+        /// Returns the `Erc20` token whose votes are counted.
+        #[ink(message)]
+        pub fn token(&self) -> AccountId {
+            self.token
+        }
+
+        /// Returns the blocks between the creation of a proposal and the start of
+        /// voting.
+        #[ink(message)]
+        pub fn voting_delay(&self) -> BlockNumber {
+            self.voting_delay
+        }
+
+        /// Returns the blocks voting stays open for.
+        #[ink(message)]
+        pub fn voting_period(&self) -> BlockNumber {
+            self.voting_period
+        }
+
+        /// Returns the votes an account needs to create a proposal.
+        #[ink(message)]
+        pub fn proposal_threshold(&self) -> Balance {
+            self.proposal_threshold
+        }
+
+        /// Returns the percentage of the total supply that must vote for or abstain.
+        #[ink(message)]
+        pub fn quorum(&self) -> Balance {
+            self.quorum
+        }
+
+        /// Returns the milliseconds a queued proposal waits before it can be
+        /// executed.
+        #[ink(message)]
+        pub fn timelock_delay(&self) -> Timestamp {
+            self.timelock_delay
+        }
+
+        /// Returns the milliseconds a proposal stays executable after its timelock
+        /// expired.
+        #[ink(message)]
+        pub fn grace_period(&self) -> Timestamp {
+            self.grace_period
+        }
+
+        /// Returns the proposal with identifier `proposal_id`, if any.
+        #[ink(message)]
+        pub fn proposal(&self, proposal_id: ProposalId) -> Option<Proposal> {
+            self.proposals.get(&proposal_id).cloned()
+        }
+
+        /// Returns the vote `account` cast on `proposal_id`, if any.
+        #[ink(message)]
+        pub fn receipt(
+            &self,
+            proposal_id: ProposalId,
+            account: AccountId,
+        ) -> Option<VoteType> {
+            self.receipts.get(&(proposal_id, account)).copied()
+        }
+
+        /// Returns the current stage of `proposal_id`.
+        ///
+        /// # Errors
+        ///
+        /// Returns `ProposalNotFound` error if there is no such proposal.
+        #[ink(message)]
+        pub fn state(&self, proposal_id: ProposalId) -> Result<ProposalState> {
+            let proposal = self
+                .proposals
+                .get(&proposal_id)
+                .ok_or(Error::ProposalNotFound)?;
+            let block = self.env().block_number();
+            let state = if proposal.canceled {
+                ProposalState::Canceled
+            } else if proposal.executed {
+                ProposalState::Executed
+            } else if block <= proposal.start_block {
+                ProposalState::Pending
+            } else if block <= proposal.end_block {
+                ProposalState::Active
+            } else if !self.is_passed(proposal) {
+                ProposalState::Defeated
+            } else {
+                match proposal.eta {
+                    None => ProposalState::Succeeded,
+                    Some(eta)
+                        if self.env().block_timestamp()
+                            >= eta.saturating_add(self.grace_period) =>
+                    {
+                        ProposalState::Expired
+                    }
+                    Some(_) => ProposalState::Queued,
+                }
+            };
+            Ok(state)
+        }
+
+        /// Creates a proposal making `calls` in order once it passed.
+        ///
+        /// The caller must have had at least `proposal_threshold` votes at the end
+        /// of the previous block. Voting starts `voting_delay` blocks from now.
+        ///
+        /// On success a `ProposalCreated` event is emitted.
+        ///
+        /// # Errors
+        ///
+        /// Returns `EmptyProposal` error if `calls` is empty.
+        ///
+        /// Returns `BelowProposalThreshold` error if the caller has too few votes.
+        #[ink(message)]
+        pub fn propose(
+            &mut self,
+            calls: Vec<ProposalCall>,
+            description: String,
+        ) -> Result<ProposalId> {
+            if calls.is_empty() {
+                return Err(Error::EmptyProposal)
+            }
+            let proposer = self.env().caller();
+            let block = self.env().block_number();
+            let votes =
+                ext::get_past_votes(self.token, proposer, block.saturating_sub(1));
+            if votes < self.proposal_threshold {
+                return Err(Error::BelowProposalThreshold)
+            }
+            let start_block = block + self.voting_delay;
+            let end_block = start_block + self.voting_period;
+            let proposal_id = self.next_proposal_id;
+            self.next_proposal_id += 1;
+            self.proposals.insert(
+                proposal_id,
+                Proposal {
+                    proposer,
+                    calls: calls.clone(),
+                    start_block,
+                    end_block,
+                    for_votes: 0,
+                    against_votes: 0,
+                    abstain_votes: 0,
+                    eta: None,
+                    canceled: false,
+                    executed: false,
+                },
+            );
+            self.env().emit_event(ProposalCreated {
+                proposal_id,
+                proposer,
+                calls,
+                start_block,
+                end_block,
+                description,
+            });
+            Ok(proposal_id)
+        }
+
+        /// Casts the caller's votes at the start of voting on `proposal_id`.
+        ///
+        /// On success a `VoteCast` event is emitted.
+        ///
+        /// # Errors
+        ///
+        /// Returns `ProposalNotFound` error if there is no such proposal.
+        ///
+        /// Returns `ProposalNotActive` error if the proposal is not open for votes.
+        ///
+        /// Returns `AlreadyVoted` error if the caller already voted on it.
+        #[ink(message)]
+        pub fn cast_vote(
+            &mut self,
+            proposal_id: ProposalId,
+            support: VoteType,
+        ) -> Result<()> {
+            if self.state(proposal_id)? != ProposalState::Active {
+                return Err(Error::ProposalNotActive)
+            }
+            let voter = self.env().caller();
+            if self.receipts.contains_key(&(proposal_id, voter)) {
+                return Err(Error::AlreadyVoted)
+            }
+            let token = self.token;
+            let proposal = self
+                .proposals
+                .get_mut(&proposal_id)
+                .ok_or(Error::ProposalNotFound)?;
+            let weight = ext::get_past_votes(token, voter, proposal.start_block);
+            let tally = match support {
+                VoteType::Against => &mut proposal.against_votes,
+                VoteType::For => &mut proposal.for_votes,
+                VoteType::Abstain => &mut proposal.abstain_votes,
+            };
+            *tally = tally.saturating_add(weight);
+            self.receipts.insert((proposal_id, voter), support);
+            self.env().emit_event(VoteCast {
+                voter,
+                proposal_id,
+                support,
+                weight,
+            });
+            Ok(())
+        }
+
+        /// Puts a passed proposal in the timelock, after which it can be executed
+        /// once `timelock_delay` has passed.
+        ///
+        /// On success a `ProposalQueued` event is emitted.
+        ///
+        /// # Errors
+        ///
+        /// Returns `ProposalNotFound` error if there is no such proposal.
+        ///
+        /// Returns `ProposalNotSucceeded` error if voting has not ended with the
+        /// proposal passed, or if it is already queued.
+        #[ink(message)]
+        pub fn queue(&mut self, proposal_id: ProposalId) -> Result<()> {
+            if self.state(proposal_id)? != ProposalState::Succeeded {
+                return Err(Error::ProposalNotSucceeded)
+            }
+            let eta = self.env().block_timestamp() + self.timelock_delay;
+            let proposal = self
+                .proposals
+                .get_mut(&proposal_id)
+                .ok_or(Error::ProposalNotFound)?;
+            proposal.eta = Some(eta);
+            self.env().emit_event(ProposalQueued { proposal_id, eta });
+            Ok(())
+        }
+
+        /// Makes the calls of a queued proposal whose timelock has expired.
+        ///
+        /// On success a `ProposalExecuted` event is emitted.
+        ///
+        /// # Errors
+        ///
+        /// Returns `ProposalNotFound` error if there is no such proposal.
+        ///
+        /// Returns `ProposalNotQueued` error if the proposal is not queued, or its
+        /// grace period is over.
+        ///
+        /// Returns `TimelockNotExpired` error if the timelock has not expired yet.
+        ///
+        /// # Panics
+        ///
+        /// If one of the calls traps or returns an `Err`, which reverts the calls
+        /// made before it.
+        #[ink(message)]
+        pub fn execute(&mut self, proposal_id: ProposalId) -> Result<()> {
+            if self.state(proposal_id)? != ProposalState::Queued {
+                return Err(Error::ProposalNotQueued)
+            }
+            let now = self.env().block_timestamp();
+            let proposal = self
+                .proposals
+                .get_mut(&proposal_id)
+                .ok_or(Error::ProposalNotFound)?;
+            if proposal.eta.map_or(true, |eta| now < eta) {
+                return Err(Error::TimelockNotExpired)
+            }
+            proposal.executed = true;
+            for call in &proposal.calls {
+                assert!(ext::invoke(call), "proposal call failed");
+            }
+            self.env().emit_event(ProposalExecuted { proposal_id });
+            Ok(())
+        }
+
+        /// Withdraws `proposal_id`, which then can no longer be voted on or
+        /// executed.
+        ///
+        /// On success a `ProposalCanceled` event is emitted.
+        ///
+        /// # Errors
+        ///
+        /// Returns `ProposalNotFound` error if there is no such proposal.
+        ///
+        /// Returns `CallerNotProposer` error if the caller did not create it.
+        ///
+        /// Returns `ProposalClosed` error if it was already executed or canceled.
+        #[ink(message)]
+        pub fn cancel(&mut self, proposal_id: ProposalId) -> Result<()> {
+            let caller = self.env().caller();
+            let proposal = self
+                .proposals
+                .get_mut(&proposal_id)
+                .ok_or(Error::ProposalNotFound)?;
+            if proposal.proposer != caller {
+                return Err(Error::CallerNotProposer)
+            }
+            if proposal.executed || proposal.canceled {
+                return Err(Error::ProposalClosed)
+            }
+            proposal.canceled = true;
+            self.env().emit_event(ProposalCanceled { proposal_id });
+            Ok(())
+        }
+
+        /// Returns `true` if enough votes were cast on `proposal` and most of the
+        /// ones taking sides are in favour.
+        fn is_passed(&self, proposal: &Proposal) -> bool {
+            let total_supply =
+                ext::get_past_total_supply(self.token, proposal.start_block);
+            let turnout = proposal.for_votes.saturating_add(proposal.abstain_votes);
+            turnout.saturating_mul(100) >= total_supply.saturating_mul(self.quorum)
+                && proposal.for_votes > proposal.against_votes
+        }
+    }
+
+    /// Calls to the voting token and to the contracts governed by proposals.
+    #[cfg(not(test))]
+    mod ext {
+        use super::{
+            AccountId,
+            Balance,
+            BlockNumber,
+            ProposalCall,
+        };
+        use erc20::Erc20;
+        use ink_env::call::{
+            build_call,
+            utils::ReturnType,
+            FromAccountId,
+        };
+
+        /// Returns the votes of `account` in `token` at the end of `block`, or zero
+        /// if they cannot be queried.
+        pub fn get_past_votes(
+            token: AccountId,
+            account: AccountId,
+            block: BlockNumber,
+        ) -> Balance {
+            let token: Erc20 = FromAccountId::from_account_id(token);
+            token.get_past_votes(account, block).unwrap_or(0)
+        }
+
+        /// Returns the total supply of `token` at the end of `block`, or zero if
+        /// it cannot be queried.
+        pub fn get_past_total_supply(token: AccountId, block: BlockNumber) -> Balance {
+            let token: Erc20 = FromAccountId::from_account_id(token);
+            token.get_past_total_supply(block).unwrap_or(0)
+        }
+
+        /// Whether a called message succeeded, read from its SCALE encoded output.
+        struct CallOutcome(bool);
+
+        impl scale::Decode for CallOutcome {
+            fn decode<I: scale::Input>(
+                input: &mut I,
+            ) -> core::result::Result<Self, scale::Error> {
+                // Messages without a return value write no output at all.
+                if input.remaining_len()? == Some(0) {
+                    return Ok(CallOutcome(true))
+                }
+                // A `Result` is encoded with a leading `0` for `Ok`.
+                Ok(CallOutcome(input.read_byte()? == 0))
+            }
+        }
+
+        /// Makes `call` and returns `true` if it neither trapped nor returned an
+        /// `Err`.
+        pub fn invoke(call: &ProposalCall) -> bool {
+            build_call::<ink_env::DefaultEnvironment>()
+                .callee(call.callee)
+                .exec_input(call.execution_input())
+                .returns::<ReturnType<CallOutcome>>()
+                .fire()
+                .map_or(false, |outcome| outcome.0)
+        }
+    }
+
+    /// In-memory stand-ins for the voting token and the governed contracts.
+    ///
+    /// The off-chain environment cannot dispatch calls to other contracts, so
+    /// unit tests keep the vote checkpoints of the token here and record the
+    /// calls made by executed proposals, along with the input they would send,
+    /// instead.
+    #[cfg(test)]
+    mod ext {
+        use super::{
+            AccountId,
+            Balance,
+            BlockNumber,
+            ProposalCall,
+        };
+        use std::{
+            cell::RefCell,
+            collections::{
+                BTreeMap,
+                HashMap,
+                HashSet,
+            },
+        };
+
+        /// Values set from a block on, by block.
+        type Checkpoints = BTreeMap<BlockNumber, Balance>;
+
+        thread_local! {
+            static VOTES: RefCell<HashMap<(AccountId, AccountId), Checkpoints>> =
+                RefCell::new(HashMap::new());
+            static TOTAL_SUPPLY: RefCell<HashMap<AccountId, Checkpoints>> =
+                RefCell::new(HashMap::new());
+            static FAILING: RefCell<HashSet<AccountId>> = RefCell::new(HashSet::new());
+            static INVOKED: RefCell<Vec<ProposalCall>> = RefCell::new(Vec::new());
+            static SENT: RefCell<Vec<(AccountId, Vec<u8>)>> = RefCell::new(Vec::new());
+        }
+
+        /// Returns the last value set at or before `block`.
+        fn value_at(checkpoints: Option<&Checkpoints>, block: BlockNumber) -> Balance {
+            checkpoints
+                .and_then(|checkpoints| checkpoints.range(..=block).next_back())
+                .map_or(0, |(_, value)| *value)
+        }
+
+        /// Sets the votes of `account` in `token` from `block` on.
+        pub fn set_votes(
+            token: AccountId,
+            account: AccountId,
+            block: BlockNumber,
+            votes: Balance,
+        ) {
+            VOTES.with(|checkpoints| {
+                checkpoints
+                    .borrow_mut()
+                    .entry((token, account))
+                    .or_default()
+                    .insert(block, votes);
+            })
+        }
+
+        /// Sets the total supply of `token` from `block` on.
+        pub fn set_total_supply(token: AccountId, block: BlockNumber, supply: Balance) {
+            TOTAL_SUPPLY.with(|checkpoints| {
+                checkpoints
+                    .borrow_mut()
+                    .entry(token)
+                    .or_default()
+                    .insert(block, supply);
+            })
+        }
+
+        /// Makes all calls to `callee` fail.
+        pub fn fail_calls_to(callee: AccountId) {
+            FAILING.with(|failing| {
+                failing.borrow_mut().insert(callee);
+            })
+        }
+
+        /// Returns the calls made so far.
+        pub fn invoked() -> Vec<ProposalCall> {
+            INVOKED.with(|invoked| invoked.borrow().clone())
+        }
+
+        /// Returns the callee and the encoded input of every call made so far.
+        pub fn sent() -> Vec<(AccountId, Vec<u8>)> {
+            SENT.with(|sent| sent.borrow().clone())
+        }
+
+        pub fn get_past_votes(
+            token: AccountId,
+            account: AccountId,
+            block: BlockNumber,
+        ) -> Balance {
+            VOTES.with(|checkpoints| {
+                value_at(checkpoints.borrow().get(&(token, account)), block)
+            })
+        }
+
+        pub fn get_past_total_supply(token: AccountId, block: BlockNumber) -> Balance {
+            TOTAL_SUPPLY
+                .with(|checkpoints| value_at(checkpoints.borrow().get(&token), block))
+        }
+
+        pub fn invoke(call: &ProposalCall) -> bool {
+            if FAILING.with(|failing| failing.borrow().contains(&call.callee)) {
+                return false
+            }
+            INVOKED.with(|invoked| invoked.borrow_mut().push(call.clone()));
+            let input = scale::Encode::encode(&call.execution_input());
+            SENT.with(|sent| sent.borrow_mut().push((call.callee, input)));
+            true
+        }
+    }
+
+    /// Unit tests.
+    #[cfg(test)]
+    mod tests {
+        /// Imports all the definitions from the outer scope so we can use them here.
+        use super::*;
+
+        type Event = <Governor as ::ink_lang::BaseEvent>::Type;
+
+        use ink_lang as ink;
+        use kyc_registry::KycTier;
+        use lending_pool::{
+            lending_pool::Error as PoolError,
+            LendingPool,
+        };
+
+        const TOKEN: [u8; 32] = [0xAA; 32];
+        const POOL: [u8; 32] = [0xBB; 32];
+        const DOT: [u8; 32] = [0xDD; 32];
+        const STRATEGY: [u8; 32] = [0xEE; 32];
+
+        /// The selectors pinned on the governed `LendingPool` setters.
+        const SET_CLOSE_FACTOR: [u8; 4] = [0xC1, 0x05, 0xEF, 0xAC];
+        const SET_RESERVE_FACTOR: [u8; 4] = [0x60, 0xF0, 0x00, 0x01];
+        const SET_LIQUIDATION_BONUS: [u8; 4] = [0x60, 0xF0, 0x00, 0x02];
+        const SET_BORROW_CAP: [u8; 4] = [0x60, 0xF0, 0x00, 0x03];
+        const SET_RESERVE_INTEREST_RATE_STRATEGY: [u8; 4] = [0x60, 0xF0, 0x00, 0x04];
+
+        /// Voting starts 2 blocks after a proposal and lasts 5 blocks.
+        const VOTING_DELAY: BlockNumber = 2;
+        const VOTING_PERIOD: BlockNumber = 5;
+
+        fn default_accounts(
+        ) -> ink_env::test::DefaultAccounts<ink_env::DefaultEnvironment> {
+            ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts")
+        }
+
+        fn contract_id() -> AccountId {
+            ink_env::account_id::<ink_env::DefaultEnvironment>()
+                .unwrap_or([0x0; 32].into())
+        }
+
+        /// Pushes a new execution context so that `caller` calls the governor.
+        fn set_caller(caller: AccountId) {
+            // Create call.
+            let mut data =
+                ink_env::test::CallData::new(ink_env::call::Selector::new([0x00; 4]));
+            data.push_arg(&caller);
+            ink_env::test::push_execution_context::<ink_env::DefaultEnvironment>(
+                caller,
+                contract_id(),
+                1000000,
+                1000000,
+                data,
+            );
+        }
+
+        fn block_number() -> BlockNumber {
+            ink_env::block_number::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get block number")
+        }
+
+        fn block_timestamp() -> Timestamp {
+            ink_env::block_timestamp::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get block timestamp")
+        }
+
+        fn advance_blocks(count: BlockNumber) {
+            for _ in 0..count {
+                ink_env::test::advance_block::<ink_env::DefaultEnvironment>()
+                    .expect("Cannot advance block");
+            }
+        }
+
+        /// Returns how far a block moves the clock.
+        fn block_time() -> Timestamp {
+            let start = block_timestamp();
+            advance_blocks(1);
+            block_timestamp() - start
+        }
+
+        fn emitted_events() -> Vec<Event> {
+            ink_env::test::recorded_events()
+                .map(|event| {
+                    <Event as scale::Decode>::decode(&mut &event.data[..])
+                        .expect("encountered invalid contract event data buffer")
+                })
+                .collect()
+        }
+
+        /// A call setting the close factor of the pool to 40%.
+        fn set_close_factor_call() -> ProposalCall {
+            let close_factor: Balance = 40;
+            ProposalCall {
+                callee: POOL.into(),
+                selector: SET_CLOSE_FACTOR,
+                input: scale::Encode::encode(&close_factor),
+            }
+        }
+
+        /// Creates a governor with a proposal threshold of 10, a quorum of 10%, a
+        /// timelock of 3 blocks and a grace period of 10 blocks, where Alice, Bob
+        /// and Charlie hold 600, 300 and 100 of the 1000 votes.
+        fn setup() -> Governor {
+            let accounts = default_accounts();
+            ext::set_votes(TOKEN.into(), accounts.alice, 0, 600);
+            ext::set_votes(TOKEN.into(), accounts.bob, 0, 300);
+            ext::set_votes(TOKEN.into(), accounts.charlie, 0, 100);
+            ext::set_total_supply(TOKEN.into(), 0, 1000);
+            let block_time = block_time();
+            Governor::new(
+                TOKEN.into(),
+                VOTING_DELAY,
+                VOTING_PERIOD,
+                10,
+                10,
+                3 * block_time,
+                10 * block_time,
+            )
+        }
+
+        /// Creates a proposal by Alice and advances to the start of voting.
+        fn propose_and_start(governor: &mut Governor) -> ProposalId {
+            let proposal_id = governor
+                .propose(vec![set_close_factor_call()], "Lower close factor".into())
+                .expect("proposal must be accepted");
+            advance_blocks(VOTING_DELAY + 1);
+            proposal_id
+        }
+
+        fn vote(governor: &mut Governor, voter: AccountId, support: VoteType) {
+            set_caller(voter);
+            assert_eq!(governor.cast_vote(0, support), Ok(()));
+            ink_env::test::pop_execution_context();
+        }
+
+        #[ink::test]
+        fn new_works() {
+            let governor = setup();
+            assert_eq!(governor.token(), TOKEN.into());
+            assert_eq!(governor.voting_delay(), VOTING_DELAY);
+            assert_eq!(governor.voting_period(), VOTING_PERIOD);
+            assert_eq!(governor.proposal_threshold(), 10);
+            assert_eq!(governor.quorum(), 10);
+            assert_eq!(governor.timelock_delay(), 3 * block_time());
+            assert_eq!(governor.grace_period(), 10 * block_time());
+            assert_eq!(governor.state(0), Err(Error::ProposalNotFound));
+        }
+
+        #[ink::test]
+        #[should_panic(expected = "quorum must not be above 100")]
+        fn quorum_above_100_is_rejected() {
+            Governor::new(TOKEN.into(), 0, 1, 0, 101, 0, 0);
+        }
+
+        #[ink::test]
+        fn propose_works() {
+            let mut governor = setup();
+            let accounts = default_accounts();
+            let block = block_number();
+            assert_eq!(
+                governor
+                    .propose(vec![set_close_factor_call()], "Lower close factor".into()),
+                Ok(0)
+            );
+            let proposal = governor.proposal(0).expect("proposal must exist");
+            assert_eq!(proposal.proposer, accounts.alice);
+            assert_eq!(proposal.calls, vec![set_close_factor_call()]);
+            assert_eq!(proposal.start_block, block + VOTING_DELAY);
+            assert_eq!(proposal.end_block, block + VOTING_DELAY + VOTING_PERIOD);
+            assert_eq!(governor.state(0), Ok(ProposalState::Pending));
+
+            let emitted_events = emitted_events();
+            assert_eq!(emitted_events.len(), 1);
+            match &emitted_events[0] {
+                Event::ProposalCreated(ProposalCreated {
+                    proposal_id,
+                    proposer,
+                    description,
+                    ..
+                }) => {
+                    assert_eq!(*proposal_id, 0);
+                    assert_eq!(*proposer, accounts.alice);
+                    assert_eq!(description, "Lower close factor");
+                }
+                _ => panic!("encountered unexpected event kind"),
+            }
+        }
+
+        #[ink::test]
+        fn propose_rejects_invalid_proposals() {
+            let mut governor = setup();
+            let accounts = default_accounts();
+            assert_eq!(
+                governor.propose(Vec::new(), String::new()),
+                Err(Error::EmptyProposal)
+            );
+
+            // Votes gained in the current block do not count yet.
+            ext::set_votes(TOKEN.into(), accounts.django, block_number(), 10);
+            set_caller(accounts.django);
+            assert_eq!(
+                governor.propose(vec![set_close_factor_call()], String::new()),
+                Err(Error::BelowProposalThreshold)
+            );
+            advance_blocks(1);
+            assert_eq!(
+                governor.propose(vec![set_close_factor_call()], String::new()),
+                Ok(0)
+            );
+            ink_env::test::pop_execution_context();
+        }
+
+        #[ink::test]
+        fn cast_vote_works() {
+            let mut governor = setup();
+            let accounts = default_accounts();
+            let proposal_id = propose_and_start(&mut governor);
+            assert_eq!(governor.state(proposal_id), Ok(ProposalState::Active));
+
+            vote(&mut governor, accounts.alice, VoteType::For);
+            vote(&mut governor, accounts.bob, VoteType::Against);
+            vote(&mut governor, accounts.charlie, VoteType::Abstain);
+
+            let proposal = governor.proposal(proposal_id).expect("proposal must exist");
+            assert_eq!(proposal.for_votes, 600);
+            assert_eq!(proposal.against_votes, 300);
+            assert_eq!(proposal.abstain_votes, 100);
+            assert_eq!(
+                governor.receipt(proposal_id, accounts.bob),
+                Some(VoteType::Against)
+            );
+            assert_eq!(governor.receipt(proposal_id, accounts.django), None);
+
+            match emitted_events().last() {
+                Some(Event::VoteCast(VoteCast {
+                    voter,
+                    proposal_id: voted_on,
+                    support,
+                    weight,
+                })) => {
+                    assert_eq!(*voter, accounts.charlie);
+                    assert_eq!(*voted_on, proposal_id);
+                    assert_eq!(*support, VoteType::Abstain);
+                    assert_eq!(*weight, 100);
+                }
+                _ => panic!("encountered unexpected event kind"),
+            }
+        }
+
+        #[ink::test]
+        fn cast_vote_uses_votes_at_start_of_voting() {
+            let mut governor = setup();
+            let accounts = default_accounts();
+            let proposal_id = propose_and_start(&mut governor);
+
+            // Votes gained after voting started are ignored.
+            ext::set_votes(TOKEN.into(), accounts.django, block_number(), 500);
+            vote(&mut governor, accounts.django, VoteType::Against);
+            let proposal = governor.proposal(proposal_id).expect("proposal must exist");
+            assert_eq!(proposal.against_votes, 0);
+        }
+
+        #[ink::test]
+        fn cast_vote_rejects_invalid_votes() {
+            let mut governor = setup();
+            assert_eq!(
+                governor.cast_vote(0, VoteType::For),
+                Err(Error::ProposalNotFound)
+            );
+            let proposal_id = governor
+                .propose(vec![set_close_factor_call()], String::new())
+                .expect("proposal must be accepted");
+            assert_eq!(
+                governor.cast_vote(proposal_id, VoteType::For),
+                Err(Error::ProposalNotActive)
+            );
+
+            advance_blocks(VOTING_DELAY + 1);
+            assert_eq!(governor.cast_vote(proposal_id, VoteType::For), Ok(()));
+            assert_eq!(
+                governor.cast_vote(proposal_id, VoteType::Against),
+                Err(Error::AlreadyVoted)
+            );
+
+            advance_blocks(VOTING_PERIOD);
+            assert_eq!(governor.state(proposal_id), Ok(ProposalState::Succeeded));
+            assert_eq!(
+                governor.cast_vote(proposal_id, VoteType::For),
+                Err(Error::ProposalNotActive)
+            );
+        }
+
+        #[ink::test]
+        fn proposal_without_majority_is_defeated() {
+            let mut governor = setup();
+            let accounts = default_accounts();
+            let proposal_id = propose_and_start(&mut governor);
+            vote(&mut governor, accounts.bob, VoteType::For);
+            vote(&mut governor, accounts.alice, VoteType::Against);
+            advance_blocks(VOTING_PERIOD);
+
+            assert_eq!(governor.state(proposal_id), Ok(ProposalState::Defeated));
+            assert_eq!(
+                governor.queue(proposal_id),
+                Err(Error::ProposalNotSucceeded)
+            );
+        }
+
+        #[ink::test]
+        fn proposal_without_quorum_is_defeated() {
+            let mut governor = setup();
+            let accounts = default_accounts();
+            ext::set_votes(TOKEN.into(), accounts.django, 0, 99);
+            let proposal_id = propose_and_start(&mut governor);
+            // 99 of 1000 votes are just below the quorum of 10%.
+            vote(&mut governor, accounts.django, VoteType::For);
+            advance_blocks(VOTING_PERIOD);
+
+            assert_eq!(governor.state(proposal_id), Ok(ProposalState::Defeated));
+        }
+
+        #[ink::test]
+        fn abstain_votes_count_towards_quorum() {
+            let mut governor = setup();
+            let accounts = default_accounts();
+            ext::set_votes(TOKEN.into(), accounts.django, 0, 1);
+            let proposal_id = propose_and_start(&mut governor);
+            vote(&mut governor, accounts.django, VoteType::For);
+            vote(&mut governor, accounts.charlie, VoteType::Abstain);
+            advance_blocks(VOTING_PERIOD);
+
+            assert_eq!(governor.state(proposal_id), Ok(ProposalState::Succeeded));
+        }
+
+        #[ink::test]
+        fn proposal_is_executed_after_timelock() {
+            let mut governor = setup();
+            let accounts = default_accounts();
+            let proposal_id = propose_and_start(&mut governor);
+            vote(&mut governor, accounts.alice, VoteType::For);
+            vote(&mut governor, accounts.bob, VoteType::Against);
+            assert_eq!(
+                governor.queue(proposal_id),
+                Err(Error::ProposalNotSucceeded)
+            );
+            advance_blocks(VOTING_PERIOD);
+
+            assert_eq!(governor.queue(proposal_id), Ok(()));
+            let eta = block_timestamp() + governor.timelock_delay();
+            assert_eq!(
+                governor
+                    .proposal(proposal_id)
+                    .and_then(|proposal| proposal.eta),
+                Some(eta)
+            );
+            assert_eq!(governor.state(proposal_id), Ok(ProposalState::Queued));
+            assert_eq!(
+                governor.queue(proposal_id),
+                Err(Error::ProposalNotSucceeded)
+            );
+
+            // The calls can only be made once the timelock expired.
+            assert_eq!(
+                governor.execute(proposal_id),
+                Err(Error::TimelockNotExpired)
+            );
+            advance_blocks(2);
+            assert_eq!(
+                governor.execute(proposal_id),
+                Err(Error::TimelockNotExpired)
+            );
+            assert!(ext::invoked().is_empty());
+            advance_blocks(1);
+
+            assert_eq!(governor.execute(proposal_id), Ok(()));
+            assert_eq!(ext::invoked(), vec![set_close_factor_call()]);
+            assert_eq!(governor.state(proposal_id), Ok(ProposalState::Executed));
+            assert_eq!(governor.execute(proposal_id), Err(Error::ProposalNotQueued));
+
+            match emitted_events().last() {
+                Some(Event::ProposalExecuted(ProposalExecuted {
+                    proposal_id: executed,
+                })) => assert_eq!(*executed, proposal_id),
+                _ => panic!("encountered unexpected event kind"),
+            }
+        }
+
+        /// Returns a call to the pool message with `selector` taking `args`.
+        fn pool_call<T: scale::Encode>(selector: [u8; 4], args: T) -> ProposalCall {
+            ProposalCall {
+                callee: POOL.into(),
+                selector,
+                input: scale::Encode::encode(&args),
+            }
+        }
+
+        /// Decodes the arguments of a governed setter from `input`.
+        fn decode_args<T: scale::Decode>(input: &[u8]) -> T {
+            <T as scale::Decode>::decode(&mut &input[..])
+                .expect("proposal call input must decode")
+        }
+
+        /// Calls the message of `pool` that `input`, as sent by the governor,
+        /// selects.
+        fn dispatch(pool: &mut LendingPool, input: &[u8]) -> Result<(), PoolError> {
+            let (selector, args) = input.split_at(4);
+            match [selector[0], selector[1], selector[2], selector[3]] {
+                SET_CLOSE_FACTOR => pool.set_close_factor(decode_args(args)),
+                SET_RESERVE_FACTOR => {
+                    let (asset, reserve_factor) = decode_args(args);
+                    pool.set_reserve_factor(asset, reserve_factor)
+                }
+                SET_LIQUIDATION_BONUS => {
+                    let (asset, liquidation_bonus) = decode_args(args);
+                    pool.set_liquidation_bonus(asset, liquidation_bonus)
+                }
+                SET_BORROW_CAP => {
+                    let (tier, cap) = decode_args(args);
+                    pool.set_borrow_cap(tier, cap)
+                }
+                SET_RESERVE_INTEREST_RATE_STRATEGY => {
+                    let (asset, strategy) = decode_args(args);
+                    pool.set_reserve_interest_rate_strategy(asset, strategy)
+                }
+                _ => panic!("proposal call selects no governed pool message"),
+            }
+        }
+
+        #[ink::test]
+        fn executed_calls_reach_governed_pool_setters() {
+            let mut governor = setup();
+            let accounts = default_accounts();
+            let dot = AccountId::from(DOT);
+            let strategy = AccountId::from(STRATEGY);
+            let reserve_factor: Balance = 20;
+            let liquidation_bonus: Balance = 10;
+            let cap: Balance = 5000;
+            let calls = vec![
+                set_close_factor_call(),
+                pool_call(SET_BORROW_CAP, (KycTier::L2, cap)),
+                pool_call(SET_RESERVE_FACTOR, (dot, reserve_factor)),
+                pool_call(SET_LIQUIDATION_BONUS, (dot, liquidation_bonus)),
+                pool_call(SET_RESERVE_INTEREST_RATE_STRATEGY, (dot, strategy)),
+            ];
+            let proposal_id = governor
+                .propose(calls.clone(), "Tune risk parameters".into())
+                .expect("proposal must be accepted");
+            advance_blocks(VOTING_DELAY + 1);
+            vote(&mut governor, accounts.alice, VoteType::For);
+            advance_blocks(VOTING_PERIOD);
+            assert_eq!(governor.queue(proposal_id), Ok(()));
+            advance_blocks(3);
+            assert_eq!(governor.execute(proposal_id), Ok(()));
+
+            // Each call sends its selector followed by its encoded arguments.
+            let sent = ext::sent();
+            assert_eq!(ext::invoked(), calls);
+            assert_eq!(sent.len(), calls.len());
+            for ((callee, input), call) in sent.iter().zip(&calls) {
+                assert_eq!(*callee, AccountId::from(POOL));
+                assert_eq!(input[..4], call.selector);
+                assert_eq!(input[4..], call.input[..]);
+            }
+            let mut expected = SET_BORROW_CAP.to_vec();
+            expected.extend(scale::Encode::encode(&(KycTier::L2, cap)));
+            assert_eq!(sent[1].1, expected);
+
+            // The governor owns the pool, which takes the sent input. Reserves
+            // cannot be listed off-chain, since listing asks the asset for its
+            // decimals, so the reserve setters stop at the missing reserve.
+            set_caller(contract_id());
+            let mut pool = LendingPool::new(accounts.django);
+            let outcomes = sent
+                .iter()
+                .map(|(_, input)| dispatch(&mut pool, input))
+                .collect::<Vec<_>>();
+            assert_eq!(
+                outcomes,
+                vec![
+                    Ok(()),
+                    Ok(()),
+                    Err(PoolError::ReserveNotFound),
+                    Err(PoolError::ReserveNotFound),
+                    Err(PoolError::ReserveNotFound),
+                ]
+            );
+            assert_eq!(pool.close_factor(), 40);
+            assert_eq!(pool.borrow_cap(KycTier::L2), cap);
+        }
+
+        #[ink::test]
+        fn queued_proposal_expires_after_grace_period() {
+            let mut governor = setup();
+            let accounts = default_accounts();
+            let proposal_id = propose_and_start(&mut governor);
+            vote(&mut governor, accounts.alice, VoteType::For);
+            advance_blocks(VOTING_PERIOD);
+            assert_eq!(governor.queue(proposal_id), Ok(()));
+
+            advance_blocks(12);
+            assert_eq!(governor.state(proposal_id), Ok(ProposalState::Queued));
+            advance_blocks(1);
+            assert_eq!(governor.state(proposal_id), Ok(ProposalState::Expired));
+            assert_eq!(governor.execute(proposal_id), Err(Error::ProposalNotQueued));
+            assert!(ext::invoked().is_empty());
+        }
+
+        #[ink::test]
+        #[should_panic(expected = "proposal call failed")]
+        fn failing_call_panics() {
+            let mut governor = setup();
+            let accounts = default_accounts();
+            let proposal_id = propose_and_start(&mut governor);
+            vote(&mut governor, accounts.alice, VoteType::For);
+            advance_blocks(VOTING_PERIOD);
+            assert_eq!(governor.queue(proposal_id), Ok(()));
+            advance_blocks(3);
+
+            ext::fail_calls_to(POOL.into());
+            let _ = governor.execute(proposal_id);
+        }
+
+        #[ink::test]
+        fn cancel_works() {
+            let mut governor = setup();
+            let accounts = default_accounts();
+            let proposal_id = propose_and_start(&mut governor);
+
+            set_caller(accounts.bob);
+            assert_eq!(governor.cancel(proposal_id), Err(Error::CallerNotProposer));
+            ink_env::test::pop_execution_context();
+            assert_eq!(governor.cancel(proposal_id), Ok(()));
+            assert_eq!(governor.state(proposal_id), Ok(ProposalState::Canceled));
+            assert_eq!(governor.cancel(proposal_id), Err(Error::ProposalClosed));
+            assert_eq!(
+                governor.cast_vote(proposal_id, VoteType::For),
+                Err(Error::ProposalNotActive)
+            );
+            assert_eq!(governor.cancel(1), Err(Error::ProposalNotFound));
+        }
+    }
+}
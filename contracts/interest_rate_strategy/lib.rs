@@ -0,0 +1,250 @@
+// Copyright 2021 Bandot Developers.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+use ink_lang as ink;
+
+pub use self::interest_rate_strategy::{
+    DefaultReserveInterestRateStrategy,
+    InterestRateStrategy,
+};
+
+#[ink::contract]
+pub mod interest_rate_strategy {
+    use ink_lang as ink;
+    use lending_math::{
+        ray_div,
+        ray_mul,
+        RAY,
+    };
+
+    /// Trait implemented by all contracts the lending pool can ask for the rates
+    /// of a reserve.
+    ///
+    /// All rates are yearly and expressed in rays.
+    #[ink::trait_definition]
+    pub trait InterestRateStrategy {
+        /// Returns the `(liquidity_rate, variable_borrow_rate, stable_borrow_rate)`
+        /// of a reserve holding `available_liquidity` with `total_debt` borrowed.
+        #[ink(message)]
+        fn calculate_interest_rates(
+            &self,
+            available_liquidity: Balance,
+            total_debt: Balance,
+        ) -> (Balance, Balance, Balance);
+    }
+
+    /// An interest rate strategy whose borrow rate rises slowly up to an optimal
+    /// utilization and steeply after it.
+    ///
+    /// Below the optimal utilization the variable borrow rate grows linearly from
+    /// `base_rate` to `base_rate + slope1`; above it, it grows by up to another
+    /// `slope2` until the reserve is fully borrowed.
+    #[ink(storage)]
+    pub struct DefaultReserveInterestRateStrategy {
+        /// The utilization, in rays, at which the curve switches to `slope2`.
+        optimal_utilization: Balance,
+        /// The variable borrow rate at zero utilization.
+        base_rate: Balance,
+        /// The rate added between zero and optimal utilization.
+        slope1: Balance,
+        /// The rate added between optimal and full utilization.
+        slope2: Balance,
+    }
+
+    impl DefaultReserveInterestRateStrategy {
+        /// Creates a new strategy with the given curve, all values in rays.
+        ///
+        /// # Panics
+        ///
+        /// If `optimal_utilization` is zero or above one ray.
+        #[ink(constructor)]
+        pub fn new(
+            optimal_utilization: Balance,
+            base_rate: Balance,
+            slope1: Balance,
+            slope2: Balance,
+        ) -> Self {
+            assert!(
+                optimal_utilization > 0 && optimal_utilization <= RAY,
+                "optimal utilization must be within (0, 1]"
+            );
+            Self {
+                optimal_utilization,
+                base_rate,
+                slope1,
+                slope2,
+            }
+        }
+
+        /// Returns the utilization at which the curve switches to `slope2`.
+        #[ink(message)]
+        pub fn optimal_utilization(&self) -> Balance {
+            self.optimal_utilization
+        }
+
+        /// Returns the variable borrow rate at zero utilization.
+        #[ink(message)]
+        pub fn base_rate(&self) -> Balance {
+            self.base_rate
+        }
+
+        /// Returns the rate added between zero and optimal utilization.
+        #[ink(message)]
+        pub fn slope1(&self) -> Balance {
+            self.slope1
+        }
+
+        /// Returns the rate added between optimal and full utilization.
+        #[ink(message)]
+        pub fn slope2(&self) -> Balance {
+            self.slope2
+        }
+    }
+
+    impl InterestRateStrategy for DefaultReserveInterestRateStrategy {
+        /// Returns the `(liquidity_rate, variable_borrow_rate, stable_borrow_rate)`
+        /// of a reserve holding `available_liquidity` with `total_debt` borrowed.
+        ///
+        /// The stable borrow rate never drops below the variable borrow rate at
+        /// optimal utilization, so that locking a rate costs a premium while the
+        /// reserve is underused.
+        #[ink(message)]
+        fn calculate_interest_rates(
+            &self,
+            available_liquidity: Balance,
+            total_debt: Balance,
+        ) -> (Balance, Balance, Balance) {
+            let total = available_liquidity + total_debt;
+            let utilization = if total == 0 {
+                0
+            } else {
+                ray_div(total_debt, total)
+            };
+            let optimal_rate = self.base_rate + self.slope1;
+            let variable_borrow_rate = if utilization > self.optimal_utilization {
+                let excess = ray_div(
+                    utilization - self.optimal_utilization,
+                    RAY - self.optimal_utilization,
+                );
+                optimal_rate + ray_mul(self.slope2, excess)
+            } else {
+                self.base_rate
+                    + ray_mul(self.slope1, ray_div(utilization, self.optimal_utilization))
+            };
+            let stable_borrow_rate = core::cmp::max(variable_borrow_rate, optimal_rate);
+            let liquidity_rate = ray_mul(variable_borrow_rate, utilization);
+            (liquidity_rate, variable_borrow_rate, stable_borrow_rate)
+        }
+    }
+
+    /// Unit tests.
+    #[cfg(test)]
+    mod tests {
+        /// Imports all the definitions from the outer scope so we can use them here.
+        use super::*;
+
+        use ink_lang as ink;
+
+        const PERCENT: Balance = RAY / 100;
+
+        /// Creates a strategy with an 80% optimal utilization, a 1% base rate and
+        /// slopes of 4% and 75%.
+        fn new_strategy() -> DefaultReserveInterestRateStrategy {
+            DefaultReserveInterestRateStrategy::new(
+                80 * PERCENT,
+                PERCENT,
+                4 * PERCENT,
+                75 * PERCENT,
+            )
+        }
+
+        #[ink::test]
+        fn new_works() {
+            let strategy = new_strategy();
+            assert_eq!(strategy.optimal_utilization(), 80 * PERCENT);
+            assert_eq!(strategy.base_rate(), PERCENT);
+            assert_eq!(strategy.slope1(), 4 * PERCENT);
+            assert_eq!(strategy.slope2(), 75 * PERCENT);
+        }
+
+        #[ink::test]
+        #[should_panic(expected = "optimal utilization must be within (0, 1]")]
+        fn zero_optimal_utilization_is_rejected() {
+            DefaultReserveInterestRateStrategy::new(0, PERCENT, PERCENT, PERCENT);
+        }
+
+        #[ink::test]
+        fn empty_reserve_has_base_rate() {
+            let strategy = new_strategy();
+            assert_eq!(
+                strategy.calculate_interest_rates(0, 0),
+                (0, PERCENT, 5 * PERCENT)
+            );
+        }
+
+        #[ink::test]
+        fn zero_utilization_works() {
+            let strategy = new_strategy();
+            assert_eq!(
+                strategy.calculate_interest_rates(1000, 0),
+                (0, PERCENT, 5 * PERCENT)
+            );
+        }
+
+        #[ink::test]
+        fn below_optimal_utilization_uses_slope1() {
+            let strategy = new_strategy();
+            // 40% utilization is half way to the optimal point.
+            assert_eq!(
+                strategy.calculate_interest_rates(600, 400),
+                (ray_mul(3 * PERCENT, 40 * PERCENT), 3 * PERCENT, 5 * PERCENT)
+            );
+        }
+
+        #[ink::test]
+        fn optimal_utilization_works() {
+            let strategy = new_strategy();
+            assert_eq!(
+                strategy.calculate_interest_rates(200, 800),
+                (4 * PERCENT, 5 * PERCENT, 5 * PERCENT)
+            );
+        }
+
+        #[ink::test]
+        fn above_optimal_utilization_uses_slope2() {
+            let strategy = new_strategy();
+            // 90% utilization is half way between the optimal point and 100%.
+            let variable_rate = 5 * PERCENT + 75 * PERCENT / 2;
+            assert_eq!(
+                strategy.calculate_interest_rates(100, 900),
+                (
+                    ray_mul(variable_rate, 90 * PERCENT),
+                    variable_rate,
+                    variable_rate
+                )
+            );
+        }
+
+        #[ink::test]
+        fn full_utilization_works() {
+            let strategy = new_strategy();
+            assert_eq!(
+                strategy.calculate_interest_rates(0, 1000),
+                (80 * PERCENT, 80 * PERCENT, 80 * PERCENT)
+            );
+        }
+    }
+}
@@ -0,0 +1,421 @@
+// Copyright 2021 Bandot Developers.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+use ink_lang as ink;
+
+pub use self::kyc_registry::{
+    KycRegistry,
+    KycTier,
+};
+
+#[ink::contract]
+pub mod kyc_registry {
+    #[cfg(not(feature = "ink-as-dependency"))]
+    use ink_storage::collections::HashMap as StorageHashMap;
+    use ink_storage::traits::{
+        PackedLayout,
+        SpreadLayout,
+    };
+
+    /// The KYC verification levels an account can reach.
+    #[derive(
+        Debug,
+        Copy,
+        Clone,
+        PartialEq,
+        Eq,
+        PartialOrd,
+        Ord,
+        scale::Encode,
+        scale::Decode,
+        SpreadLayout,
+        PackedLayout,
+    )]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink_storage::traits::StorageLayout)
+    )]
+    pub enum KycTier {
+        /// The account is not verified.
+        None,
+        /// Basic verification with email and name.
+        L1,
+        /// Verification with an identity document.
+        L2,
+        /// Full verification including proof of address and income.
+        L3,
+    }
+
+    /// The KYC verification of an account.
+    #[derive(
+        Debug,
+        Copy,
+        Clone,
+        PartialEq,
+        Eq,
+        scale::Encode,
+        scale::Decode,
+        SpreadLayout,
+        PackedLayout,
+    )]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink_storage::traits::StorageLayout)
+    )]
+    pub struct KycRecord {
+        /// The verified tier.
+        pub tier: KycTier,
+        /// The time after which the verification no longer counts.
+        pub expiry: Timestamp,
+    }
+
+    /// A registry of the KYC tier verified for each account.
+    #[ink(storage)]
+    pub struct KycRegistry {
+        /// The account allowed to appoint verifiers.
+        admin: AccountId,
+        /// The accounts allowed to set KYC tiers.
+        verifiers: StorageHashMap<AccountId, bool>,
+        /// Mapping from account to its latest verification.
+        records: StorageHashMap<AccountId, KycRecord>,
+    }
+
+    /// Event emitted when the admin appoints a verifier.
+    #[ink(event)]
+    pub struct VerifierAdded {
+        #[ink(topic)]
+        verifier: AccountId,
+    }
+
+    /// Event emitted when the admin dismisses a verifier.
+    #[ink(event)]
+    pub struct VerifierRemoved {
+        #[ink(topic)]
+        verifier: AccountId,
+    }
+
+    /// Event emitted when a verifier sets the KYC tier of `account`.
+    #[ink(event)]
+    pub struct KycUpdated {
+        #[ink(topic)]
+        account: AccountId,
+        #[ink(topic)]
+        verifier: AccountId,
+        tier: KycTier,
+        expiry: Timestamp,
+    }
+
+    /// The KYC registry error types.
+    #[derive(Debug, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+    pub enum Error {
+        /// Returned if the caller is not the admin.
+        CallerNotAdmin,
+        /// Returned if the caller is not a verifier.
+        CallerNotVerifier,
+        /// Returned if a verification would expire before it is stored.
+        InvalidExpiry,
+    }
+
+    /// The KYC registry result type.
+    pub type Result<T> = core::result::Result<T, Error>;
+
+    impl KycRegistry {
+        /// Creates a new registry administered by the caller.
+        #[ink(constructor)]
+        pub fn new() -> Self {
+            Self {
+                admin: Self::env().caller(),
+                verifiers: StorageHashMap::new(),
+                records: StorageHashMap::new(),
+            }
+        }
+
+        /// Returns the account allowed to appoint verifiers.
+        #[ink(message)]
+        pub fn admin(&self) -> AccountId {
+            self.admin
+        }
+
+        /// Returns `true` if `account` may set KYC tiers.
+        #[ink(message)]
+        pub fn is_verifier(&self, account: AccountId) -> bool {
+            self.verifiers.get(&account).copied().unwrap_or(false)
+        }
+
+        /// Returns the latest verification of `account`, even if it has expired.
+        #[ink(message)]
+        pub fn kyc_of(&self, account: AccountId) -> Option<KycRecord> {
+            self.records.get(&account).copied()
+        }
+
+        /// Returns the KYC tier of `account`.
+        ///
+        /// Returns `KycTier::None` if the account was never verified or its
+        /// verification has expired.
+        #[ink(message)]
+        pub fn tier_of(&self, account: AccountId) -> KycTier {
+            match self.records.get(&account) {
+                Some(record) if record.expiry > self.env().block_timestamp() => {
+                    record.tier
+                }
+                _ => KycTier::None,
+            }
+        }
+
+        /// Hands the registry over to `new_admin`.
+        ///
+        /// # Errors
+        ///
+        /// Returns `CallerNotAdmin` error if the caller is not the admin.
+        #[ink(message)]
+        pub fn transfer_admin(&mut self, new_admin: AccountId) -> Result<()> {
+            self.ensure_admin()?;
+            self.admin = new_admin;
+            Ok(())
+        }
+
+        /// Allows `verifier` to set KYC tiers.
+        ///
+        /// On success a `VerifierAdded` event is emitted.
+        ///
+        /// # Errors
+        ///
+        /// Returns `CallerNotAdmin` error if the caller is not the admin.
+        #[ink(message)]
+        pub fn add_verifier(&mut self, verifier: AccountId) -> Result<()> {
+            self.ensure_admin()?;
+            self.verifiers.insert(verifier, true);
+            self.env().emit_event(VerifierAdded { verifier });
+            Ok(())
+        }
+
+        /// Stops `verifier` from setting KYC tiers.
+        ///
+        /// Tiers it has already set are kept.
+        ///
+        /// On success a `VerifierRemoved` event is emitted.
+        ///
+        /// # Errors
+        ///
+        /// Returns `CallerNotAdmin` error if the caller is not the admin.
+        #[ink(message)]
+        pub fn remove_verifier(&mut self, verifier: AccountId) -> Result<()> {
+            self.ensure_admin()?;
+            self.verifiers.take(&verifier);
+            self.env().emit_event(VerifierRemoved { verifier });
+            Ok(())
+        }
+
+        /// Sets the KYC tier of `account` until `expiry`.
+        ///
+        /// Setting `KycTier::None` revokes the verification.
+        ///
+        /// On success a `KycUpdated` event is emitted.
+        ///
+        /// # Errors
+        ///
+        /// Returns `CallerNotVerifier` error if the caller is not a verifier.
+        ///
+        /// Returns `InvalidExpiry` error if `expiry` is not in the future.
+        #[ink(message)]
+        pub fn set_kyc(
+            &mut self,
+            account: AccountId,
+            tier: KycTier,
+            expiry: Timestamp,
+        ) -> Result<()> {
+            let verifier = self.env().caller();
+            if !self.is_verifier(verifier) {
+                return Err(Error::CallerNotVerifier)
+            }
+            if expiry <= self.env().block_timestamp() {
+                return Err(Error::InvalidExpiry)
+            }
+            self.records.insert(account, KycRecord { tier, expiry });
+            self.env().emit_event(KycUpdated {
+                account,
+                verifier,
+                tier,
+                expiry,
+            });
+            Ok(())
+        }
+
+        /// Returns `CallerNotAdmin` error if the caller is not the admin.
+        fn ensure_admin(&self) -> Result<()> {
+            if self.env().caller() != self.admin {
+                return Err(Error::CallerNotAdmin)
+            }
+            Ok(())
+        }
+    }
+
+    /// Unit tests.
+    #[cfg(test)]
+    mod tests {
+        /// Imports all the definitions from the outer scope so we can use them here.
+        use super::*;
+
+        type Event = <KycRegistry as ::ink_lang::BaseEvent>::Type;
+
+        use ink_lang as ink;
+
+        fn default_accounts(
+        ) -> ink_env::test::DefaultAccounts<ink_env::DefaultEnvironment> {
+            ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts")
+        }
+
+        /// Pushes a new execution context so that `caller` calls the registry.
+        fn set_caller(caller: AccountId) {
+            let callee = ink_env::account_id::<ink_env::DefaultEnvironment>()
+                .unwrap_or([0x0; 32].into());
+            // Create call.
+            let mut data =
+                ink_env::test::CallData::new(ink_env::call::Selector::new([0x00; 4]));
+            data.push_arg(&caller);
+            ink_env::test::push_execution_context::<ink_env::DefaultEnvironment>(
+                caller, callee, 1000000, 1000000, data,
+            );
+        }
+
+        fn now() -> Timestamp {
+            ink_env::block_timestamp::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get block timestamp")
+        }
+
+        /// Creates a registry administered by Alice with Bob as verifier.
+        fn new_registry() -> KycRegistry {
+            let mut registry = KycRegistry::new();
+            assert_eq!(registry.add_verifier(default_accounts().bob), Ok(()));
+            registry
+        }
+
+        #[ink::test]
+        fn new_works() {
+            let registry = KycRegistry::new();
+            let accounts = default_accounts();
+            assert_eq!(registry.admin(), accounts.alice);
+            assert!(!registry.is_verifier(accounts.alice));
+            assert_eq!(registry.tier_of(accounts.alice), KycTier::None);
+        }
+
+        #[ink::test]
+        fn only_admin_manages_verifiers() {
+            let mut registry = new_registry();
+            let accounts = default_accounts();
+            assert!(registry.is_verifier(accounts.bob));
+
+            set_caller(accounts.bob);
+            assert_eq!(
+                registry.add_verifier(accounts.charlie),
+                Err(Error::CallerNotAdmin)
+            );
+            assert_eq!(
+                registry.remove_verifier(accounts.bob),
+                Err(Error::CallerNotAdmin)
+            );
+            ink_env::test::pop_execution_context();
+
+            assert_eq!(registry.remove_verifier(accounts.bob), Ok(()));
+            assert!(!registry.is_verifier(accounts.bob));
+        }
+
+        #[ink::test]
+        fn set_kyc_works() {
+            let mut registry = new_registry();
+            let accounts = default_accounts();
+            let expiry = now() + 1000;
+
+            set_caller(accounts.bob);
+            assert_eq!(
+                registry.set_kyc(accounts.charlie, KycTier::L2, expiry),
+                Ok(())
+            );
+            assert_eq!(registry.tier_of(accounts.charlie), KycTier::L2);
+            assert_eq!(
+                registry.kyc_of(accounts.charlie),
+                Some(KycRecord {
+                    tier: KycTier::L2,
+                    expiry
+                })
+            );
+
+            let emitted_events = ink_env::test::recorded_events().collect::<Vec<_>>();
+            assert_eq!(emitted_events.len(), 2);
+            let decoded_event =
+                <Event as scale::Decode>::decode(&mut &emitted_events[1].data[..])
+                    .expect("encountered invalid contract event data buffer");
+            if let Event::KycUpdated(KycUpdated {
+                account,
+                verifier,
+                tier,
+                expiry: event_expiry,
+            }) = decoded_event
+            {
+                assert_eq!(account, accounts.charlie);
+                assert_eq!(verifier, accounts.bob);
+                assert_eq!(tier, KycTier::L2);
+                assert_eq!(event_expiry, expiry);
+            } else {
+                panic!("encountered unexpected event kind: expected a KycUpdated event")
+            }
+        }
+
+        #[ink::test]
+        fn only_verifiers_set_kyc() {
+            let mut registry = new_registry();
+            let accounts = default_accounts();
+
+            // Not even the admin may verify accounts directly.
+            assert_eq!(
+                registry.set_kyc(accounts.charlie, KycTier::L3, now() + 1000),
+                Err(Error::CallerNotVerifier)
+            );
+            assert_eq!(registry.tier_of(accounts.charlie), KycTier::None);
+        }
+
+        #[ink::test]
+        fn expired_kyc_is_ignored() {
+            let mut registry = new_registry();
+            let accounts = default_accounts();
+
+            set_caller(accounts.bob);
+            assert_eq!(
+                registry.set_kyc(accounts.charlie, KycTier::L1, now()),
+                Err(Error::InvalidExpiry)
+            );
+            assert_eq!(
+                registry.set_kyc(accounts.charlie, KycTier::L1, now() + 1),
+                Ok(())
+            );
+            assert_eq!(registry.tier_of(accounts.charlie), KycTier::L1);
+
+            ink_env::test::advance_block::<ink_env::DefaultEnvironment>()
+                .expect("Cannot advance block");
+            assert_eq!(registry.tier_of(accounts.charlie), KycTier::None);
+            assert!(registry.kyc_of(accounts.charlie).is_some());
+        }
+
+        #[ink::test]
+        fn tiers_are_ordered() {
+            assert!(KycTier::None < KycTier::L1);
+            assert!(KycTier::L1 < KycTier::L2);
+            assert!(KycTier::L2 < KycTier::L3);
+        }
+    }
+}
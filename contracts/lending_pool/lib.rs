@@ -0,0 +1,6299 @@
+// Copyright 2021 Bandot Developers.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+use ink_lang as ink;
+
+pub use self::lending_pool::{
+    FlashLoanMode,
+    FlashLoanReceiver,
+    LendingPool,
+    UserAccountData,
+};
+
+#[ink::contract]
+pub mod lending_pool {
+    use ink_prelude::vec::Vec;
+    #[cfg(not(feature = "ink-as-dependency"))]
+    use ink_storage::collections::{
+        HashMap as StorageHashMap,
+        Vec as StorageVec,
+    };
+    use ink_storage::traits::{
+        PackedLayout,
+        SpreadLayout,
+    };
+    use kyc_registry::KycTier;
+    use lending_math::{
+        calculate_compounded_interest,
+        calculate_linear_interest,
+        checked_mul_div,
+        ray_div,
+        ray_mul,
+        RAY,
+    };
+
+    /// Default percentage of the collateral value that can be borrowed against it.
+    const DEFAULT_LOAN_TO_VALUE: Balance = 75;
+
+    /// Default percentage of the collateral value at which a position can be
+    /// liquidated.
+    const DEFAULT_LIQUIDATION_THRESHOLD: Balance = 80;
+
+    /// Default percentage of a debt that can be covered in one liquidation.
+    const DEFAULT_CLOSE_FACTOR: Balance = 50;
+
+    /// Default percentage of extra collateral a liquidator receives.
+    const DEFAULT_LIQUIDATION_BONUS: Balance = 5;
+
+    /// The most installments a loan agreement can be split into.
+    const MAX_INSTALLMENTS: u32 = 120;
+
+    /// Default fee on flash loans, in basis points of the borrowed amount.
+    const DEFAULT_FLASH_LOAN_PREMIUM: Balance = 9;
+
+    /// Identifier of a loan agreement.
+    pub type LoanId = u64;
+
+    /// How the amount owed under a loan agreement falls due.
+    #[derive(
+        Debug,
+        Copy,
+        Clone,
+        PartialEq,
+        Eq,
+        scale::Encode,
+        scale::Decode,
+        SpreadLayout,
+        PackedLayout,
+    )]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink_storage::traits::StorageLayout)
+    )]
+    pub enum RepaymentSchedule {
+        /// Principal and interest are due at the end of the term.
+        Bullet,
+        /// Principal and interest are split into equal installments.
+        EqualInstallment,
+        /// Interest is split into equal installments and the principal is due
+        /// with the last one.
+        InterestOnly,
+    }
+
+    /// The state of a loan agreement.
+    #[derive(
+        Debug,
+        Copy,
+        Clone,
+        PartialEq,
+        Eq,
+        scale::Encode,
+        scale::Decode,
+        SpreadLayout,
+        PackedLayout,
+    )]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink_storage::traits::StorageLayout)
+    )]
+    pub enum LoanStatus {
+        /// Offered by the delegator and waiting for the borrower to accept.
+        Proposed,
+        /// Withdrawn by the delegator before it was accepted.
+        Cancelled,
+        /// All installments due so far are paid.
+        Active,
+        /// An installment is overdue but still within the grace period.
+        Late,
+        /// An installment is overdue by more than the grace period.
+        Defaulted,
+        /// Everything owed has been paid.
+        Repaid,
+    }
+
+    /// How a flash loan of one asset is settled once the receiver has used it.
+    #[derive(Debug, Copy, Clone, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+    pub enum FlashLoanMode {
+        /// The receiver pays back the amount plus the premium.
+        NoDebt,
+        /// The receiver keeps the amount and the initiator owes it to the pool
+        /// at the variable rate, without a premium.
+        VariableDebt,
+    }
+
+    /// Trait implemented by contracts receiving flash loans from the pool.
+    #[ink::trait_definition]
+    pub trait FlashLoanReceiver {
+        /// Uses the `amounts` of `assets` the pool just sent to the contract and
+        /// returns `true` on success.
+        ///
+        /// Before returning, the contract must approve the pool to take back each
+        /// amount plus its `premiums` entry for the assets borrowed in
+        /// `FlashLoanMode::NoDebt`. `initiator` is the account that asked for the
+        /// loan and `params` are passed through from it.
+        #[ink(message)]
+        fn execute_operation(
+            &mut self,
+            assets: Vec<AccountId>,
+            amounts: Vec<Balance>,
+            premiums: Vec<Balance>,
+            initiator: AccountId,
+            params: Vec<u8>,
+        ) -> bool;
+    }
+
+    /// A fixed-term loan a delegator grants a borrower against its collateral.
+    #[derive(
+        Debug,
+        Clone,
+        PartialEq,
+        Eq,
+        scale::Encode,
+        scale::Decode,
+        SpreadLayout,
+        PackedLayout,
+    )]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink_storage::traits::StorageLayout)
+    )]
+    pub struct LoanAgreement {
+        /// The `Erc20` reserve lent out.
+        pub reserve: AccountId,
+        /// The account whose collateral backs the loan and who earns the interest.
+        pub delegator: AccountId,
+        /// The account receiving the loan.
+        pub borrower: AccountId,
+        /// The amount lent.
+        pub principal: Balance,
+        /// The yearly interest rate in rays.
+        pub apr: Balance,
+        /// The simple interest owed over the whole term.
+        pub interest: Balance,
+        /// The duration of the loan in milliseconds.
+        pub term: Timestamp,
+        /// The number of equal periods the term is split into; ignored for
+        /// `Bullet` loans.
+        pub installments: u32,
+        /// How the amount owed falls due.
+        pub schedule: RepaymentSchedule,
+        /// How long, in milliseconds, an installment may be overdue before the
+        /// loan defaults.
+        pub grace_period: Timestamp,
+        /// The time the borrower accepted the loan.
+        pub start: Timestamp,
+        /// The amount of principal and interest paid so far.
+        pub repaid: Balance,
+        /// The state of the loan, as of the time it was read.
+        pub status: LoanStatus,
+    }
+
+    impl LoanAgreement {
+        /// Returns the principal and interest owed over the whole term.
+        pub fn total_due(&self) -> Balance {
+            self.principal + self.interest
+        }
+
+        /// Returns the principal and interest still to be paid.
+        pub fn outstanding(&self) -> Balance {
+            self.total_due() - self.repaid
+        }
+
+        /// Returns the time installment number `installment` falls due.
+        pub fn due_date(&self, installment: u32) -> Timestamp {
+            self.start + self.term * installment as u64 / self.installments as u64
+        }
+
+        /// Returns the total amount due once installment number `installment` has
+        /// fallen due.
+        pub fn due_by(&self, installment: u32) -> Balance {
+            let last = installment == self.installments;
+            match self.schedule {
+                RepaymentSchedule::Bullet if last => self.total_due(),
+                RepaymentSchedule::Bullet => 0,
+                RepaymentSchedule::EqualInstallment => {
+                    self.total_due() * installment as Balance
+                        / self.installments as Balance
+                }
+                RepaymentSchedule::InterestOnly => {
+                    let interest = self.interest * installment as Balance
+                        / self.installments as Balance;
+                    if last {
+                        interest + self.principal
+                    } else {
+                        interest
+                    }
+                }
+            }
+        }
+
+        /// Returns the part of `repaid` that went to the principal.
+        ///
+        /// Every payment is split between principal and interest pro rata.
+        pub fn principal_part(&self, repaid: Balance) -> Balance {
+            checked_mul_div(repaid, self.principal, self.total_due())
+                .unwrap_or(self.principal)
+        }
+
+        /// Returns the state of the loan at `now`.
+        pub fn status_at(&self, now: Timestamp) -> LoanStatus {
+            match self.status {
+                LoanStatus::Proposed | LoanStatus::Cancelled | LoanStatus::Repaid => {
+                    return self.status
+                }
+                _ => {}
+            }
+            let unpaid = (1..=self.installments)
+                .find(|installment| self.due_by(*installment) > self.repaid)
+                .unwrap_or(self.installments);
+            let due_date = self.due_date(unpaid);
+            if now > due_date + self.grace_period {
+                LoanStatus::Defaulted
+            } else if now > due_date {
+                LoanStatus::Late
+            } else {
+                LoanStatus::Active
+            }
+        }
+    }
+
+    /// The risk parameters and flags of a reserve, packed into one word so that
+    /// the pool reads all of them at once.
+    ///
+    /// | Bits  | Parameter                                  |
+    /// |-------|--------------------------------------------|
+    /// | 0-15  | LTV percentage                             |
+    /// | 16-31 | Liquidation threshold percentage           |
+    /// | 32-47 | Liquidation bonus percentage               |
+    /// | 48-55 | Token decimals                             |
+    /// | 56    | Active                                     |
+    /// | 57    | Frozen                                     |
+    /// | 58    | Borrowing enabled                          |
+    /// | 59    | Stable rate borrowing enabled              |
+    /// | 64-79 | Reserve factor percentage                  |
+    #[derive(
+        Debug,
+        Default,
+        Copy,
+        Clone,
+        PartialEq,
+        Eq,
+        scale::Encode,
+        scale::Decode,
+        SpreadLayout,
+        PackedLayout,
+    )]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink_storage::traits::StorageLayout)
+    )]
+    pub struct ReserveConfiguration {
+        data: u128,
+    }
+
+    const LTV_START: u32 = 0;
+    const LIQUIDATION_THRESHOLD_START: u32 = 16;
+    const LIQUIDATION_BONUS_START: u32 = 32;
+    const DECIMALS_START: u32 = 48;
+    const ACTIVE_BIT: u32 = 56;
+    const FROZEN_BIT: u32 = 57;
+    const BORROWING_ENABLED_BIT: u32 = 58;
+    const STABLE_RATE_ENABLED_BIT: u32 = 59;
+    const RESERVE_FACTOR_START: u32 = 64;
+
+    /// The width of the percentage fields of a `ReserveConfiguration`.
+    const PERCENTAGE_BITS: u32 = 16;
+
+    impl ReserveConfiguration {
+        /// Returns the `bits` wide field starting at bit `start`.
+        fn get(&self, start: u32, bits: u32) -> u128 {
+            (self.data >> start) & ((1 << bits) - 1)
+        }
+
+        /// Overwrites the `bits` wide field starting at bit `start` with the low
+        /// bits of `value`.
+        fn set(&mut self, start: u32, bits: u32, value: u128) {
+            let mask = ((1 << bits) - 1) << start;
+            self.data = (self.data & !mask) | ((value << start) & mask);
+        }
+
+        /// Returns the percentage of the deposit value that can be borrowed
+        /// against it.
+        pub fn ltv(&self) -> Balance {
+            self.get(LTV_START, PERCENTAGE_BITS)
+        }
+
+        pub fn set_ltv(&mut self, ltv: Balance) {
+            self.set(LTV_START, PERCENTAGE_BITS, ltv)
+        }
+
+        /// Returns the percentage of the deposit value at which a position can
+        /// be liquidated.
+        pub fn liquidation_threshold(&self) -> Balance {
+            self.get(LIQUIDATION_THRESHOLD_START, PERCENTAGE_BITS)
+        }
+
+        pub fn set_liquidation_threshold(&mut self, threshold: Balance) {
+            self.set(LIQUIDATION_THRESHOLD_START, PERCENTAGE_BITS, threshold)
+        }
+
+        /// Returns the percentage of extra collateral a liquidator seizes.
+        pub fn liquidation_bonus(&self) -> Balance {
+            self.get(LIQUIDATION_BONUS_START, PERCENTAGE_BITS)
+        }
+
+        pub fn set_liquidation_bonus(&mut self, bonus: Balance) {
+            self.set(LIQUIDATION_BONUS_START, PERCENTAGE_BITS, bonus)
+        }
+
+        /// Returns the number of decimals of the reserve token.
+        pub fn decimals(&self) -> u8 {
+            self.get(DECIMALS_START, 8) as u8
+        }
+
+        pub fn set_decimals(&mut self, decimals: u8) {
+            self.set(DECIMALS_START, 8, decimals.into())
+        }
+
+        /// Returns `true` if the reserve can be used at all.
+        pub fn is_active(&self) -> bool {
+            self.get(ACTIVE_BIT, 1) == 1
+        }
+
+        pub fn set_active(&mut self, active: bool) {
+            self.set(ACTIVE_BIT, 1, active.into())
+        }
+
+        /// Returns `true` if the reserve takes no new deposits or borrows.
+        pub fn is_frozen(&self) -> bool {
+            self.get(FROZEN_BIT, 1) == 1
+        }
+
+        pub fn set_frozen(&mut self, frozen: bool) {
+            self.set(FROZEN_BIT, 1, frozen.into())
+        }
+
+        /// Returns `true` if the reserve can be borrowed.
+        pub fn is_borrowing_enabled(&self) -> bool {
+            self.get(BORROWING_ENABLED_BIT, 1) == 1
+        }
+
+        pub fn set_borrowing_enabled(&mut self, enabled: bool) {
+            self.set(BORROWING_ENABLED_BIT, 1, enabled.into())
+        }
+
+        /// Returns `true` if the reserve can be borrowed at a stable rate.
+        pub fn is_stable_rate_enabled(&self) -> bool {
+            self.get(STABLE_RATE_ENABLED_BIT, 1) == 1
+        }
+
+        pub fn set_stable_rate_enabled(&mut self, enabled: bool) {
+            self.set(STABLE_RATE_ENABLED_BIT, 1, enabled.into())
+        }
+
+        /// Returns the percentage of the interest paid by borrowers that goes to
+        /// the protocol.
+        pub fn reserve_factor(&self) -> Balance {
+            self.get(RESERVE_FACTOR_START, PERCENTAGE_BITS)
+        }
+
+        pub fn set_reserve_factor(&mut self, reserve_factor: Balance) {
+            self.set(RESERVE_FACTOR_START, PERCENTAGE_BITS, reserve_factor)
+        }
+    }
+
+    /// The configuration, contracts and interest state of a reserve.
+    ///
+    /// The indices and rates are as of `last_update_timestamp`; see
+    /// `reserve_normalized_income` and `reserve_normalized_variable_debt` for
+    /// their current values.
+    #[derive(
+        Debug,
+        Copy,
+        Clone,
+        PartialEq,
+        Eq,
+        scale::Encode,
+        scale::Decode,
+        SpreadLayout,
+        PackedLayout,
+    )]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink_storage::traits::StorageLayout)
+    )]
+    pub struct ReserveData {
+        /// The risk parameters and flags of the reserve.
+        pub configuration: ReserveConfiguration,
+        /// The `AToken` representing deposits.
+        pub a_token: AccountId,
+        /// The `StableDebtToken` representing stable rate debt.
+        pub stable_debt_token: AccountId,
+        /// The `VariableDebtToken` representing variable rate debt.
+        pub variable_debt_token: AccountId,
+        /// The `InterestRateStrategy` setting the rates of the reserve.
+        pub interest_rate_strategy: AccountId,
+        /// The interest accrued per deposited token since the reserve was
+        /// listed, in rays.
+        pub liquidity_index: Balance,
+        /// The interest accrued per borrowed token since the reserve was listed,
+        /// in rays.
+        pub variable_borrow_index: Balance,
+        /// The yearly rate earned by depositors, in rays.
+        pub current_liquidity_rate: Balance,
+        /// The yearly rate paid by variable rate borrowers, in rays.
+        pub current_variable_borrow_rate: Balance,
+        /// The yearly rate a new stable rate borrow would pay, in rays.
+        pub current_stable_borrow_rate: Balance,
+        /// The time the indices were last compounded.
+        pub last_update_timestamp: Timestamp,
+    }
+
+    /// An overview of the position of an account across all reserves.
+    ///
+    /// Values are in the base currency of the `PriceOracle` and percentages are
+    /// weighted by the value of the deposits in each reserve.
+    #[derive(Debug, Copy, Clone, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+    pub struct UserAccountData {
+        /// The value of all deposits.
+        pub total_collateral: Balance,
+        /// The value of all debt, including the debt drawn by delegates.
+        pub total_debt: Balance,
+        /// The value that can still be borrowed against the deposits.
+        pub available_borrows: Balance,
+        /// The percentage of the deposit value at which the position can be
+        /// liquidated.
+        pub current_liquidation_threshold: Balance,
+        /// The percentage of the deposit value that can be borrowed against it.
+        pub ltv: Balance,
+        /// The health factor in rays, `Balance::MAX` without debt.
+        pub health_factor: Balance,
+    }
+
+    /// A lending pool for a set of `Erc20` reserve assets.
+    ///
+    /// Deposits in any reserve back debts in all reserves. Values are
+    /// compared in the base currency of the `PriceOracle`.
+    #[ink(storage)]
+    pub struct LendingPool {
+        /// The account allowed to change the pool settings.
+        owner: AccountId,
+        /// The account allowed to list and configure reserves.
+        pool_admin: AccountId,
+        /// The account allowed to pause the pool and freeze reserves.
+        guardian: AccountId,
+        /// Whether all deposits, withdrawals, borrows, repayments and
+        /// liquidations are stopped.
+        paused: bool,
+        /// The `PriceOracle` contract pricing the reserves.
+        oracle: AccountId,
+        /// The `Erc20` contracts that can be deposited and borrowed.
+        reserves: StorageVec<AccountId>,
+        /// Mapping from reserve to its configuration and contracts.
+        reserve_data: StorageHashMap<AccountId, ReserveData>,
+        /// The percentage of a debt that can be covered in one liquidation.
+        close_factor: Balance,
+        /// The fee on flash loans, in basis points of the borrowed amount.
+        flash_loan_premium: Balance,
+        /// The `KycRegistry` contract gating delegated borrows, if any.
+        kyc_registry: Option<AccountId>,
+        /// The `Treasury` contract receiving the reserve factor share of the
+        /// interest, if any.
+        treasury: Option<AccountId>,
+        /// Mapping from KYC tier to the most a borrower of that tier may owe
+        /// against delegated credit, in the base currency.
+        borrow_caps: StorageHashMap<KycTier, Balance>,
+        /// Mapping of the amount which a borrower is still allowed to draw from a
+        /// reserve against a delegator's collateral.
+        credit_allowances: StorageHashMap<(AccountId, AccountId, AccountId), Balance>,
+        /// Mapping of the outstanding amount a borrower has drawn from a reserve
+        /// against a delegator's collateral.
+        delegated_debts: StorageHashMap<(AccountId, AccountId, AccountId), Balance>,
+        /// Mapping of the total amount all borrowers have drawn from a reserve
+        /// against a delegator's collateral.
+        total_delegated_debts: StorageHashMap<(AccountId, AccountId), Balance>,
+        /// Mapping of the total amount a borrower has drawn from a reserve against
+        /// the collateral of delegators.
+        delegated_borrows: StorageHashMap<(AccountId, AccountId), Balance>,
+        /// Mapping from identifier to loan agreement.
+        loans: StorageHashMap<LoanId, LoanAgreement>,
+        /// Mapping from borrower to the loan agreements offered to it.
+        borrower_loans: StorageHashMap<AccountId, Vec<LoanId>>,
+        /// The identifier of the next loan agreement.
+        next_loan_id: LoanId,
+    }
+
+    /// Event emitted when reserve tokens are deposited into the pool.
+    #[ink(event)]
+    pub struct Deposit {
+        #[ink(topic)]
+        reserve: AccountId,
+        #[ink(topic)]
+        user: AccountId,
+        value: Balance,
+    }
+
+    /// Event emitted when reserve tokens are withdrawn from the pool.
+    #[ink(event)]
+    pub struct Withdraw {
+        #[ink(topic)]
+        reserve: AccountId,
+        #[ink(topic)]
+        user: AccountId,
+        value: Balance,
+    }
+
+    /// Event emitted when reserve tokens are borrowed from the pool.
+    #[ink(event)]
+    pub struct Borrow {
+        #[ink(topic)]
+        reserve: AccountId,
+        #[ink(topic)]
+        user: AccountId,
+        value: Balance,
+    }
+
+    /// Event emitted when reserve tokens are borrowed at the stable `rate`.
+    #[ink(event)]
+    pub struct BorrowStable {
+        #[ink(topic)]
+        reserve: AccountId,
+        #[ink(topic)]
+        user: AccountId,
+        value: Balance,
+        rate: Balance,
+    }
+
+    /// Event emitted when a stable rate debt is repaid to the pool.
+    #[ink(event)]
+    pub struct RepayStable {
+        #[ink(topic)]
+        reserve: AccountId,
+        #[ink(topic)]
+        user: AccountId,
+        value: Balance,
+    }
+
+    /// Event emitted when the stable rate of `user` is moved to the market rate.
+    #[ink(event)]
+    pub struct RebalanceStableBorrowRate {
+        #[ink(topic)]
+        reserve: AccountId,
+        #[ink(topic)]
+        user: AccountId,
+    }
+
+    /// Event emitted when a debt is repaid to the pool.
+    #[ink(event)]
+    pub struct Repay {
+        #[ink(topic)]
+        reserve: AccountId,
+        #[ink(topic)]
+        user: AccountId,
+        value: Balance,
+    }
+
+    /// Event emitted when a delegator allows `borrower` to draw up to `value`
+    /// reserve tokens against its collateral.
+    #[ink(event)]
+    pub struct CreditDelegated {
+        #[ink(topic)]
+        reserve: AccountId,
+        #[ink(topic)]
+        delegator: AccountId,
+        #[ink(topic)]
+        borrower: AccountId,
+        value: Balance,
+    }
+
+    /// Event emitted when a borrower draws `value` reserve tokens against a
+    /// delegator's collateral.
+    #[ink(event)]
+    pub struct DelegationUsed {
+        #[ink(topic)]
+        reserve: AccountId,
+        #[ink(topic)]
+        delegator: AccountId,
+        #[ink(topic)]
+        borrower: AccountId,
+        value: Balance,
+    }
+
+    /// Event emitted when a borrower repays debt drawn against a delegator's
+    /// collateral.
+    #[ink(event)]
+    pub struct DelegationRepaid {
+        #[ink(topic)]
+        reserve: AccountId,
+        #[ink(topic)]
+        delegator: AccountId,
+        #[ink(topic)]
+        borrower: AccountId,
+        value: Balance,
+    }
+
+    /// Event emitted when a delegator offers a borrower a loan agreement.
+    #[ink(event)]
+    pub struct LoanProposed {
+        #[ink(topic)]
+        loan_id: LoanId,
+        #[ink(topic)]
+        delegator: AccountId,
+        #[ink(topic)]
+        borrower: AccountId,
+        principal: Balance,
+    }
+
+    /// Event emitted when a delegator withdraws a loan agreement.
+    #[ink(event)]
+    pub struct LoanCancelled {
+        #[ink(topic)]
+        loan_id: LoanId,
+    }
+
+    /// Event emitted when a borrower accepts a loan agreement and receives the
+    /// principal.
+    #[ink(event)]
+    pub struct LoanAccepted {
+        #[ink(topic)]
+        loan_id: LoanId,
+        start: Timestamp,
+    }
+
+    /// Event emitted when `payer` pays `value` towards a loan agreement.
+    #[ink(event)]
+    pub struct LoanRepaid {
+        #[ink(topic)]
+        loan_id: LoanId,
+        #[ink(topic)]
+        payer: AccountId,
+        value: Balance,
+    }
+
+    /// Event emitted when a liquidator covers `debt_to_cover` of the debt of
+    /// `user` and seizes `liquidated_collateral` of its deposit.
+    #[ink(event)]
+    pub struct LiquidationCall {
+        #[ink(topic)]
+        collateral_asset: AccountId,
+        #[ink(topic)]
+        debt_asset: AccountId,
+        #[ink(topic)]
+        user: AccountId,
+        debt_to_cover: Balance,
+        liquidated_collateral: Balance,
+        liquidator: AccountId,
+        receive_underlying: bool,
+    }
+
+    /// Event emitted when `initiator` flash borrows `amount` tokens of `asset`
+    /// for `target`.
+    #[ink(event)]
+    pub struct FlashLoan {
+        #[ink(topic)]
+        target: AccountId,
+        #[ink(topic)]
+        initiator: AccountId,
+        #[ink(topic)]
+        asset: AccountId,
+        amount: Balance,
+        premium: Balance,
+        mode: FlashLoanMode,
+    }
+
+    /// Event emitted when the reserve factor share of the interest accrued in
+    /// `reserve` is deposited for `treasury`.
+    #[ink(event)]
+    pub struct TreasuryAccrued {
+        #[ink(topic)]
+        reserve: AccountId,
+        #[ink(topic)]
+        treasury: AccountId,
+        value: Balance,
+    }
+
+    /// Event emitted when `account` pauses the pool.
+    #[ink(event)]
+    pub struct Paused {
+        account: AccountId,
+    }
+
+    /// Event emitted when `account` unpauses the pool.
+    #[ink(event)]
+    pub struct Unpaused {
+        account: AccountId,
+    }
+
+    /// Event emitted when `asset` is listed as a reserve.
+    #[ink(event)]
+    pub struct ReserveInitialized {
+        #[ink(topic)]
+        asset: AccountId,
+        a_token: AccountId,
+        stable_debt_token: AccountId,
+        variable_debt_token: AccountId,
+        interest_rate_strategy: AccountId,
+    }
+
+    /// Event emitted when the LTV of `asset` changes.
+    #[ink(event)]
+    pub struct LtvChanged {
+        #[ink(topic)]
+        asset: AccountId,
+        ltv: Balance,
+    }
+
+    /// Event emitted when the liquidation threshold of `asset` changes.
+    #[ink(event)]
+    pub struct LiquidationThresholdChanged {
+        #[ink(topic)]
+        asset: AccountId,
+        liquidation_threshold: Balance,
+    }
+
+    /// Event emitted when the liquidation bonus of `asset` changes.
+    #[ink(event)]
+    pub struct LiquidationBonusChanged {
+        #[ink(topic)]
+        asset: AccountId,
+        liquidation_bonus: Balance,
+    }
+
+    /// Event emitted when the reserve factor of `asset` changes.
+    #[ink(event)]
+    pub struct ReserveFactorChanged {
+        #[ink(topic)]
+        asset: AccountId,
+        reserve_factor: Balance,
+    }
+
+    /// Event emitted when borrowing `asset` is enabled or disabled.
+    #[ink(event)]
+    pub struct BorrowingEnabledChanged {
+        #[ink(topic)]
+        asset: AccountId,
+        enabled: bool,
+    }
+
+    /// Event emitted when borrowing `asset` at a stable rate is enabled or
+    /// disabled.
+    #[ink(event)]
+    pub struct StableRateEnabledChanged {
+        #[ink(topic)]
+        asset: AccountId,
+        enabled: bool,
+    }
+
+    /// Event emitted when `asset` is activated or deactivated.
+    #[ink(event)]
+    pub struct ReserveActiveChanged {
+        #[ink(topic)]
+        asset: AccountId,
+        active: bool,
+    }
+
+    /// Event emitted when the `InterestRateStrategy` of `asset` is replaced.
+    #[ink(event)]
+    pub struct ReserveInterestRateStrategyChanged {
+        #[ink(topic)]
+        asset: AccountId,
+        strategy: AccountId,
+    }
+
+    /// Event emitted when `reserve` stops taking new deposits and borrows.
+    #[ink(event)]
+    pub struct ReserveFrozen {
+        #[ink(topic)]
+        reserve: AccountId,
+    }
+
+    /// Event emitted when `reserve` takes new deposits and borrows again.
+    #[ink(event)]
+    pub struct ReserveUnfrozen {
+        #[ink(topic)]
+        reserve: AccountId,
+    }
+
+    /// The lending pool error types.
+    #[derive(Debug, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+    pub enum Error {
+        /// Returned if the caller has deposited less than the requested amount.
+        InsufficientDeposit,
+        /// Returned if the caller's deposit does not cover the resulting debt.
+        InsufficientCollateral,
+        /// Returned if the pool does not hold enough reserve tokens.
+        InsufficientLiquidity,
+        /// Returned if the reserve `Erc20` refused a transfer.
+        TransferFailed,
+        /// Returned if the delegator has not allowed the caller to borrow enough.
+        InsufficientCreditAllowance,
+        /// Returned if the caller is not the owner.
+        CallerNotOwner,
+        /// Returned if the borrow would exceed the cap of the borrower's KYC tier.
+        BorrowCapExceeded,
+        /// Returned if the asset is not a reserve of the pool.
+        ReserveNotFound,
+        /// Returned if the asset already is a reserve of the pool.
+        ReserveAlreadyAdded,
+        /// Returned if the oracle cannot price a reserve.
+        PriceUnavailable,
+        /// Returned if a setting is out of its allowed range.
+        InvalidParameter,
+        /// Returned if the position to liquidate is still healthy.
+        HealthFactorNotBelowThreshold,
+        /// Returned if the user to liquidate owes nothing in the debt asset.
+        NoDebtToCover,
+        /// Returned if the user to liquidate has no deposit in the collateral asset.
+        NoCollateralToSeize,
+        /// Returned if no loan agreement has the given identifier.
+        LoanNotFound,
+        /// Returned if a loan agreement has no principal, no term, or too few or
+        /// too many installments.
+        InvalidLoanTerms,
+        /// Returned if the loan agreement is not in a state that allows the call.
+        InvalidLoanStatus,
+        /// Returned if the caller is not the delegator of the loan agreement.
+        CallerNotDelegator,
+        /// Returned if the caller is not the borrower of the loan agreement.
+        CallerNotBorrower,
+        /// Returned if a value does not fit into a `Balance`.
+        Overflow,
+        /// Returned if the caller is not the guardian.
+        CallerNotGuardian,
+        /// Returned if the pool is paused.
+        Paused,
+        /// Returned if the pool is not paused.
+        NotPaused,
+        /// Returned if the reserve is frozen.
+        ReserveFrozen,
+        /// Returned if the reserve is not frozen.
+        ReserveNotFrozen,
+        /// Returned if the caller is not the pool admin.
+        CallerNotPoolAdmin,
+        /// Returned if the reserve is not active.
+        ReserveInactive,
+        /// Returned if borrowing the reserve is disabled.
+        BorrowingNotEnabled,
+        /// Returned if the assets, amounts and modes of a flash loan are empty or
+        /// differ in length, or an asset is listed twice.
+        InconsistentFlashLoanParams,
+        /// Returned if borrowing the reserve at a stable rate is disabled.
+        StableBorrowingNotEnabled,
+        /// Returned if the borrower's stable rate is too close to the market rate
+        /// to be rebalanced.
+        RebalanceNotNeeded,
+        /// Returned if the amount scales down to zero at the reserve's index, so
+        /// that no tokens can be minted or burned for it.
+        InvalidAmount,
+    }
+
+    /// The lending pool result type.
+    pub type Result<T> = core::result::Result<T, Error>;
+
+    impl LendingPool {
+        /// Creates a new lending pool priced by the `PriceOracle` at `oracle`,
+        /// owned by the caller.
+        ///
+        /// The pool starts without reserves; see `init_reserve`.
+        #[ink(constructor)]
+        pub fn new(oracle: AccountId) -> Self {
+            Self {
+                owner: Self::env().caller(),
+                pool_admin: Self::env().caller(),
+                guardian: Self::env().caller(),
+                paused: false,
+                oracle,
+                reserves: StorageVec::new(),
+                reserve_data: StorageHashMap::new(),
+                close_factor: DEFAULT_CLOSE_FACTOR,
+                flash_loan_premium: DEFAULT_FLASH_LOAN_PREMIUM,
+                kyc_registry: None,
+                treasury: None,
+                borrow_caps: StorageHashMap::new(),
+                credit_allowances: StorageHashMap::new(),
+                delegated_debts: StorageHashMap::new(),
+                total_delegated_debts: StorageHashMap::new(),
+                delegated_borrows: StorageHashMap::new(),
+                loans: StorageHashMap::new(),
+                borrower_loans: StorageHashMap::new(),
+                next_loan_id: 0,
+            }
+        }
+
+        /// Returns the account allowed to change the pool settings.
+        #[ink(message)]
+        pub fn owner(&self) -> AccountId {
+            self.owner
+        }
+
+        /// Returns the account allowed to list and configure reserves.
+        #[ink(message)]
+        pub fn pool_admin(&self) -> AccountId {
+            self.pool_admin
+        }
+
+        /// Returns the account allowed to pause the pool and freeze reserves.
+        #[ink(message)]
+        pub fn guardian(&self) -> AccountId {
+            self.guardian
+        }
+
+        /// Returns `true` if the pool is paused.
+        #[ink(message)]
+        pub fn paused(&self) -> bool {
+            self.paused
+        }
+
+        /// Returns `true` if `reserve` takes no new deposits or borrows.
+        #[ink(message)]
+        pub fn is_frozen(&self, reserve: AccountId) -> bool {
+            self.reserve_data
+                .get(&reserve)
+                .map_or(false, |data| data.configuration.is_frozen())
+        }
+
+        /// Returns the `PriceOracle` contract pricing the reserves.
+        #[ink(message)]
+        pub fn oracle(&self) -> AccountId {
+            self.oracle
+        }
+
+        /// Returns the `Erc20` contracts that can be deposited and borrowed.
+        #[ink(message)]
+        pub fn reserves(&self) -> Vec<AccountId> {
+            self.reserves.iter().copied().collect()
+        }
+
+        /// Returns the configuration and contracts of `reserve`, if it is one.
+        #[ink(message)]
+        pub fn reserve_data(&self, reserve: AccountId) -> Option<ReserveData> {
+            self.reserve_data.get(&reserve).copied()
+        }
+
+        /// Returns the number of decimals of the `reserve` token.
+        #[ink(message)]
+        pub fn reserve_decimals(&self, reserve: AccountId) -> u8 {
+            self.reserve_data
+                .get(&reserve)
+                .map_or(0, |data| data.configuration.decimals())
+        }
+
+        /// Returns the percentage of a debt that can be covered in one liquidation.
+        #[ink(message)]
+        pub fn close_factor(&self) -> Balance {
+            self.close_factor
+        }
+
+        /// Returns the fee on flash loans, in basis points of the borrowed amount.
+        #[ink(message)]
+        pub fn flash_loan_premium(&self) -> Balance {
+            self.flash_loan_premium
+        }
+
+        /// Returns the `KycRegistry` contract gating delegated borrows, if any.
+        #[ink(message)]
+        pub fn kyc_registry(&self) -> Option<AccountId> {
+            self.kyc_registry
+        }
+
+        /// Returns the `Treasury` contract receiving the reserve factor share of
+        /// the interest, if any.
+        #[ink(message)]
+        pub fn treasury(&self) -> Option<AccountId> {
+            self.treasury
+        }
+
+        /// Returns the most a borrower of KYC `tier` may owe against delegated
+        /// credit, in the base currency.
+        ///
+        /// Returns `0` if no cap has been set for the tier.
+        #[ink(message)]
+        pub fn borrow_cap(&self, tier: KycTier) -> Balance {
+            self.borrow_caps.get(&tier).copied().unwrap_or(0)
+        }
+
+        /// Returns the total amount deposited into `reserve`, including the
+        /// interest accrued so far.
+        ///
+        /// The deposits are read from the `AToken` of the reserve.
+        #[ink(message)]
+        pub fn total_deposits(&self, reserve: AccountId) -> Balance {
+            self.reserve_data.get(&reserve).map_or(0, |data| {
+                ray_mul(
+                    ext::scaled_total_deposits(data.a_token),
+                    self.reserve_normalized_income(reserve),
+                )
+            })
+        }
+
+        /// Returns the total amount currently owed to `reserve` at the variable and
+        /// the stable rate, including the interest accrued so far.
+        ///
+        /// The debt is read from the `VariableDebtToken` and the `StableDebtToken`
+        /// of the reserve.
+        #[ink(message)]
+        pub fn total_borrows(&self, reserve: AccountId) -> Balance {
+            self.reserve_data.get(&reserve).map_or(0, |data| {
+                ray_mul(
+                    ext::scaled_total_debt(data.variable_debt_token),
+                    self.reserve_normalized_variable_debt(reserve),
+                ) + ext::stable_total_debt(data.stable_debt_token)
+            })
+        }
+
+        /// Returns the amount of `reserve` tokens that can still be borrowed or
+        /// withdrawn.
+        #[ink(message)]
+        pub fn available_liquidity(&self, reserve: AccountId) -> Balance {
+            self.total_deposits(reserve)
+                .saturating_sub(self.total_borrows(reserve))
+        }
+
+        /// Returns the liquidity index of `reserve` compounded up to now, in rays.
+        ///
+        /// A deposit grows by the ratio of this index at withdrawal to the index at
+        /// deposit. Returns one ray if `reserve` is not a reserve of the pool.
+        ///
+        /// The selector is fixed so that the `AToken` can read the index without
+        /// depending on this crate.
+        #[ink(message, selector = "0xA7040002")]
+        pub fn reserve_normalized_income(&self, reserve: AccountId) -> Balance {
+            self.reserve_data.get(&reserve).map_or(RAY, |data| {
+                let elapsed = self.env().block_timestamp() - data.last_update_timestamp;
+                ray_mul(
+                    calculate_linear_interest(data.current_liquidity_rate, elapsed),
+                    data.liquidity_index,
+                )
+            })
+        }
+
+        /// Returns the variable borrow index of `reserve` compounded up to now, in
+        /// rays.
+        ///
+        /// A debt grows by the ratio of this index at repayment to the index at
+        /// borrowing. Returns one ray if `reserve` is not a reserve of the pool.
+        ///
+        /// The selector is fixed so that the `VariableDebtToken` can read the index
+        /// without depending on this crate.
+        #[ink(message, selector = "0xA7040003")]
+        pub fn reserve_normalized_variable_debt(&self, reserve: AccountId) -> Balance {
+            self.reserve_data.get(&reserve).map_or(RAY, |data| {
+                let elapsed = self.env().block_timestamp() - data.last_update_timestamp;
+                ray_mul(
+                    calculate_compounded_interest(
+                        data.current_variable_borrow_rate,
+                        elapsed,
+                    ),
+                    data.variable_borrow_index,
+                )
+            })
+        }
+
+        /// Returns the deposit of `owner` in `reserve`, including the interest
+        /// accrued so far.
+        ///
+        /// The deposit is read from the `AToken` of the reserve. Returns `0` if
+        /// the account holds none.
+        #[ink(message)]
+        pub fn deposit_of(&self, reserve: AccountId, owner: AccountId) -> Balance {
+            self.reserve_data.get(&reserve).map_or(0, |data| {
+                ray_mul(
+                    ext::scaled_deposit_of(data.a_token, owner),
+                    self.reserve_normalized_income(reserve),
+                )
+            })
+        }
+
+        /// Returns the stable rate debt of `owner` to `reserve`, including the
+        /// interest accrued so far at its locked rate.
+        ///
+        /// The debt is read from the `StableDebtToken` of the reserve. Returns `0`
+        /// if the account owes nothing.
+        #[ink(message)]
+        pub fn stable_debt_of(&self, reserve: AccountId, owner: AccountId) -> Balance {
+            self.reserve_data
+                .get(&reserve)
+                .map_or(0, |data| ext::stable_debt_of(data.stable_debt_token, owner))
+        }
+
+        /// Returns `true` if `from` may transfer `value` of its `AToken` deposit in
+        /// `asset`, whose scaled balance is `scaled_balance`, to another account.
+        ///
+        /// The `AToken` of the reserve asks this before every transfer and passes
+        /// the balance along, since the pool cannot call back into the token while
+        /// it is executing. A transfer is refused while the pool is paused or if
+        /// the remaining deposits of `from` would no longer cover its debt. The
+        /// selector is fixed so that the token can call this without depending on
+        /// this crate.
+        #[ink(message, selector = "0xA7040001")]
+        pub fn can_transfer_deposit(
+            &self,
+            asset: AccountId,
+            from: AccountId,
+            value: Balance,
+            scaled_balance: Balance,
+        ) -> bool {
+            !self.paused
+                && self
+                    .ensure_deposit_removable(from, asset, value, Some(scaled_balance))
+                    .is_ok()
+        }
+
+        /// Returns the outstanding debt of `owner` to `reserve`, including the debt
+        /// its delegates have drawn against its collateral and the interest accrued
+        /// so far.
+        ///
+        /// The debt is read from the `VariableDebtToken` of the reserve. Returns `0`
+        /// if the account owes nothing.
+        #[ink(message)]
+        pub fn debt_of(&self, reserve: AccountId, owner: AccountId) -> Balance {
+            self.reserve_data.get(&reserve).map_or(0, |data| {
+                ray_mul(
+                    ext::scaled_debt_of(data.variable_debt_token, owner),
+                    self.reserve_normalized_variable_debt(reserve),
+                )
+            })
+        }
+
+        /// Returns the amount which `borrower` is still allowed to draw from
+        /// `reserve` against the collateral of `delegator`.
+        ///
+        /// Returns `0` if no credit has been delegated.
+        #[ink(message)]
+        pub fn credit_allowance(
+            &self,
+            reserve: AccountId,
+            delegator: AccountId,
+            borrower: AccountId,
+        ) -> Balance {
+            self.credit_allowances
+                .get(&(reserve, delegator, borrower))
+                .copied()
+                .unwrap_or(0)
+        }
+
+        /// Returns the outstanding amount `borrower` has drawn from `reserve`
+        /// against the collateral of `delegator`.
+        #[ink(message)]
+        pub fn delegated_debt(
+            &self,
+            reserve: AccountId,
+            delegator: AccountId,
+            borrower: AccountId,
+        ) -> Balance {
+            self.delegated_debts
+                .get(&(reserve, delegator, borrower))
+                .copied()
+                .unwrap_or(0)
+        }
+
+        /// Returns the total outstanding amount all borrowers have drawn from
+        /// `reserve` against the collateral of `delegator`.
+        #[ink(message)]
+        pub fn total_delegated_debt(
+            &self,
+            reserve: AccountId,
+            delegator: AccountId,
+        ) -> Balance {
+            self.total_delegated_debts
+                .get(&(reserve, delegator))
+                .copied()
+                .unwrap_or(0)
+        }
+
+        /// Returns the total debt `borrower` has drawn from `reserve` against the
+        /// collateral of delegators.
+        #[ink(message)]
+        pub fn delegated_borrows_of(
+            &self,
+            reserve: AccountId,
+            borrower: AccountId,
+        ) -> Balance {
+            self.delegated_borrows
+                .get(&(reserve, borrower))
+                .copied()
+                .unwrap_or(0)
+        }
+
+        /// Returns the loan agreement `loan_id` with its status as of now.
+        ///
+        /// Returns `None` if no loan agreement has this identifier.
+        #[ink(message)]
+        pub fn get_loan(&self, loan_id: LoanId) -> Option<LoanAgreement> {
+            let mut loan = self.loans.get(&loan_id)?.clone();
+            loan.status = loan.status_at(self.env().block_timestamp());
+            Some(loan)
+        }
+
+        /// Returns the identifiers of all loan agreements offered to `borrower`.
+        #[ink(message)]
+        pub fn loans_of(&self, borrower: AccountId) -> Vec<LoanId> {
+            self.borrower_loans
+                .get(&borrower)
+                .cloned()
+                .unwrap_or_default()
+        }
+
+        /// Returns the health factor of `user` in rays.
+        ///
+        /// The health factor is the collateral value weighted by the liquidation
+        /// threshold divided by the debt value. A position below one ray can be
+        /// liquidated; a position without debt returns `Balance::MAX`.
+        ///
+        /// # Errors
+        ///
+        /// Returns `PriceUnavailable` error if the oracle cannot price a reserve
+        /// the user is invested in.
+        #[ink(message)]
+        pub fn health_factor(&self, user: AccountId) -> Result<Balance> {
+            Ok(self.get_user_account_data(user)?.health_factor)
+        }
+
+        /// Returns the deposits, debt, borrowing power and health of `user` across
+        /// all reserves.
+        ///
+        /// # Errors
+        ///
+        /// Returns `PriceUnavailable` error if the oracle cannot price a reserve
+        /// the user is invested in.
+        ///
+        /// Returns `Overflow` error if a value does not fit in a `Balance`.
+        #[ink(message)]
+        pub fn get_user_account_data(&self, user: AccountId) -> Result<UserAccountData> {
+            let (total_collateral, total_debt, weighted_ltv, weighted_threshold) =
+                self.account_values(user)?;
+            let (ltv, current_liquidation_threshold) = if total_collateral == 0 {
+                (0, 0)
+            } else {
+                (
+                    weighted_ltv / total_collateral,
+                    weighted_threshold / total_collateral,
+                )
+            };
+            let health_factor = if total_debt == 0 {
+                Balance::MAX
+            } else {
+                checked_mul_div(weighted_threshold / 100, RAY, total_debt)
+                    .unwrap_or(Balance::MAX)
+            };
+            Ok(UserAccountData {
+                total_collateral,
+                total_debt,
+                available_borrows: (weighted_ltv / 100).saturating_sub(total_debt),
+                current_liquidation_threshold,
+                ltv,
+                health_factor,
+            })
+        }
+
+        /// Sets the `KycRegistry` contract gating delegated borrows.
+        ///
+        /// Passing `None` lets any borrower draw delegated credit.
+        ///
+        /// # Errors
+        ///
+        /// Returns `CallerNotOwner` error if the caller is not the owner.
+        #[ink(message)]
+        pub fn set_kyc_registry(
+            &mut self,
+            kyc_registry: Option<AccountId>,
+        ) -> Result<()> {
+            self.ensure_owner()?;
+            self.kyc_registry = kyc_registry;
+            Ok(())
+        }
+
+        /// Sets the `Treasury` contract receiving the reserve factor share of the
+        /// interest.
+        ///
+        /// The share accrued from now on is minted to the treasury as `AToken`s of
+        /// each reserve, and a `TreasuryAccrued` event is emitted for it. Passing
+        /// `None` leaves the share in the pool unclaimed.
+        ///
+        /// # Errors
+        ///
+        /// Returns `CallerNotOwner` error if the caller is not the owner.
+        #[ink(message)]
+        pub fn set_treasury(&mut self, treasury: Option<AccountId>) -> Result<()> {
+            self.ensure_owner()?;
+            self.treasury = treasury;
+            Ok(())
+        }
+
+        /// Sets the most a borrower of KYC `tier` may owe against delegated credit,
+        /// in the base currency.
+        ///
+        /// The selector is fixed so that governance proposals can call this
+        /// without depending on this crate.
+        ///
+        /// # Errors
+        ///
+        /// Returns `CallerNotOwner` error if the caller is not the owner.
+        #[ink(message, selector = "0x60F00003")]
+        pub fn set_borrow_cap(&mut self, tier: KycTier, cap: Balance) -> Result<()> {
+            self.ensure_owner()?;
+            self.borrow_caps.insert(tier, cap);
+            Ok(())
+        }
+
+        /// Lists the `Erc20` contract at `asset` as a reserve of the pool, served by
+        /// the given token and interest rate strategy contracts.
+        ///
+        /// The token contracts must have been created for `asset` with this pool as
+        /// the account allowed to mint and burn them.
+        ///
+        /// The reserve starts active and borrowable at a variable rate, with an
+        /// LTV of 75%, a liquidation threshold of 80%, a liquidation bonus of 5%
+        /// and no reserve factor. The token decimals are read once here and used
+        /// to normalize amounts of the reserve when valuing positions. Its indices
+        /// start at one ray, and its rates are set by the first deposit.
+        ///
+        /// On success a `ReserveInitialized` event is emitted.
+        ///
+        /// # Errors
+        ///
+        /// Returns `CallerNotPoolAdmin` error if the caller is not the pool admin.
+        ///
+        /// Returns `ReserveAlreadyAdded` error if the asset already is a reserve.
+        ///
+        /// Returns `InvalidParameter` error if one whole token does not fit into
+        /// a `Balance`.
+        #[ink(message)]
+        pub fn init_reserve(
+            &mut self,
+            asset: AccountId,
+            a_token: AccountId,
+            stable_debt_token: AccountId,
+            variable_debt_token: AccountId,
+            interest_rate_strategy: AccountId,
+        ) -> Result<()> {
+            self.ensure_pool_admin()?;
+            if self.is_reserve(asset) {
+                return Err(Error::ReserveAlreadyAdded)
+            }
+            let decimals = ext::token_decimals(asset);
+            if Balance::checked_pow(10, decimals.into()).is_none() {
+                return Err(Error::InvalidParameter)
+            }
+            let mut configuration = ReserveConfiguration::default();
+            configuration.set_ltv(DEFAULT_LOAN_TO_VALUE);
+            configuration.set_liquidation_threshold(DEFAULT_LIQUIDATION_THRESHOLD);
+            configuration.set_liquidation_bonus(DEFAULT_LIQUIDATION_BONUS);
+            configuration.set_decimals(decimals);
+            configuration.set_active(true);
+            configuration.set_borrowing_enabled(true);
+            self.reserves.push(asset);
+            self.reserve_data.insert(
+                asset,
+                ReserveData {
+                    configuration,
+                    a_token,
+                    stable_debt_token,
+                    variable_debt_token,
+                    interest_rate_strategy,
+                    liquidity_index: RAY,
+                    variable_borrow_index: RAY,
+                    current_liquidity_rate: 0,
+                    current_variable_borrow_rate: 0,
+                    current_stable_borrow_rate: 0,
+                    last_update_timestamp: self.env().block_timestamp(),
+                },
+            );
+            self.env().emit_event(ReserveInitialized {
+                asset,
+                a_token,
+                stable_debt_token,
+                variable_debt_token,
+                interest_rate_strategy,
+            });
+            Ok(())
+        }
+
+        /// Sets the percentage of the deposit value in `asset` that can be
+        /// borrowed against it.
+        ///
+        /// On success a `LtvChanged` event is emitted.
+        ///
+        /// # Errors
+        ///
+        /// Returns `CallerNotPoolAdmin` error if the caller is not the pool admin.
+        ///
+        /// Returns `ReserveNotFound` error if `asset` is not a reserve of the pool.
+        ///
+        /// Returns `InvalidParameter` error if `ltv` is above the liquidation
+        /// threshold of the reserve.
+        #[ink(message)]
+        pub fn set_ltv(&mut self, asset: AccountId, ltv: Balance) -> Result<()> {
+            self.ensure_pool_admin()?;
+            let mut configuration = self.configuration_of(asset)?;
+            if ltv > configuration.liquidation_threshold() {
+                return Err(Error::InvalidParameter)
+            }
+            configuration.set_ltv(ltv);
+            self.set_configuration(asset, configuration);
+            self.env().emit_event(LtvChanged { asset, ltv });
+            Ok(())
+        }
+
+        /// Sets the percentage of the deposit value in `asset` at which a position
+        /// can be liquidated.
+        ///
+        /// On success a `LiquidationThresholdChanged` event is emitted.
+        ///
+        /// # Errors
+        ///
+        /// Returns `CallerNotPoolAdmin` error if the caller is not the pool admin.
+        ///
+        /// Returns `ReserveNotFound` error if `asset` is not a reserve of the pool.
+        ///
+        /// Returns `InvalidParameter` error if `liquidation_threshold` is below the
+        /// LTV of the reserve or above 100.
+        #[ink(message)]
+        pub fn set_liquidation_threshold(
+            &mut self,
+            asset: AccountId,
+            liquidation_threshold: Balance,
+        ) -> Result<()> {
+            self.ensure_pool_admin()?;
+            let mut configuration = self.configuration_of(asset)?;
+            if liquidation_threshold < configuration.ltv() || liquidation_threshold > 100
+            {
+                return Err(Error::InvalidParameter)
+            }
+            configuration.set_liquidation_threshold(liquidation_threshold);
+            self.set_configuration(asset, configuration);
+            self.env().emit_event(LiquidationThresholdChanged {
+                asset,
+                liquidation_threshold,
+            });
+            Ok(())
+        }
+
+        /// Sets the percentage of extra `asset` collateral a liquidator seizes.
+        ///
+        /// The selector is fixed so that governance proposals can call this
+        /// without depending on this crate.
+        ///
+        /// On success a `LiquidationBonusChanged` event is emitted.
+        ///
+        /// # Errors
+        ///
+        /// Returns `CallerNotPoolAdmin` error if the caller is not the pool admin.
+        ///
+        /// Returns `ReserveNotFound` error if `asset` is not a reserve of the pool.
+        ///
+        /// Returns `InvalidParameter` error if `liquidation_bonus` is above 100.
+        #[ink(message, selector = "0x60F00002")]
+        pub fn set_liquidation_bonus(
+            &mut self,
+            asset: AccountId,
+            liquidation_bonus: Balance,
+        ) -> Result<()> {
+            self.ensure_pool_admin()?;
+            let mut configuration = self.configuration_of(asset)?;
+            if liquidation_bonus > 100 {
+                return Err(Error::InvalidParameter)
+            }
+            configuration.set_liquidation_bonus(liquidation_bonus);
+            self.set_configuration(asset, configuration);
+            self.env().emit_event(LiquidationBonusChanged {
+                asset,
+                liquidation_bonus,
+            });
+            Ok(())
+        }
+
+        /// Sets the percentage of the interest paid on `asset` that goes to the
+        /// protocol.
+        ///
+        /// Interest accrued so far is split at the previous percentage. Depositors
+        /// earn the rest of the interest.
+        ///
+        /// The selector is fixed so that governance proposals can call this
+        /// without depending on this crate.
+        ///
+        /// On success a `ReserveFactorChanged` event is emitted.
+        ///
+        /// # Errors
+        ///
+        /// Returns `CallerNotPoolAdmin` error if the caller is not the pool admin.
+        ///
+        /// Returns `ReserveNotFound` error if `asset` is not a reserve of the pool.
+        ///
+        /// Returns `InvalidParameter` error if `reserve_factor` is above 100.
+        #[ink(message, selector = "0x60F00001")]
+        pub fn set_reserve_factor(
+            &mut self,
+            asset: AccountId,
+            reserve_factor: Balance,
+        ) -> Result<()> {
+            self.ensure_pool_admin()?;
+            let mut configuration = self.configuration_of(asset)?;
+            if reserve_factor > 100 {
+                return Err(Error::InvalidParameter)
+            }
+            self.update_state(asset);
+            configuration.set_reserve_factor(reserve_factor);
+            self.set_configuration(asset, configuration);
+            self.update_interest_rates(asset);
+            self.env().emit_event(ReserveFactorChanged {
+                asset,
+                reserve_factor,
+            });
+            Ok(())
+        }
+
+        /// Enables or disables borrowing `asset`.
+        ///
+        /// On success a `BorrowingEnabledChanged` event is emitted.
+        ///
+        /// # Errors
+        ///
+        /// Returns `CallerNotPoolAdmin` error if the caller is not the pool admin.
+        ///
+        /// Returns `ReserveNotFound` error if `asset` is not a reserve of the pool.
+        #[ink(message)]
+        pub fn set_borrowing_enabled(
+            &mut self,
+            asset: AccountId,
+            enabled: bool,
+        ) -> Result<()> {
+            self.ensure_pool_admin()?;
+            let mut configuration = self.configuration_of(asset)?;
+            configuration.set_borrowing_enabled(enabled);
+            self.set_configuration(asset, configuration);
+            self.env()
+                .emit_event(BorrowingEnabledChanged { asset, enabled });
+            Ok(())
+        }
+
+        /// Enables or disables borrowing `asset` at a stable rate.
+        ///
+        /// On success a `StableRateEnabledChanged` event is emitted.
+        ///
+        /// # Errors
+        ///
+        /// Returns `CallerNotPoolAdmin` error if the caller is not the pool admin.
+        ///
+        /// Returns `ReserveNotFound` error if `asset` is not a reserve of the pool.
+        #[ink(message)]
+        pub fn set_stable_rate_enabled(
+            &mut self,
+            asset: AccountId,
+            enabled: bool,
+        ) -> Result<()> {
+            self.ensure_pool_admin()?;
+            let mut configuration = self.configuration_of(asset)?;
+            configuration.set_stable_rate_enabled(enabled);
+            self.set_configuration(asset, configuration);
+            self.env()
+                .emit_event(StableRateEnabledChanged { asset, enabled });
+            Ok(())
+        }
+
+        /// Activates or deactivates `asset`.
+        ///
+        /// An inactive reserve cannot be deposited, withdrawn, borrowed, repaid or
+        /// liquidated.
+        ///
+        /// On success a `ReserveActiveChanged` event is emitted.
+        ///
+        /// # Errors
+        ///
+        /// Returns `CallerNotPoolAdmin` error if the caller is not the pool admin.
+        ///
+        /// Returns `ReserveNotFound` error if `asset` is not a reserve of the pool.
+        #[ink(message)]
+        pub fn set_reserve_active(
+            &mut self,
+            asset: AccountId,
+            active: bool,
+        ) -> Result<()> {
+            self.ensure_pool_admin()?;
+            let mut configuration = self.configuration_of(asset)?;
+            configuration.set_active(active);
+            self.set_configuration(asset, configuration);
+            self.env()
+                .emit_event(ReserveActiveChanged { asset, active });
+            Ok(())
+        }
+
+        /// Replaces the `InterestRateStrategy` contract quoting the rates of
+        /// `asset` with `strategy`.
+        ///
+        /// Interest accrued so far is charged at the previous rates, and the rates
+        /// are requoted by `strategy` right away.
+        ///
+        /// The selector is fixed so that governance proposals can call this
+        /// without depending on this crate.
+        ///
+        /// On success a `ReserveInterestRateStrategyChanged` event is emitted.
+        ///
+        /// # Errors
+        ///
+        /// Returns `CallerNotPoolAdmin` error if the caller is not the pool admin.
+        ///
+        /// Returns `ReserveNotFound` error if `asset` is not a reserve of the pool.
+        #[ink(message, selector = "0x60F00004")]
+        pub fn set_reserve_interest_rate_strategy(
+            &mut self,
+            asset: AccountId,
+            strategy: AccountId,
+        ) -> Result<()> {
+            self.ensure_pool_admin()?;
+            self.ensure_reserve(asset)?;
+            self.update_state(asset);
+            if let Some(data) = self.reserve_data.get_mut(&asset) {
+                data.interest_rate_strategy = strategy;
+            }
+            self.update_interest_rates(asset);
+            self.env()
+                .emit_event(ReserveInterestRateStrategyChanged { asset, strategy });
+            Ok(())
+        }
+
+        /// Sets the account allowed to list and configure reserves.
+        ///
+        /// # Errors
+        ///
+        /// Returns `CallerNotOwner` error if the caller is not the owner.
+        #[ink(message)]
+        pub fn set_pool_admin(&mut self, pool_admin: AccountId) -> Result<()> {
+            self.ensure_owner()?;
+            self.pool_admin = pool_admin;
+            Ok(())
+        }
+
+        /// Hands the pool settings over to `new_owner`, such as a `Governor`.
+        ///
+        /// # Errors
+        ///
+        /// Returns `CallerNotOwner` error if the caller is not the owner.
+        #[ink(message)]
+        pub fn transfer_ownership(&mut self, new_owner: AccountId) -> Result<()> {
+            self.ensure_owner()?;
+            self.owner = new_owner;
+            Ok(())
+        }
+
+        /// Sets the account allowed to pause the pool and freeze reserves.
+        ///
+        /// # Errors
+        ///
+        /// Returns `CallerNotOwner` error if the caller is not the owner.
+        #[ink(message)]
+        pub fn set_guardian(&mut self, guardian: AccountId) -> Result<()> {
+            self.ensure_owner()?;
+            self.guardian = guardian;
+            Ok(())
+        }
+
+        /// Stops all deposits, withdrawals, borrows, repayments and liquidations
+        /// until the pool is unpaused.
+        ///
+        /// On success a `Paused` event is emitted.
+        ///
+        /// # Errors
+        ///
+        /// Returns `CallerNotGuardian` error if the caller is not the guardian.
+        ///
+        /// Returns `Paused` error if the pool is already paused.
+        #[ink(message)]
+        pub fn pause(&mut self) -> Result<()> {
+            self.ensure_guardian()?;
+            self.ensure_not_paused()?;
+            self.paused = true;
+            self.env().emit_event(Paused {
+                account: self.env().caller(),
+            });
+            Ok(())
+        }
+
+        /// Resumes the pool.
+        ///
+        /// On success an `Unpaused` event is emitted.
+        ///
+        /// # Errors
+        ///
+        /// Returns `CallerNotGuardian` error if the caller is not the guardian.
+        ///
+        /// Returns `NotPaused` error if the pool is not paused.
+        #[ink(message)]
+        pub fn unpause(&mut self) -> Result<()> {
+            self.ensure_guardian()?;
+            if !self.paused {
+                return Err(Error::NotPaused)
+            }
+            self.paused = false;
+            self.env().emit_event(Unpaused {
+                account: self.env().caller(),
+            });
+            Ok(())
+        }
+
+        /// Stops new deposits into and borrows from `reserve`.
+        ///
+        /// Withdrawals, repayments and liquidations in the reserve continue, so
+        /// that positions can still be closed.
+        ///
+        /// On success a `ReserveFrozen` event is emitted.
+        ///
+        /// # Errors
+        ///
+        /// Returns `CallerNotGuardian` error if the caller is not the guardian.
+        ///
+        /// Returns `ReserveNotFound` error if `reserve` is not a reserve of the pool.
+        ///
+        /// Returns `ReserveFrozen` error if the reserve is already frozen.
+        #[ink(message)]
+        pub fn freeze_reserve(&mut self, reserve: AccountId) -> Result<()> {
+            self.ensure_guardian()?;
+            let mut configuration = self.configuration_of(reserve)?;
+            if configuration.is_frozen() {
+                return Err(Error::ReserveFrozen)
+            }
+            configuration.set_frozen(true);
+            self.set_configuration(reserve, configuration);
+            self.env().emit_event(ReserveFrozen { reserve });
+            Ok(())
+        }
+
+        /// Lets `reserve` take new deposits and borrows again.
+        ///
+        /// On success a `ReserveUnfrozen` event is emitted.
+        ///
+        /// # Errors
+        ///
+        /// Returns `CallerNotGuardian` error if the caller is not the guardian.
+        ///
+        /// Returns `ReserveNotFound` error if `reserve` is not a reserve of the pool.
+        ///
+        /// Returns `ReserveNotFrozen` error if the reserve is not frozen.
+        #[ink(message)]
+        pub fn unfreeze_reserve(&mut self, reserve: AccountId) -> Result<()> {
+            self.ensure_guardian()?;
+            let mut configuration = self.configuration_of(reserve)?;
+            if !configuration.is_frozen() {
+                return Err(Error::ReserveNotFrozen)
+            }
+            configuration.set_frozen(false);
+            self.set_configuration(reserve, configuration);
+            self.env().emit_event(ReserveUnfrozen { reserve });
+            Ok(())
+        }
+
+        /// Sets the percentage of a debt that can be covered in one liquidation.
+        ///
+        /// The selector is fixed so that governance proposals can call this
+        /// without depending on this crate.
+        ///
+        /// # Errors
+        ///
+        /// Returns `CallerNotOwner` error if the caller is not the owner.
+        ///
+        /// Returns `InvalidParameter` error if `close_factor` is zero or above 100.
+        #[ink(message, selector = "0xC105EFAC")]
+        pub fn set_close_factor(&mut self, close_factor: Balance) -> Result<()> {
+            self.ensure_owner()?;
+            if close_factor == 0 || close_factor > 100 {
+                return Err(Error::InvalidParameter)
+            }
+            self.close_factor = close_factor;
+            Ok(())
+        }
+
+        /// Sets the fee on flash loans, in basis points of the borrowed amount.
+        ///
+        /// # Errors
+        ///
+        /// Returns `CallerNotOwner` error if the caller is not the owner.
+        ///
+        /// Returns `InvalidParameter` error if `premium` is above 10000.
+        #[ink(message)]
+        pub fn set_flash_loan_premium(&mut self, premium: Balance) -> Result<()> {
+            self.ensure_owner()?;
+            if premium > 10_000 {
+                return Err(Error::InvalidParameter)
+            }
+            self.flash_loan_premium = premium;
+            Ok(())
+        }
+
+        /// Deposits `value` tokens of `reserve` from the caller's account into the
+        /// pool.
+        ///
+        /// The caller must have approved the pool to spend at least `value` tokens.
+        ///
+        /// On success a `Deposit` event is emitted.
+        ///
+        /// # Errors
+        ///
+        /// Returns `Paused` error if the pool is paused.
+        ///
+        /// Returns `ReserveNotFound` error if `reserve` is not a reserve of the pool.
+        ///
+        /// Returns `ReserveInactive` error if the reserve is not active.
+        ///
+        /// Returns `ReserveFrozen` error if the reserve is frozen.
+        ///
+        /// Returns `InvalidAmount` error if `value` scales down to zero at the
+        /// liquidity index.
+        ///
+        /// Returns `TransferFailed` error if the reserve refused to move the tokens.
+        #[ink(message)]
+        pub fn deposit(&mut self, reserve: AccountId, value: Balance) -> Result<()> {
+            self.ensure_not_paused()?;
+            if self.active_configuration(reserve)?.is_frozen() {
+                return Err(Error::ReserveFrozen)
+            }
+            self.update_state(reserve);
+            self.ensure_scalable(value, self.reserve_normalized_income(reserve))?;
+            let caller = self.env().caller();
+            ext::transfer_from(reserve, caller, self.env().account_id(), value)?;
+            self.increase_deposit(reserve, caller, value);
+            self.update_interest_rates(reserve);
+            self.env().emit_event(Deposit {
+                reserve,
+                user: caller,
+                value,
+            });
+            Ok(())
+        }
+
+        /// Withdraws `value` tokens of `reserve` from the caller's deposit.
+        ///
+        /// On success a `Withdraw` event is emitted.
+        ///
+        /// # Errors
+        ///
+        /// Returns `Paused` error if the pool is paused.
+        ///
+        /// Returns `ReserveNotFound` error if `reserve` is not a reserve of the pool.
+        ///
+        /// Returns `ReserveInactive` error if the reserve is not active.
+        ///
+        /// Returns `InsufficientDeposit` error if the caller deposited less than `value`.
+        ///
+        /// Returns `InvalidAmount` error if `value` is less than the deposit and
+        /// scales down to zero at the liquidity index.
+        ///
+        /// Returns `InsufficientCollateral` error if the remaining deposits would no
+        /// longer cover the caller's debt.
+        ///
+        /// Returns `InsufficientLiquidity` error if the pool cannot pay out `value`.
+        #[ink(message)]
+        pub fn withdraw(&mut self, reserve: AccountId, value: Balance) -> Result<()> {
+            self.ensure_not_paused()?;
+            self.active_configuration(reserve)?;
+            self.update_state(reserve);
+            let caller = self.env().caller();
+            let deposit = self.deposit_of(reserve, caller);
+            if deposit < value {
+                return Err(Error::InsufficientDeposit)
+            }
+            if value < deposit {
+                self.ensure_scalable(value, self.reserve_normalized_income(reserve))?;
+            }
+            self.ensure_deposit_removable(caller, reserve, value, None)?;
+            if self.available_liquidity(reserve) < value {
+                return Err(Error::InsufficientLiquidity)
+            }
+            ext::transfer(reserve, caller, value)?;
+            self.decrease_deposit(reserve, caller, value);
+            self.update_interest_rates(reserve);
+            self.env().emit_event(Withdraw {
+                reserve,
+                user: caller,
+                value,
+            });
+            Ok(())
+        }
+
+        /// Borrows `value` tokens of `reserve` against the caller's deposits.
+        ///
+        /// On success a `Borrow` event is emitted.
+        ///
+        /// # Errors
+        ///
+        /// Returns `Paused` error if the pool is paused.
+        ///
+        /// Returns `ReserveNotFound` error if `reserve` is not a reserve of the pool.
+        ///
+        /// Returns `ReserveInactive` error if the reserve is not active.
+        ///
+        /// Returns `ReserveFrozen` error if the reserve is frozen.
+        ///
+        /// Returns `BorrowingNotEnabled` error if borrowing the reserve is disabled.
+        ///
+        /// Returns `InvalidAmount` error if `value` scales down to zero at the
+        /// variable borrow index.
+        ///
+        /// Returns `InsufficientCollateral` error if the caller's deposits do not
+        /// cover the resulting debt.
+        ///
+        /// Returns `InsufficientLiquidity` error if the pool cannot pay out `value`.
+        #[ink(message)]
+        pub fn borrow(&mut self, reserve: AccountId, value: Balance) -> Result<()> {
+            self.ensure_not_paused()?;
+            self.ensure_borrowable(reserve)?;
+            self.update_state(reserve);
+            self.ensure_scalable(value, self.reserve_normalized_variable_debt(reserve))?;
+            let caller = self.env().caller();
+            self.ensure_covered(caller, reserve, value)?;
+            if self.available_liquidity(reserve) < value {
+                return Err(Error::InsufficientLiquidity)
+            }
+            ext::transfer(reserve, caller, value)?;
+            self.increase_debt(reserve, caller, value);
+            self.update_interest_rates(reserve);
+            self.env().emit_event(Borrow {
+                reserve,
+                user: caller,
+                value,
+            });
+            Ok(())
+        }
+
+        /// Repays up to `value` tokens of the caller's debt to `reserve`.
+        ///
+        /// Only the outstanding debt is taken if `value` exceeds it. The caller must
+        /// have approved the pool to spend the repaid amount.
+        ///
+        /// On success a `Repay` event is emitted.
+        ///
+        /// # Errors
+        ///
+        /// Returns `Paused` error if the pool is paused.
+        ///
+        /// Returns `ReserveNotFound` error if `reserve` is not a reserve of the pool.
+        ///
+        /// Returns `ReserveInactive` error if the reserve is not active.
+        ///
+        /// Returns `InvalidAmount` error if `value` is less than the debt and
+        /// scales down to zero at the variable borrow index.
+        ///
+        /// Returns `TransferFailed` error if the reserve refused to move the tokens.
+        #[ink(message)]
+        pub fn repay(&mut self, reserve: AccountId, value: Balance) -> Result<()> {
+            self.ensure_not_paused()?;
+            self.active_configuration(reserve)?;
+            self.update_state(reserve);
+            let caller = self.env().caller();
+            let debt = self.debt_of(reserve, caller);
+            let value = core::cmp::min(value, debt);
+            if value < debt {
+                self.ensure_scalable(
+                    value,
+                    self.reserve_normalized_variable_debt(reserve),
+                )?;
+            }
+            ext::transfer_from(reserve, caller, self.env().account_id(), value)?;
+            self.decrease_debt(reserve, caller, value);
+            self.update_interest_rates(reserve);
+            self.env().emit_event(Repay {
+                reserve,
+                user: caller,
+                value,
+            });
+            Ok(())
+        }
+
+        /// Borrows `value` tokens of `reserve` against the caller's deposits at the
+        /// current stable borrow rate of the reserve.
+        ///
+        /// The caller's stable rate becomes the average of the rate of its current
+        /// stable debt and the new rate, weighted by amount, and stays there until
+        /// it is rebalanced.
+        ///
+        /// On success a `BorrowStable` event is emitted.
+        ///
+        /// # Errors
+        ///
+        /// Returns `Paused` error if the pool is paused.
+        ///
+        /// Returns `ReserveNotFound` error if `reserve` is not a reserve of the pool.
+        ///
+        /// Returns `ReserveInactive` error if the reserve is not active.
+        ///
+        /// Returns `ReserveFrozen` error if the reserve is frozen.
+        ///
+        /// Returns `BorrowingNotEnabled` error if borrowing the reserve is disabled.
+        ///
+        /// Returns `StableBorrowingNotEnabled` error if borrowing the reserve at a
+        /// stable rate is disabled.
+        ///
+        /// Returns `InsufficientCollateral` error if the caller's deposits do not
+        /// cover the resulting debt.
+        ///
+        /// Returns `InsufficientLiquidity` error if the pool cannot pay out `value`.
+        ///
+        /// # Panics
+        ///
+        /// If the `StableDebtToken` of the reserve refuses to record the debt once
+        /// the tokens were paid out, so that the whole transaction is reverted.
+        #[ink(message)]
+        pub fn borrow_stable(
+            &mut self,
+            reserve: AccountId,
+            value: Balance,
+        ) -> Result<()> {
+            self.ensure_not_paused()?;
+            self.ensure_borrowable(reserve)?;
+            if !self.configuration_of(reserve)?.is_stable_rate_enabled() {
+                return Err(Error::StableBorrowingNotEnabled)
+            }
+            self.update_state(reserve);
+            let caller = self.env().caller();
+            self.ensure_covered(caller, reserve, value)?;
+            if self.available_liquidity(reserve) < value {
+                return Err(Error::InsufficientLiquidity)
+            }
+            ext::transfer(reserve, caller, value)?;
+            let rate = self.increase_stable_debt(reserve, caller, value);
+            self.update_interest_rates(reserve);
+            self.env().emit_event(BorrowStable {
+                reserve,
+                user: caller,
+                value,
+                rate,
+            });
+            Ok(())
+        }
+
+        /// Repays up to `value` tokens of the caller's stable rate debt to
+        /// `reserve`.
+        ///
+        /// Only the outstanding debt is taken if `value` exceeds it. The caller must
+        /// have approved the pool to spend the repaid amount.
+        ///
+        /// On success a `RepayStable` event is emitted.
+        ///
+        /// # Errors
+        ///
+        /// Returns `Paused` error if the pool is paused.
+        ///
+        /// Returns `ReserveNotFound` error if `reserve` is not a reserve of the pool.
+        ///
+        /// Returns `ReserveInactive` error if the reserve is not active.
+        ///
+        /// Returns `TransferFailed` error if the reserve refused to move the tokens.
+        ///
+        /// # Panics
+        ///
+        /// If the `StableDebtToken` of the reserve refuses to clear the debt once
+        /// the tokens were taken, so that the whole transaction is reverted.
+        #[ink(message)]
+        pub fn repay_stable(&mut self, reserve: AccountId, value: Balance) -> Result<()> {
+            self.ensure_not_paused()?;
+            self.active_configuration(reserve)?;
+            self.update_state(reserve);
+            let caller = self.env().caller();
+            let value = core::cmp::min(value, self.stable_debt_of(reserve, caller));
+            ext::transfer_from(reserve, caller, self.env().account_id(), value)?;
+            self.decrease_stable_debt(reserve, caller, value);
+            self.update_interest_rates(reserve);
+            self.env().emit_event(RepayStable {
+                reserve,
+                user: caller,
+                value,
+            });
+            Ok(())
+        }
+
+        /// Moves the stable rate of `user` in `reserve` to the current stable
+        /// borrow rate of the reserve.
+        ///
+        /// Anyone can call this once the rate `user` locked in has drifted far
+        /// enough from the market rate; the interest accrued so far is kept at the
+        /// old rate.
+        ///
+        /// On success a `RebalanceStableBorrowRate` event is emitted.
+        ///
+        /// # Errors
+        ///
+        /// Returns `Paused` error if the pool is paused.
+        ///
+        /// Returns `ReserveNotFound` error if `reserve` is not a reserve of the pool.
+        ///
+        /// Returns `ReserveInactive` error if the reserve is not active.
+        ///
+        /// Returns `RebalanceNotNeeded` error if `user` owes no stable rate debt or
+        /// its rate is too close to the market rate.
+        #[ink(message)]
+        pub fn rebalance_stable_borrow_rate(
+            &mut self,
+            reserve: AccountId,
+            user: AccountId,
+        ) -> Result<()> {
+            self.ensure_not_paused()?;
+            self.active_configuration(reserve)?;
+            self.update_state(reserve);
+            let data = self
+                .reserve_data
+                .get(&reserve)
+                .ok_or(Error::ReserveNotFound)?;
+            if !ext::rebalance_stable_rate(
+                data.stable_debt_token,
+                user,
+                data.current_stable_borrow_rate,
+            ) {
+                return Err(Error::RebalanceNotNeeded)
+            }
+            self.update_interest_rates(reserve);
+            self.env()
+                .emit_event(RebalanceStableBorrowRate { reserve, user });
+            Ok(())
+        }
+
+        /// Allows `borrower` to draw up to `value` tokens of `reserve` against the
+        /// caller's collateral.
+        ///
+        /// If this function is called again it overwrites the current credit allowance
+        /// with `value`; debt already drawn is not affected. Delegating `0` revokes
+        /// the credit line.
+        ///
+        /// A `CreditDelegated` event is emitted.
+        #[ink(message)]
+        pub fn delegate_credit(
+            &mut self,
+            reserve: AccountId,
+            borrower: AccountId,
+            value: Balance,
+        ) -> Result<()> {
+            let delegator = self.env().caller();
+            self.credit_allowances
+                .insert((reserve, delegator, borrower), value);
+            self.env().emit_event(CreditDelegated {
+                reserve,
+                delegator,
+                borrower,
+                value,
+            });
+            Ok(())
+        }
+
+        /// Borrows `value` tokens of `reserve` against the collateral of `delegator`.
+        ///
+        /// The debt is owed to the pool by the delegator, and the caller's credit
+        /// allowance is reduced by `value`.
+        ///
+        /// On success a `DelegationUsed` event is emitted.
+        ///
+        /// # Errors
+        ///
+        /// Returns `Paused` error if the pool is paused.
+        ///
+        /// Returns `InsufficientCreditAllowance` error if `delegator` has not allowed
+        /// the caller to borrow `value`.
+        ///
+        /// Returns `ReserveNotFound` error if `reserve` is not a reserve of the pool.
+        ///
+        /// Returns `ReserveInactive` error if the reserve is not active.
+        ///
+        /// Returns `ReserveFrozen` error if the reserve is frozen.
+        ///
+        /// Returns `BorrowingNotEnabled` error if borrowing the reserve is disabled.
+        ///
+        /// Returns `BorrowCapExceeded` error if a KYC registry is set and the caller's
+        /// delegated debt would exceed the cap of its tier.
+        ///
+        /// Returns `InsufficientCollateral` error if the delegator's deposits do not
+        /// cover the resulting debt.
+        ///
+        /// Returns `InsufficientLiquidity` error if the pool cannot pay out `value`.
+        #[ink(message)]
+        pub fn borrow_on_behalf(
+            &mut self,
+            reserve: AccountId,
+            delegator: AccountId,
+            value: Balance,
+        ) -> Result<()> {
+            self.ensure_not_paused()?;
+            let borrower = self.env().caller();
+            let allowance = self.credit_allowance(reserve, delegator, borrower);
+            if allowance < value {
+                return Err(Error::InsufficientCreditAllowance)
+            }
+            self.draw_delegated(reserve, delegator, borrower, value)?;
+            self.credit_allowances
+                .insert((reserve, delegator, borrower), allowance - value);
+            Ok(())
+        }
+
+        /// Repays up to `value` tokens of `reserve` the caller has drawn against the
+        /// collateral of `delegator`.
+        ///
+        /// Only the outstanding debt is taken if `value` exceeds it, and never more
+        /// than the delegator still owes the pool. Repaying does not restore the
+        /// credit allowance.
+        ///
+        /// On success a `DelegationRepaid` event is emitted.
+        ///
+        /// # Errors
+        ///
+        /// Returns `Paused` error if the pool is paused.
+        ///
+        /// Returns `ReserveNotFound` error if `reserve` is not a reserve of the pool.
+        ///
+        /// Returns `ReserveInactive` error if the reserve is not active.
+        ///
+        /// Returns `TransferFailed` error if the reserve refused to move the tokens.
+        #[ink(message)]
+        pub fn repay_on_behalf(
+            &mut self,
+            reserve: AccountId,
+            delegator: AccountId,
+            value: Balance,
+        ) -> Result<()> {
+            self.ensure_not_paused()?;
+            self.active_configuration(reserve)?;
+            self.update_state(reserve);
+            let borrower = self.env().caller();
+            let value = core::cmp::min(
+                value,
+                self.repayable_delegated_debt(reserve, delegator, borrower),
+            );
+            ext::transfer_from(reserve, borrower, self.env().account_id(), value)?;
+            self.settle_delegated(reserve, delegator, borrower, value);
+            self.update_interest_rates(reserve);
+            Ok(())
+        }
+
+        /// Offers `borrower` a loan of `principal` tokens of `reserve` against the
+        /// caller's collateral and returns its identifier.
+        ///
+        /// The loan runs for `term` milliseconds at a yearly simple interest rate of
+        /// `apr` rays. The term is split into `installments` equal periods at the
+        /// end of which a share of the amount owed falls due according to
+        /// `schedule`. An installment may be overdue for `grace_period`
+        /// milliseconds before the loan defaults. No funds move until the
+        /// borrower accepts.
+        ///
+        /// On success a `LoanProposed` event is emitted.
+        ///
+        /// # Errors
+        ///
+        /// Returns `ReserveNotFound` error if `reserve` is not a reserve of the pool.
+        ///
+        /// Returns `InvalidLoanTerms` error if `principal` or `term` is zero, or if
+        /// `installments` is zero or above 120.
+        #[ink(message)]
+        #[allow(clippy::too_many_arguments)]
+        pub fn propose_loan(
+            &mut self,
+            reserve: AccountId,
+            borrower: AccountId,
+            principal: Balance,
+            apr: Balance,
+            term: Timestamp,
+            installments: u32,
+            schedule: RepaymentSchedule,
+            grace_period: Timestamp,
+        ) -> Result<LoanId> {
+            self.ensure_reserve(reserve)?;
+            if principal == 0
+                || term == 0
+                || installments == 0
+                || installments > MAX_INSTALLMENTS
+            {
+                return Err(Error::InvalidLoanTerms)
+            }
+            let delegator = self.env().caller();
+            let interest = ray_mul(principal, calculate_linear_interest(apr, term) - RAY);
+            let loan_id = self.next_loan_id;
+            self.next_loan_id += 1;
+            self.loans.insert(
+                loan_id,
+                LoanAgreement {
+                    reserve,
+                    delegator,
+                    borrower,
+                    principal,
+                    apr,
+                    interest,
+                    term,
+                    installments,
+                    schedule,
+                    grace_period,
+                    start: 0,
+                    repaid: 0,
+                    status: LoanStatus::Proposed,
+                },
+            );
+            let mut loans = self.loans_of(borrower);
+            loans.push(loan_id);
+            self.borrower_loans.insert(borrower, loans);
+            self.env().emit_event(LoanProposed {
+                loan_id,
+                delegator,
+                borrower,
+                principal,
+            });
+            Ok(loan_id)
+        }
+
+        /// Withdraws the loan agreement `loan_id` before the borrower accepts it.
+        ///
+        /// On success a `LoanCancelled` event is emitted.
+        ///
+        /// # Errors
+        ///
+        /// Returns `LoanNotFound` error if no loan agreement has this identifier.
+        ///
+        /// Returns `CallerNotDelegator` error if the caller did not propose the loan.
+        ///
+        /// Returns `InvalidLoanStatus` error if the loan is no longer `Proposed`.
+        #[ink(message)]
+        pub fn cancel_loan(&mut self, loan_id: LoanId) -> Result<()> {
+            let mut loan = self
+                .loans
+                .get(&loan_id)
+                .cloned()
+                .ok_or(Error::LoanNotFound)?;
+            if self.env().caller() != loan.delegator {
+                return Err(Error::CallerNotDelegator)
+            }
+            if loan.status != LoanStatus::Proposed {
+                return Err(Error::InvalidLoanStatus)
+            }
+            loan.status = LoanStatus::Cancelled;
+            self.loans.insert(loan_id, loan);
+            self.env().emit_event(LoanCancelled { loan_id });
+            Ok(())
+        }
+
+        /// Accepts the loan agreement `loan_id` and pays out its principal to the
+        /// caller.
+        ///
+        /// The principal is drawn against the delegator's collateral like
+        /// `borrow_on_behalf`, without using the credit allowance, and the term
+        /// starts now.
+        ///
+        /// On success a `LoanAccepted` event is emitted.
+        ///
+        /// # Errors
+        ///
+        /// Returns `Paused` error if the pool is paused.
+        ///
+        /// Returns `LoanNotFound` error if no loan agreement has this identifier.
+        ///
+        /// Returns `CallerNotBorrower` error if the loan was offered to someone else.
+        ///
+        /// Returns `InvalidLoanStatus` error if the loan is no longer `Proposed`.
+        ///
+        /// Returns `ReserveInactive`, `ReserveFrozen`, `BorrowingNotEnabled`,
+        /// `BorrowCapExceeded`, `InsufficientCollateral` or `InsufficientLiquidity`
+        /// error if the principal cannot be drawn.
+        #[ink(message)]
+        pub fn accept_loan(&mut self, loan_id: LoanId) -> Result<()> {
+            self.ensure_not_paused()?;
+            let mut loan = self
+                .loans
+                .get(&loan_id)
+                .cloned()
+                .ok_or(Error::LoanNotFound)?;
+            if self.env().caller() != loan.borrower {
+                return Err(Error::CallerNotBorrower)
+            }
+            if loan.status != LoanStatus::Proposed {
+                return Err(Error::InvalidLoanStatus)
+            }
+            self.draw_delegated(
+                loan.reserve,
+                loan.delegator,
+                loan.borrower,
+                loan.principal,
+            )?;
+            let start = self.env().block_timestamp();
+            loan.start = start;
+            loan.status = LoanStatus::Active;
+            self.loans.insert(loan_id, loan);
+            self.env().emit_event(LoanAccepted { loan_id, start });
+            Ok(())
+        }
+
+        /// Pays up to `value` tokens towards the loan agreement `loan_id`.
+        ///
+        /// Only the outstanding amount is taken if `value` exceeds it. Each payment
+        /// is split pro rata: the principal part repays the delegator's debt to the
+        /// pool and the interest part goes to the delegator. The caller must have
+        /// approved the pool to spend the paid amount.
+        ///
+        /// On success a `LoanRepaid` event is emitted.
+        ///
+        /// # Errors
+        ///
+        /// Returns `Paused` error if the pool is paused.
+        ///
+        /// Returns `LoanNotFound` error if no loan agreement has this identifier.
+        ///
+        /// Returns `InvalidLoanStatus` error if the loan has not been accepted or is
+        /// already repaid.
+        ///
+        /// Returns `TransferFailed` error if the reserve refused to move the tokens.
+        ///
+        /// # Panics
+        ///
+        /// If the reserve refuses to pay the delegator its share, so that the whole
+        /// payment is reverted.
+        #[ink(message)]
+        pub fn repay_loan(&mut self, loan_id: LoanId, value: Balance) -> Result<()> {
+            self.ensure_not_paused()?;
+            let loan = self.loans.get(&loan_id).ok_or(Error::LoanNotFound)?;
+            match loan.status {
+                LoanStatus::Proposed | LoanStatus::Cancelled | LoanStatus::Repaid => {
+                    return Err(Error::InvalidLoanStatus)
+                }
+                _ => {}
+            }
+            let payer = self.env().caller();
+            self.pay_loan(loan_id, payer, value)?;
+            Ok(())
+        }
+
+        /// Repays up to `debt_to_cover` of the `debt_asset` debt of an unhealthy
+        /// `user` and seizes its `collateral_asset` deposit at a discount.
+        ///
+        /// A `user` that has a defaulted loan agreement in `debt_asset` can be
+        /// liquidated whatever its health factor. In that case the debt covered is
+        /// the amount outstanding on its defaulted loans, paid as with `repay_loan`.
+        ///
+        /// At most `close_factor` percent of the debt is covered at once, variable
+        /// rate debt before stable rate debt. The caller
+        /// receives collateral worth the covered debt plus the liquidation bonus
+        /// percentage of `collateral_asset`, either as underlying tokens or, if
+        /// `receive_underlying` is `false`, as a deposit in the pool. If the user's
+        /// deposit is too small, all of it is seized and the covered debt is
+        /// reduced to match. The caller must have approved the pool to spend the
+        /// covered debt.
+        ///
+        /// On success a `LiquidationCall` event is emitted.
+        ///
+        /// # Errors
+        ///
+        /// Returns `Paused` error if the pool is paused.
+        ///
+        /// Returns `ReserveNotFound` error if either asset is not a reserve of the pool.
+        ///
+        /// Returns `ReserveInactive` error if either reserve is not active.
+        ///
+        /// Returns `HealthFactorNotBelowThreshold` error if the health factor of
+        /// `user` is at least one and it has no defaulted loan in `debt_asset`.
+        ///
+        /// Returns `NoDebtToCover` error if `user` owes nothing in `debt_asset`.
+        ///
+        /// Returns `NoCollateralToSeize` error if `user` has no deposit in
+        /// `collateral_asset`.
+        ///
+        /// Returns `InsufficientLiquidity` error if the pool cannot pay out the
+        /// seized collateral as underlying tokens.
+        ///
+        /// Returns `Overflow` error if the covered debt or the seized collateral do
+        /// not fit into a `Balance`.
+        ///
+        /// Returns `TransferFailed` error if the debt reserve refused to take the
+        /// covered debt from the caller, or the collateral reserve refused to send
+        /// the seized collateral. In the latter case the covered debt is sent back.
+        ///
+        /// # Panics
+        ///
+        /// If a delegator's share of a defaulted loan payment cannot be sent once
+        /// the covered debt was taken, so that the whole transaction is reverted.
+        #[ink(message)]
+        pub fn liquidation_call(
+            &mut self,
+            collateral_asset: AccountId,
+            debt_asset: AccountId,
+            user: AccountId,
+            debt_to_cover: Balance,
+            receive_underlying: bool,
+        ) -> Result<()> {
+            self.ensure_not_paused()?;
+            let collateral_configuration = self.active_configuration(collateral_asset)?;
+            self.active_configuration(debt_asset)?;
+            self.update_state(collateral_asset);
+            self.update_state(debt_asset);
+            let defaulted_debt = self.defaulted_debt(debt_asset, user);
+            let user_debt = if defaulted_debt > 0 {
+                defaulted_debt
+            } else {
+                if self.health_factor(user)? >= RAY {
+                    return Err(Error::HealthFactorNotBelowThreshold)
+                }
+                self.debt_of(debt_asset, user)
+                    .checked_add(self.stable_debt_of(debt_asset, user))
+                    .ok_or(Error::Overflow)?
+            };
+            if user_debt == 0 {
+                return Err(Error::NoDebtToCover)
+            }
+            let user_collateral = self.deposit_of(collateral_asset, user);
+            if user_collateral == 0 {
+                return Err(Error::NoCollateralToSeize)
+            }
+            let max_debt_to_cover = user_debt
+                .checked_mul(self.close_factor)
+                .ok_or(Error::Overflow)?
+                / 100;
+            let mut debt_to_cover = core::cmp::min(debt_to_cover, max_debt_to_cover);
+            let collateral_price = ext::price(self.oracle, collateral_asset)?;
+            let debt_price = ext::price(self.oracle, debt_asset)?;
+            let collateral_unit = self.unit_of(collateral_asset);
+            let debt_unit = self.unit_of(debt_asset);
+            let bonus = 100 + collateral_configuration.liquidation_bonus();
+            let mut liquidated_collateral = checked_mul_div(
+                debt_to_cover,
+                debt_price * bonus,
+                100 * collateral_price,
+            )
+            .and_then(|amount| checked_mul_div(amount, collateral_unit, debt_unit))
+            .ok_or(Error::Overflow)?;
+            if liquidated_collateral > user_collateral {
+                liquidated_collateral = user_collateral;
+                debt_to_cover = checked_mul_div(
+                    user_collateral,
+                    collateral_price * 100,
+                    bonus * debt_price,
+                )
+                .and_then(|amount| checked_mul_div(amount, debt_unit, collateral_unit))
+                .ok_or(Error::Overflow)?;
+            }
+            if receive_underlying
+                && self.available_liquidity(collateral_asset) < liquidated_collateral
+            {
+                return Err(Error::InsufficientLiquidity)
+            }
+            let liquidator = self.env().caller();
+            ext::transfer_from(
+                debt_asset,
+                liquidator,
+                self.env().account_id(),
+                debt_to_cover,
+            )?;
+            if receive_underlying {
+                if let Err(error) =
+                    ext::transfer(collateral_asset, liquidator, liquidated_collateral)
+                {
+                    ext::transfer(debt_asset, liquidator, debt_to_cover)?;
+                    return Err(error)
+                }
+            }
+            if defaulted_debt > 0 {
+                self.pay_defaulted_loans(debt_asset, user, liquidator, debt_to_cover);
+            } else {
+                let variable_debt =
+                    core::cmp::min(debt_to_cover, self.debt_of(debt_asset, user));
+                self.decrease_debt(debt_asset, user, variable_debt);
+                self.decrease_stable_debt(
+                    debt_asset,
+                    user,
+                    debt_to_cover - variable_debt,
+                );
+            }
+            self.decrease_deposit(collateral_asset, user, liquidated_collateral);
+            if !receive_underlying {
+                self.increase_deposit(
+                    collateral_asset,
+                    liquidator,
+                    liquidated_collateral,
+                );
+            }
+            self.update_interest_rates(collateral_asset);
+            self.update_interest_rates(debt_asset);
+            self.env().emit_event(LiquidationCall {
+                collateral_asset,
+                debt_asset,
+                user,
+                debt_to_cover,
+                liquidated_collateral,
+                liquidator,
+                receive_underlying,
+            });
+            Ok(())
+        }
+
+        /// Sends `amounts` of `assets` to the `FlashLoanReceiver` contract at
+        /// `receiver`, calls its `execute_operation` with `params`, and settles
+        /// each asset according to its entry in `modes`.
+        ///
+        /// In `FlashLoanMode::NoDebt` the pool takes back the amount plus a
+        /// premium of `flash_loan_premium` basis points from the receiver, which
+        /// must have approved the pool to spend it. The premium is paid out to
+        /// the depositors of the reserve. In `FlashLoanMode::VariableDebt` the
+        /// receiver keeps the amount and the caller owes it to the pool as if it
+        /// had borrowed it.
+        ///
+        /// On success a `FlashLoan` event is emitted for each asset, preceded by a
+        /// `Borrow` event for each asset left as debt.
+        ///
+        /// # Errors
+        ///
+        /// Returns `Paused` error if the pool is paused.
+        ///
+        /// Returns `InconsistentFlashLoanParams` error if `assets`, `amounts` and
+        /// `modes` are empty or differ in length, or if an asset is listed twice.
+        ///
+        /// Returns `ReserveNotFound` error if an asset is not a reserve of the pool.
+        ///
+        /// Returns `ReserveInactive` error if a reserve is not active.
+        ///
+        /// Returns `ReserveFrozen` or `BorrowingNotEnabled` error if a reserve to be
+        /// left as debt cannot be borrowed.
+        ///
+        /// Returns `InsufficientCollateral` error if the caller's deposits do not
+        /// cover the debt it would be left with.
+        ///
+        /// Returns `InsufficientLiquidity` error if the pool cannot pay out an
+        /// amount.
+        ///
+        /// # Panics
+        ///
+        /// If a transfer to the receiver fails, if `execute_operation` does not
+        /// return `true`, or if the receiver does not pay back what it owes, so
+        /// that the whole transaction is reverted.
+        #[ink(message)]
+        pub fn flash_loan(
+            &mut self,
+            receiver: AccountId,
+            assets: Vec<AccountId>,
+            amounts: Vec<Balance>,
+            modes: Vec<FlashLoanMode>,
+            params: Vec<u8>,
+        ) -> Result<()> {
+            self.ensure_not_paused()?;
+            if assets.is_empty()
+                || assets.len() != amounts.len()
+                || assets.len() != modes.len()
+                || assets
+                    .iter()
+                    .enumerate()
+                    .any(|(i, asset)| assets[..i].contains(asset))
+            {
+                return Err(Error::InconsistentFlashLoanParams)
+            }
+            let initiator = self.env().caller();
+            let mut premiums = Vec::new();
+            let mut debt_value: Balance = 0;
+            for ((asset, amount), mode) in assets.iter().zip(&amounts).zip(&modes) {
+                match mode {
+                    FlashLoanMode::NoDebt => {
+                        self.active_configuration(*asset)?;
+                        premiums.push(
+                            checked_mul_div(*amount, self.flash_loan_premium, 10_000)
+                                .ok_or(Error::Overflow)?,
+                        );
+                    }
+                    FlashLoanMode::VariableDebt => {
+                        self.ensure_borrowable(*asset)?;
+                        debt_value = debt_value
+                            .checked_add(self.value_of(*asset, *amount)?)
+                            .ok_or(Error::Overflow)?;
+                        premiums.push(0);
+                    }
+                }
+                if self.available_liquidity(*asset) < *amount {
+                    return Err(Error::InsufficientLiquidity)
+                }
+            }
+            if debt_value > 0 {
+                self.ensure_debt_covered(initiator, debt_value)?;
+            }
+            for (asset, amount) in assets.iter().zip(&amounts) {
+                self.update_state(*asset);
+                ext::transfer(*asset, receiver, *amount)
+                    .expect("flash loan transfer failed");
+            }
+            assert!(
+                ext::execute_operation(
+                    receiver, &assets, &amounts, &premiums, initiator, &params
+                ),
+                "flash loan receiver failed"
+            );
+            for (((asset, amount), mode), premium) in
+                assets.iter().zip(&amounts).zip(&modes).zip(&premiums)
+            {
+                match mode {
+                    FlashLoanMode::NoDebt => {
+                        ext::transfer_from(
+                            *asset,
+                            receiver,
+                            self.env().account_id(),
+                            amount + premium,
+                        )
+                        .expect("flash loan was not repaid");
+                        self.cumulate_to_liquidity_index(*asset, *premium);
+                    }
+                    FlashLoanMode::VariableDebt => {
+                        self.increase_debt(*asset, initiator, *amount);
+                        self.env().emit_event(Borrow {
+                            reserve: *asset,
+                            user: initiator,
+                            value: *amount,
+                        });
+                    }
+                }
+                self.update_interest_rates(*asset);
+                self.env().emit_event(FlashLoan {
+                    target: receiver,
+                    initiator,
+                    asset: *asset,
+                    amount: *amount,
+                    premium: *premium,
+                    mode: *mode,
+                });
+            }
+            Ok(())
+        }
+
+        /// Returns `CallerNotOwner` error if the caller is not the owner.
+        fn ensure_owner(&self) -> Result<()> {
+            if self.env().caller() != self.owner {
+                return Err(Error::CallerNotOwner)
+            }
+            Ok(())
+        }
+
+        /// Returns `CallerNotGuardian` error if the caller is not the guardian.
+        fn ensure_guardian(&self) -> Result<()> {
+            if self.env().caller() != self.guardian {
+                return Err(Error::CallerNotGuardian)
+            }
+            Ok(())
+        }
+
+        /// Returns `Paused` error if the pool is paused.
+        fn ensure_not_paused(&self) -> Result<()> {
+            if self.paused {
+                return Err(Error::Paused)
+            }
+            Ok(())
+        }
+
+        /// Returns `CallerNotPoolAdmin` error if the caller is not the pool admin.
+        fn ensure_pool_admin(&self) -> Result<()> {
+            if self.env().caller() != self.pool_admin {
+                return Err(Error::CallerNotPoolAdmin)
+            }
+            Ok(())
+        }
+
+        /// Returns `true` if `asset` is a reserve of the pool.
+        fn is_reserve(&self, asset: AccountId) -> bool {
+            self.reserve_data.contains_key(&asset)
+        }
+
+        /// Returns `InvalidAmount` error if `value` scales down to zero at `index`.
+        fn ensure_scalable(&self, value: Balance, index: Balance) -> Result<()> {
+            if value > 0 && ray_div(value, index) == 0 {
+                return Err(Error::InvalidAmount)
+            }
+            Ok(())
+        }
+
+        /// Returns `ReserveNotFound` error if `asset` is not a reserve of the pool.
+        fn ensure_reserve(&self, asset: AccountId) -> Result<()> {
+            if !self.is_reserve(asset) {
+                return Err(Error::ReserveNotFound)
+            }
+            Ok(())
+        }
+
+        /// Returns the configuration of `asset`.
+        ///
+        /// Returns `ReserveNotFound` error if `asset` is not a reserve of the pool.
+        fn configuration_of(&self, asset: AccountId) -> Result<ReserveConfiguration> {
+            self.reserve_data
+                .get(&asset)
+                .map(|data| data.configuration)
+                .ok_or(Error::ReserveNotFound)
+        }
+
+        /// Returns the configuration of `asset` if it is an active reserve.
+        ///
+        /// Returns `ReserveNotFound` error if `asset` is not a reserve of the pool,
+        /// or `ReserveInactive` error if it is not active.
+        fn active_configuration(&self, asset: AccountId) -> Result<ReserveConfiguration> {
+            let configuration = self.configuration_of(asset)?;
+            if !configuration.is_active() {
+                return Err(Error::ReserveInactive)
+            }
+            Ok(configuration)
+        }
+
+        /// Returns `ReserveNotFound`, `ReserveInactive`, `ReserveFrozen` or
+        /// `BorrowingNotEnabled` error if `reserve` cannot be borrowed.
+        fn ensure_borrowable(&self, reserve: AccountId) -> Result<()> {
+            let configuration = self.active_configuration(reserve)?;
+            if configuration.is_frozen() {
+                return Err(Error::ReserveFrozen)
+            }
+            if !configuration.is_borrowing_enabled() {
+                return Err(Error::BorrowingNotEnabled)
+            }
+            Ok(())
+        }
+
+        /// Stores `configuration` as the configuration of the reserve `asset`.
+        fn set_configuration(
+            &mut self,
+            asset: AccountId,
+            configuration: ReserveConfiguration,
+        ) {
+            if let Some(data) = self.reserve_data.get_mut(&asset) {
+                data.configuration = configuration;
+            }
+        }
+
+        /// Returns `InsufficientCollateral` error if the deposits of `account` do
+        /// not cover its debt after borrowing another `value` tokens of `reserve`.
+        fn ensure_covered(
+            &self,
+            account: AccountId,
+            reserve: AccountId,
+            value: Balance,
+        ) -> Result<()> {
+            self.ensure_debt_covered(account, self.value_of(reserve, value)?)
+        }
+
+        /// Returns `InsufficientCollateral` error if the deposits of `account` do
+        /// not cover its debt after giving up `value` tokens of its deposit in
+        /// `reserve`.
+        ///
+        /// The scaled deposit in `reserve` is read from its `AToken` unless
+        /// `scaled_deposit` is given.
+        fn ensure_deposit_removable(
+            &self,
+            account: AccountId,
+            reserve: AccountId,
+            value: Balance,
+            scaled_deposit: Option<Balance>,
+        ) -> Result<()> {
+            let configuration = self.configuration_of(reserve)?;
+            let (_, debt, weighted_ltv, _) = self.account_values_with(
+                account,
+                scaled_deposit.map(|scaled| (reserve, scaled)),
+            )?;
+            if debt > 0 {
+                let removed = self.value_of(reserve, value)? * configuration.ltv();
+                if debt.saturating_mul(100) > weighted_ltv.saturating_sub(removed) {
+                    return Err(Error::InsufficientCollateral)
+                }
+            }
+            Ok(())
+        }
+
+        /// Returns `InsufficientCollateral` error if the deposits of `account` do
+        /// not cover its debt after borrowing another `value` in the base currency.
+        fn ensure_debt_covered(&self, account: AccountId, value: Balance) -> Result<()> {
+            let (_, debt, weighted_ltv, _) = self.account_values(account)?;
+            let debt = debt.checked_add(value).ok_or(Error::Overflow)?;
+            if debt.saturating_mul(100) > weighted_ltv {
+                return Err(Error::InsufficientCollateral)
+            }
+            Ok(())
+        }
+
+        /// Pays out `value` tokens of `reserve` to `borrower` as debt of `delegator`.
+        ///
+        /// Checks that the reserve can be borrowed, the borrower's KYC cap, the
+        /// delegator's collateral and the liquidity of the reserve, and emits a
+        /// `DelegationUsed` event. Returns `Overflow` error if the delegated debt
+        /// would no longer fit into a `Balance`.
+        fn draw_delegated(
+            &mut self,
+            reserve: AccountId,
+            delegator: AccountId,
+            borrower: AccountId,
+            value: Balance,
+        ) -> Result<()> {
+            self.ensure_borrowable(reserve)?;
+            self.update_state(reserve);
+            if let Some(kyc_registry) = self.kyc_registry {
+                let tier = ext::kyc_tier(kyc_registry, borrower);
+                let borrowed = self
+                    .delegated_borrows_value(borrower)?
+                    .checked_add(self.value_of(reserve, value)?)
+                    .ok_or(Error::Overflow)?;
+                if borrowed > self.borrow_cap(tier) {
+                    return Err(Error::BorrowCapExceeded)
+                }
+            }
+            self.ensure_covered(delegator, reserve, value)?;
+            if self.available_liquidity(reserve) < value {
+                return Err(Error::InsufficientLiquidity)
+            }
+            let debt = self
+                .delegated_debt(reserve, delegator, borrower)
+                .checked_add(value)
+                .ok_or(Error::Overflow)?;
+            let total_debt = self
+                .total_delegated_debt(reserve, delegator)
+                .checked_add(value)
+                .ok_or(Error::Overflow)?;
+            let borrowed = self
+                .delegated_borrows_of(reserve, borrower)
+                .checked_add(value)
+                .ok_or(Error::Overflow)?;
+            ext::transfer(reserve, borrower, value)?;
+            self.delegated_debts
+                .insert((reserve, delegator, borrower), debt);
+            self.total_delegated_debts
+                .insert((reserve, delegator), total_debt);
+            self.delegated_borrows.insert((reserve, borrower), borrowed);
+            self.increase_debt(reserve, delegator, value);
+            self.update_interest_rates(reserve);
+            self.env().emit_event(DelegationUsed {
+                reserve,
+                delegator,
+                borrower,
+                value,
+            });
+            Ok(())
+        }
+
+        /// Returns how much of the debt `borrower` has drawn against the collateral
+        /// of `delegator` can still be repaid to the pool.
+        ///
+        /// This is never more than the delegator owes, which liquidations may have
+        /// reduced.
+        fn repayable_delegated_debt(
+            &self,
+            reserve: AccountId,
+            delegator: AccountId,
+            borrower: AccountId,
+        ) -> Balance {
+            core::cmp::min(
+                self.delegated_debt(reserve, delegator, borrower),
+                self.debt_of(reserve, delegator),
+            )
+        }
+
+        /// Books `value` tokens of `reserve` the pool received against the debt
+        /// `borrower` has drawn against the collateral of `delegator`, and emits a
+        /// `DelegationRepaid` event.
+        fn settle_delegated(
+            &mut self,
+            reserve: AccountId,
+            delegator: AccountId,
+            borrower: AccountId,
+            value: Balance,
+        ) {
+            let debt = self.delegated_debt(reserve, delegator, borrower);
+            self.delegated_debts
+                .insert((reserve, delegator, borrower), debt - value);
+            let total_debt = self.total_delegated_debt(reserve, delegator);
+            self.total_delegated_debts
+                .insert((reserve, delegator), total_debt - value);
+            let borrowed = self.delegated_borrows_of(reserve, borrower);
+            self.delegated_borrows
+                .insert((reserve, borrower), borrowed - value);
+            self.decrease_debt(reserve, delegator, value);
+            self.env().emit_event(DelegationRepaid {
+                reserve,
+                delegator,
+                borrower,
+                value,
+            });
+        }
+
+        /// Takes up to `value` tokens from `payer` towards the loan agreement
+        /// `loan_id` and returns the amount paid.
+        fn pay_loan(
+            &mut self,
+            loan_id: LoanId,
+            payer: AccountId,
+            value: Balance,
+        ) -> Result<Balance> {
+            let loan = self
+                .loans
+                .get(&loan_id)
+                .cloned()
+                .ok_or(Error::LoanNotFound)?;
+            let value = core::cmp::min(value, loan.outstanding());
+            ext::transfer_from(loan.reserve, payer, self.env().account_id(), value)?;
+            self.settle_loan(loan_id, loan, payer, value);
+            Ok(value)
+        }
+
+        /// Books `value` tokens the pool received from `payer` towards `loan`,
+        /// stored as `loan_id`.
+        ///
+        /// The principal part repays the delegator's debt to the pool and the pool
+        /// pays the rest out to the delegator. A `LoanRepaid` event is emitted.
+        ///
+        /// # Panics
+        ///
+        /// If the reserve refuses to pay the delegator, so that the payment taken
+        /// from `payer` is reverted as well.
+        fn settle_loan(
+            &mut self,
+            loan_id: LoanId,
+            mut loan: LoanAgreement,
+            payer: AccountId,
+            value: Balance,
+        ) {
+            self.update_state(loan.reserve);
+            let principal_part = loan.principal_part(loan.repaid + value)
+                - loan.principal_part(loan.repaid);
+            let to_pool = core::cmp::min(
+                principal_part,
+                self.repayable_delegated_debt(
+                    loan.reserve,
+                    loan.delegator,
+                    loan.borrower,
+                ),
+            );
+            ext::transfer(loan.reserve, loan.delegator, value - to_pool)
+                .expect("loan payout failed");
+            self.settle_delegated(loan.reserve, loan.delegator, loan.borrower, to_pool);
+            self.update_interest_rates(loan.reserve);
+            loan.repaid += value;
+            if loan.repaid == loan.total_due() {
+                loan.status = LoanStatus::Repaid;
+            }
+            self.loans.insert(loan_id, loan);
+            self.env().emit_event(LoanRepaid {
+                loan_id,
+                payer,
+                value,
+            });
+        }
+
+        /// Returns the amount outstanding on the defaulted loan agreements of
+        /// `borrower` in `reserve`.
+        fn defaulted_debt(&self, reserve: AccountId, borrower: AccountId) -> Balance {
+            let now = self.env().block_timestamp();
+            self.loans_of(borrower)
+                .iter()
+                .filter_map(|loan_id| self.loans.get(loan_id))
+                .filter(|loan| {
+                    loan.reserve == reserve
+                        && loan.status_at(now) == LoanStatus::Defaulted
+                })
+                .map(|loan| loan.outstanding())
+                .sum()
+        }
+
+        /// Pays `value` tokens of `reserve`, already taken from `payer`, towards
+        /// the defaulted loan agreements of `borrower`, oldest first.
+        ///
+        /// `value` must not exceed the amount outstanding on them.
+        fn pay_defaulted_loans(
+            &mut self,
+            reserve: AccountId,
+            borrower: AccountId,
+            payer: AccountId,
+            value: Balance,
+        ) {
+            let now = self.env().block_timestamp();
+            let mut remaining = value;
+            for loan_id in self.loans_of(borrower) {
+                if remaining == 0 {
+                    break
+                }
+                let defaulted = self.loans.get(&loan_id).cloned().filter(|loan| {
+                    loan.reserve == reserve
+                        && loan.status_at(now) == LoanStatus::Defaulted
+                });
+                if let Some(loan) = defaulted {
+                    let paid = core::cmp::min(remaining, loan.outstanding());
+                    self.settle_loan(loan_id, loan, payer, paid);
+                    remaining -= paid;
+                }
+            }
+        }
+
+        /// Compounds the indices of `reserve` up to now and deposits the reserve
+        /// factor share of the accrued interest for the treasury.
+        ///
+        /// The interest on stable rate debt is taken as compounded at the average
+        /// stable rate since the last update, which is the rate the depositors'
+        /// liquidity rate was derived from.
+        ///
+        /// Must run before the deposits or debts of the reserve change, so that
+        /// the interest accrued so far is charged at the rates that applied.
+        fn update_state(&mut self, reserve: AccountId) {
+            let data = match self.reserve_data.get(&reserve) {
+                Some(data) => *data,
+                None => return,
+            };
+            let liquidity_index = self.reserve_normalized_income(reserve);
+            let variable_borrow_index = self.reserve_normalized_variable_debt(reserve);
+            let now = self.env().block_timestamp();
+            if let Some(data) = self.reserve_data.get_mut(&reserve) {
+                data.liquidity_index = liquidity_index;
+                data.variable_borrow_index = variable_borrow_index;
+                data.last_update_timestamp = now;
+            }
+            if let Some(treasury) = self.treasury {
+                let scaled_debt = ext::scaled_total_debt(data.variable_debt_token);
+                let variable_accrued = ray_mul(scaled_debt, variable_borrow_index)
+                    - ray_mul(scaled_debt, data.variable_borrow_index);
+                let stable_debt = ext::stable_total_debt(data.stable_debt_token);
+                let stable_growth = calculate_compounded_interest(
+                    ext::average_stable_rate(data.stable_debt_token),
+                    now - data.last_update_timestamp,
+                );
+                let stable_accrued =
+                    stable_debt.saturating_sub(ray_div(stable_debt, stable_growth));
+                let share = (variable_accrued + stable_accrued)
+                    * data.configuration.reserve_factor()
+                    / 100;
+                if share > 0 {
+                    self.increase_deposit(reserve, treasury, share);
+                    self.env().emit_event(TreasuryAccrued {
+                        reserve,
+                        treasury,
+                        value: share,
+                    });
+                }
+            }
+        }
+
+        /// Asks the `InterestRateStrategy` of `reserve` for the rates matching its
+        /// current liquidity and debt.
+        ///
+        /// The strategy prices all debt at the variable rate, so the liquidity
+        /// rate is moved by the share of stable rate debt times the difference of
+        /// the average stable rate to the variable rate. Depositors earn the
+        /// liquidity rate less the reserve factor.
+        ///
+        /// Must run after the deposits or debts of the reserve change.
+        fn update_interest_rates(&mut self, reserve: AccountId) {
+            let (strategy, stable_debt_token) = match self.reserve_data.get(&reserve) {
+                Some(data) => (data.interest_rate_strategy, data.stable_debt_token),
+                None => return,
+            };
+            let available_liquidity = self.available_liquidity(reserve);
+            let total_borrows = self.total_borrows(reserve);
+            let (mut liquidity_rate, variable_borrow_rate, stable_borrow_rate) =
+                ext::calculate_interest_rates(
+                    strategy,
+                    available_liquidity,
+                    total_borrows,
+                );
+            let stable_debt = ext::stable_total_debt(stable_debt_token);
+            if stable_debt > 0 {
+                let stable_share =
+                    ray_div(stable_debt, available_liquidity + total_borrows);
+                let average_stable_rate = ext::average_stable_rate(stable_debt_token);
+                liquidity_rate = (liquidity_rate
+                    + ray_mul(stable_share, average_stable_rate))
+                .saturating_sub(ray_mul(stable_share, variable_borrow_rate));
+            }
+            if let Some(data) = self.reserve_data.get_mut(&reserve) {
+                let depositors_share = 100 - data.configuration.reserve_factor();
+                data.current_liquidity_rate = liquidity_rate * depositors_share / 100;
+                data.current_variable_borrow_rate = variable_borrow_rate;
+                data.current_stable_borrow_rate = stable_borrow_rate;
+            }
+        }
+
+        /// Pays `amount` tokens the pool received for `reserve` out to its
+        /// depositors by raising the liquidity index.
+        ///
+        /// Must run after `update_state`, so that the index is current.
+        fn cumulate_to_liquidity_index(&mut self, reserve: AccountId, amount: Balance) {
+            let total_deposits = self.total_deposits(reserve);
+            if amount == 0 || total_deposits == 0 {
+                return
+            }
+            if let Some(data) = self.reserve_data.get_mut(&reserve) {
+                data.liquidity_index =
+                    ray_mul(RAY + ray_div(amount, total_deposits), data.liquidity_index);
+            }
+        }
+
+        /// Adds `value` to the deposit of `account` in `reserve` by minting
+        /// `AToken`s at the liquidity index.
+        ///
+        /// Nothing is minted if `value` scales down to zero at the index.
+        ///
+        /// # Panics
+        ///
+        /// If the `AToken` of the reserve refuses the mint.
+        fn increase_deposit(
+            &mut self,
+            reserve: AccountId,
+            account: AccountId,
+            value: Balance,
+        ) {
+            let a_token = match self.reserve_data.get(&reserve) {
+                Some(data) => data.a_token,
+                None => return,
+            };
+            let index = self.reserve_normalized_income(reserve);
+            if ray_div(value, index) == 0 {
+                return
+            }
+            assert!(
+                ext::mint_deposit(a_token, account, value, index),
+                "deposit mint failed"
+            );
+        }
+
+        /// Removes `value` from the deposit of `account` in `reserve` by burning
+        /// `AToken`s at the liquidity index.
+        ///
+        /// The whole deposit is removed if `value` covers it, so that rounding
+        /// leaves no dust behind. Otherwise nothing is burned if `value` scales
+        /// down to zero at the index.
+        ///
+        /// # Panics
+        ///
+        /// If the `AToken` of the reserve refuses the burn.
+        fn decrease_deposit(
+            &mut self,
+            reserve: AccountId,
+            account: AccountId,
+            value: Balance,
+        ) {
+            let a_token = match self.reserve_data.get(&reserve) {
+                Some(data) => data.a_token,
+                None => return,
+            };
+            let deposit = self.deposit_of(reserve, account);
+            let value = core::cmp::min(value, deposit);
+            let index = self.reserve_normalized_income(reserve);
+            if value == 0 || (value < deposit && ray_div(value, index) == 0) {
+                return
+            }
+            assert!(
+                ext::burn_deposit(a_token, account, value, index),
+                "deposit burn failed"
+            );
+        }
+
+        /// Adds `value` to the debt of `account` to `reserve` by minting
+        /// `VariableDebtToken`s at the variable borrow index.
+        ///
+        /// Nothing is minted if `value` scales down to zero at the index.
+        ///
+        /// # Panics
+        ///
+        /// If the `VariableDebtToken` of the reserve refuses the mint.
+        fn increase_debt(
+            &mut self,
+            reserve: AccountId,
+            account: AccountId,
+            value: Balance,
+        ) {
+            let debt_token = match self.reserve_data.get(&reserve) {
+                Some(data) => data.variable_debt_token,
+                None => return,
+            };
+            let index = self.reserve_normalized_variable_debt(reserve);
+            if ray_div(value, index) == 0 {
+                return
+            }
+            assert!(
+                ext::mint_debt(debt_token, account, value, index),
+                "debt mint failed"
+            );
+        }
+
+        /// Removes `value` from the debt of `account` to `reserve` by burning
+        /// `VariableDebtToken`s at the variable borrow index.
+        ///
+        /// The whole debt is removed if `value` covers it, so that rounding leaves
+        /// no dust behind. Otherwise nothing is burned if `value` scales down to
+        /// zero at the index.
+        ///
+        /// # Panics
+        ///
+        /// If the `VariableDebtToken` of the reserve refuses the burn.
+        fn decrease_debt(
+            &mut self,
+            reserve: AccountId,
+            account: AccountId,
+            value: Balance,
+        ) {
+            let debt_token = match self.reserve_data.get(&reserve) {
+                Some(data) => data.variable_debt_token,
+                None => return,
+            };
+            let debt = self.debt_of(reserve, account);
+            let value = core::cmp::min(value, debt);
+            let index = self.reserve_normalized_variable_debt(reserve);
+            if value == 0 || (value < debt && ray_div(value, index) == 0) {
+                return
+            }
+            assert!(
+                ext::burn_debt(debt_token, account, value, index),
+                "debt burn failed"
+            );
+        }
+
+        /// Adds `value` to the stable rate debt of `account` to `reserve` at the
+        /// current stable borrow rate of the reserve, which is returned.
+        ///
+        /// # Panics
+        ///
+        /// If the `StableDebtToken` of the reserve refuses the mint.
+        fn increase_stable_debt(
+            &mut self,
+            reserve: AccountId,
+            account: AccountId,
+            value: Balance,
+        ) -> Balance {
+            let (debt_token, rate) = match self.reserve_data.get(&reserve) {
+                Some(data) => (data.stable_debt_token, data.current_stable_borrow_rate),
+                None => return 0,
+            };
+            if value > 0 {
+                assert!(
+                    ext::mint_stable_debt(debt_token, account, value, rate),
+                    "stable debt mint failed"
+                );
+            }
+            rate
+        }
+
+        /// Removes up to `value` from the stable rate debt of `account` to
+        /// `reserve`.
+        ///
+        /// # Panics
+        ///
+        /// If the `StableDebtToken` of the reserve refuses the burn.
+        fn decrease_stable_debt(
+            &mut self,
+            reserve: AccountId,
+            account: AccountId,
+            value: Balance,
+        ) {
+            let debt_token = match self.reserve_data.get(&reserve) {
+                Some(data) => data.stable_debt_token,
+                None => return,
+            };
+            let value = core::cmp::min(value, self.stable_debt_of(reserve, account));
+            if value == 0 {
+                return
+            }
+            assert!(
+                ext::burn_stable_debt(debt_token, account, value),
+                "stable debt burn failed"
+            );
+        }
+
+        /// Returns the smallest amount of `reserve` tokens making one whole token.
+        fn unit_of(&self, reserve: AccountId) -> Balance {
+            Balance::pow(10, self.reserve_decimals(reserve).into())
+        }
+
+        /// Returns the value of `amount` tokens of `reserve` in the base currency.
+        ///
+        /// The oracle prices whole tokens, so the amount is first divided by the
+        /// precision of the reserve token.
+        fn value_of(&self, reserve: AccountId, amount: Balance) -> Result<Balance> {
+            if amount == 0 {
+                return Ok(0)
+            }
+            let price = ext::price(self.oracle, reserve)?;
+            checked_mul_div(amount, price, self.unit_of(reserve)).ok_or(Error::Overflow)
+        }
+
+        /// Returns the total value of the deposits and of the debts of `account`
+        /// across all reserves, in the base currency, followed by the sums of the
+        /// deposit values multiplied by the LTV and by the liquidation threshold
+        /// percentage of their reserve.
+        ///
+        /// The balances are read from the `AToken`, `VariableDebtToken` and
+        /// `StableDebtToken` of each reserve.
+        fn account_values(
+            &self,
+            account: AccountId,
+        ) -> Result<(Balance, Balance, Balance, Balance)> {
+            self.account_values_with(account, None)
+        }
+
+        /// Returns the same as `account_values`, but takes the scaled deposit of
+        /// `account` in the reserve of `known_deposit` as given instead of asking
+        /// that reserve's `AToken`.
+        fn account_values_with(
+            &self,
+            account: AccountId,
+            known_deposit: Option<(AccountId, Balance)>,
+        ) -> Result<(Balance, Balance, Balance, Balance)> {
+            let mut total_collateral: Balance = 0;
+            let mut total_debt: Balance = 0;
+            let mut weighted_ltv: Balance = 0;
+            let mut weighted_threshold: Balance = 0;
+            for reserve in self.reserves.iter() {
+                let data = self
+                    .reserve_data
+                    .get(reserve)
+                    .ok_or(Error::ReserveNotFound)?;
+                let configuration = data.configuration;
+                let scaled_deposit = match known_deposit {
+                    Some((known, scaled)) if known == *reserve => scaled,
+                    _ => ext::scaled_deposit_of(data.a_token, account),
+                };
+                let deposit =
+                    ray_mul(scaled_deposit, self.reserve_normalized_income(*reserve));
+                let variable_debt = ray_mul(
+                    ext::scaled_debt_of(data.variable_debt_token, account),
+                    self.reserve_normalized_variable_debt(*reserve),
+                );
+                let stable_debt = ext::stable_debt_of(data.stable_debt_token, account);
+                let collateral = self.value_of(*reserve, deposit)?;
+                let debt = variable_debt
+                    .checked_add(stable_debt)
+                    .ok_or(Error::Overflow)
+                    .and_then(|debt| self.value_of(*reserve, debt))?;
+                total_collateral = total_collateral
+                    .checked_add(collateral)
+                    .ok_or(Error::Overflow)?;
+                total_debt = total_debt.checked_add(debt).ok_or(Error::Overflow)?;
+                weighted_ltv = collateral
+                    .checked_mul(configuration.ltv())
+                    .and_then(|value| value.checked_add(weighted_ltv))
+                    .ok_or(Error::Overflow)?;
+                weighted_threshold = collateral
+                    .checked_mul(configuration.liquidation_threshold())
+                    .and_then(|value| value.checked_add(weighted_threshold))
+                    .ok_or(Error::Overflow)?;
+            }
+            Ok((
+                total_collateral,
+                total_debt,
+                weighted_ltv,
+                weighted_threshold,
+            ))
+        }
+
+        /// Returns the value of all debt `borrower` has drawn against the collateral
+        /// of delegators, in the base currency.
+        fn delegated_borrows_value(&self, borrower: AccountId) -> Result<Balance> {
+            let mut borrowed: Balance = 0;
+            for reserve in self.reserves.iter() {
+                let value = self
+                    .value_of(*reserve, self.delegated_borrows_of(*reserve, borrower))?;
+                borrowed = borrowed.checked_add(value).ok_or(Error::Overflow)?;
+            }
+            Ok(borrowed)
+        }
+    }
+
+    /// Calls into the reserve `Erc20`, the `AToken`, the `VariableDebtToken`, the
+    /// `StableDebtToken`, the `PriceOracle`, the `KycRegistry`, the
+    /// `InterestRateStrategy` and the `FlashLoanReceiver` contracts.
+    #[cfg(not(test))]
+    mod ext {
+        use super::{
+            AccountId,
+            Balance,
+            Error,
+            KycTier,
+            Result,
+        };
+        use a_token::AToken;
+        use erc20::Erc20;
+        use ink_env::call::{
+            build_call,
+            utils::ReturnType,
+            ExecutionInput,
+            FromAccountId,
+            Selector,
+        };
+        use interest_rate_strategy::{
+            DefaultReserveInterestRateStrategy,
+            InterestRateStrategy,
+        };
+        use kyc_registry::KycRegistry;
+        use price_oracle::PriceOracle;
+        use stable_debt_token::StableDebtToken;
+        use variable_debt_token::VariableDebtToken;
+
+        /// The selector of `FlashLoanReceiver::execute_operation`, the first four
+        /// bytes of the BLAKE2b-256 hash of that path.
+        const EXECUTE_OPERATION_SELECTOR: [u8; 4] = [0xF0, 0xC7, 0x2D, 0x60];
+
+        /// Moves `value` tokens of `token` from `from` to `to` using the pool's allowance.
+        pub fn transfer_from(
+            token: AccountId,
+            from: AccountId,
+            to: AccountId,
+            value: Balance,
+        ) -> Result<()> {
+            let mut token: Erc20 = FromAccountId::from_account_id(token);
+            token
+                .transfer_from(from, to, value)
+                .map_err(|_| Error::TransferFailed)
+        }
+
+        /// Moves `value` tokens of `token` from the pool to `to`.
+        pub fn transfer(token: AccountId, to: AccountId, value: Balance) -> Result<()> {
+            let mut token: Erc20 = FromAccountId::from_account_id(token);
+            token.transfer(to, value).map_err(|_| Error::TransferFailed)
+        }
+
+        /// Returns the number of decimals of `token`.
+        pub fn token_decimals(token: AccountId) -> u8 {
+            let token: Erc20 = FromAccountId::from_account_id(token);
+            token.token_decimals()
+        }
+
+        /// Returns the deposit of `owner` recorded by `a_token`, divided by the
+        /// liquidity index.
+        pub fn scaled_deposit_of(a_token: AccountId, owner: AccountId) -> Balance {
+            let a_token: AToken = FromAccountId::from_account_id(a_token);
+            a_token.scaled_balance_of(owner)
+        }
+
+        /// Returns the total deposits recorded by `a_token`, divided by the
+        /// liquidity index.
+        pub fn scaled_total_deposits(a_token: AccountId) -> Balance {
+            let a_token: AToken = FromAccountId::from_account_id(a_token);
+            a_token.scaled_total_supply()
+        }
+
+        /// Mints `a_token` worth `value` to `user` at the liquidity `index` and
+        /// returns `true` if it succeeded.
+        pub fn mint_deposit(
+            a_token: AccountId,
+            user: AccountId,
+            value: Balance,
+            index: Balance,
+        ) -> bool {
+            let mut a_token: AToken = FromAccountId::from_account_id(a_token);
+            a_token.mint(user, value, index).is_ok()
+        }
+
+        /// Burns `a_token` worth `value` of `user` at the liquidity `index` and
+        /// returns `true` if it succeeded.
+        pub fn burn_deposit(
+            a_token: AccountId,
+            user: AccountId,
+            value: Balance,
+            index: Balance,
+        ) -> bool {
+            let mut a_token: AToken = FromAccountId::from_account_id(a_token);
+            a_token.burn(user, value, index).is_ok()
+        }
+
+        /// Returns the debt of `owner` recorded by `debt_token`, divided by the
+        /// variable borrow index.
+        pub fn scaled_debt_of(debt_token: AccountId, owner: AccountId) -> Balance {
+            let debt_token: VariableDebtToken =
+                FromAccountId::from_account_id(debt_token);
+            debt_token.scaled_balance_of(owner)
+        }
+
+        /// Returns the total debt recorded by `debt_token`, divided by the variable
+        /// borrow index.
+        pub fn scaled_total_debt(debt_token: AccountId) -> Balance {
+            let debt_token: VariableDebtToken =
+                FromAccountId::from_account_id(debt_token);
+            debt_token.scaled_total_supply()
+        }
+
+        /// Mints `debt_token` worth `value` to `user` at the variable borrow `index`
+        /// and returns `true` if it succeeded.
+        pub fn mint_debt(
+            debt_token: AccountId,
+            user: AccountId,
+            value: Balance,
+            index: Balance,
+        ) -> bool {
+            let mut debt_token: VariableDebtToken =
+                FromAccountId::from_account_id(debt_token);
+            debt_token.mint(user, value, index).is_ok()
+        }
+
+        /// Burns `debt_token` worth `value` of `user` at the variable borrow `index`
+        /// and returns `true` if it succeeded.
+        pub fn burn_debt(
+            debt_token: AccountId,
+            user: AccountId,
+            value: Balance,
+            index: Balance,
+        ) -> bool {
+            let mut debt_token: VariableDebtToken =
+                FromAccountId::from_account_id(debt_token);
+            debt_token.burn(user, value, index).is_ok()
+        }
+
+        /// Returns the debt of `owner` recorded by `debt_token`, including the
+        /// interest accrued at its locked stable rate.
+        pub fn stable_debt_of(debt_token: AccountId, owner: AccountId) -> Balance {
+            let debt_token: StableDebtToken = FromAccountId::from_account_id(debt_token);
+            debt_token.balance_of(owner)
+        }
+
+        /// Returns the total debt recorded by `debt_token`, including the interest
+        /// accrued at the average stable rate.
+        pub fn stable_total_debt(debt_token: AccountId) -> Balance {
+            let debt_token: StableDebtToken = FromAccountId::from_account_id(debt_token);
+            debt_token.total_supply()
+        }
+
+        /// Returns the average stable rate of all debt recorded by `debt_token`.
+        pub fn average_stable_rate(debt_token: AccountId) -> Balance {
+            let debt_token: StableDebtToken = FromAccountId::from_account_id(debt_token);
+            debt_token.average_stable_rate()
+        }
+
+        /// Records `value` of new debt of `user` in `debt_token` at the stable
+        /// `rate` and returns `true` if it succeeded.
+        pub fn mint_stable_debt(
+            debt_token: AccountId,
+            user: AccountId,
+            value: Balance,
+            rate: Balance,
+        ) -> bool {
+            let mut debt_token: StableDebtToken =
+                FromAccountId::from_account_id(debt_token);
+            debt_token.mint(user, value, rate).is_ok()
+        }
+
+        /// Clears `value` of the debt of `user` in `debt_token` and returns `true`
+        /// if it succeeded.
+        pub fn burn_stable_debt(
+            debt_token: AccountId,
+            user: AccountId,
+            value: Balance,
+        ) -> bool {
+            let mut debt_token: StableDebtToken =
+                FromAccountId::from_account_id(debt_token);
+            debt_token.burn(user, value).is_ok()
+        }
+
+        /// Moves the rate of `user` in `debt_token` to the market stable `rate` and
+        /// returns `true` if the token agreed to rebalance it.
+        pub fn rebalance_stable_rate(
+            debt_token: AccountId,
+            user: AccountId,
+            rate: Balance,
+        ) -> bool {
+            let mut debt_token: StableDebtToken =
+                FromAccountId::from_account_id(debt_token);
+            debt_token.update_stable_rate(rate).is_ok()
+                && debt_token.rebalance_stable_rate(user).is_ok()
+        }
+
+        /// Returns the price of one token of `asset` reported by `oracle`.
+        pub fn price(oracle: AccountId, asset: AccountId) -> Result<Balance> {
+            let oracle: PriceOracle = FromAccountId::from_account_id(oracle);
+            oracle
+                .get_price(asset)
+                .map(|(price, _)| price)
+                .map_err(|_| Error::PriceUnavailable)
+        }
+
+        /// Returns the KYC tier of `account` in `registry`.
+        pub fn kyc_tier(registry: AccountId, account: AccountId) -> KycTier {
+            let registry: KycRegistry = FromAccountId::from_account_id(registry);
+            registry.tier_of(account)
+        }
+
+        /// Returns the `(liquidity_rate, variable_borrow_rate, stable_borrow_rate)`
+        /// `strategy` sets for a reserve holding `available_liquidity` with
+        /// `total_debt` borrowed.
+        pub fn calculate_interest_rates(
+            strategy: AccountId,
+            available_liquidity: Balance,
+            total_debt: Balance,
+        ) -> (Balance, Balance, Balance) {
+            let strategy: DefaultReserveInterestRateStrategy =
+                FromAccountId::from_account_id(strategy);
+            strategy.calculate_interest_rates(available_liquidity, total_debt)
+        }
+
+        /// Calls `execute_operation` on the `FlashLoanReceiver` at `receiver` and
+        /// returns `true` if it succeeded.
+        pub fn execute_operation(
+            receiver: AccountId,
+            assets: &[AccountId],
+            amounts: &[Balance],
+            premiums: &[Balance],
+            initiator: AccountId,
+            params: &[u8],
+        ) -> bool {
+            build_call::<ink_env::DefaultEnvironment>()
+                .callee(receiver)
+                .exec_input(
+                    ExecutionInput::new(Selector::new(EXECUTE_OPERATION_SELECTOR))
+                        .push_arg(assets)
+                        .push_arg(amounts)
+                        .push_arg(premiums)
+                        .push_arg(initiator)
+                        .push_arg(params),
+                )
+                .returns::<ReturnType<bool>>()
+                .fire()
+                .unwrap_or(false)
+        }
+    }
+
+    /// In-memory stand-ins for the reserve `Erc20`, the `AToken`, the
+    /// `VariableDebtToken`, the `StableDebtToken`, the `PriceOracle`, the
+    /// `KycRegistry`, the `InterestRateStrategy` and the `FlashLoanReceiver`
+    /// contracts.
+    ///
+    /// The off-chain environment cannot dispatch calls to other contracts, so
+    /// unit tests keep token balances, scaled deposit token balances, prices,
+    /// KYC tiers and interest rates here and record the flash loans received
+    /// instead.
+    #[cfg(test)]
+    mod ext {
+        use super::{
+            AccountId,
+            Balance,
+            Error,
+            KycTier,
+            Result,
+        };
+        use lending_math::{
+            ray_div,
+            ray_mul,
+        };
+        use std::{
+            cell::RefCell,
+            collections::{
+                HashMap,
+                HashSet,
+            },
+        };
+
+        thread_local! {
+            static BALANCES: RefCell<HashMap<(AccountId, AccountId), Balance>> =
+                RefCell::new(HashMap::new());
+            static SCALED_BALANCES: RefCell<HashMap<(AccountId, AccountId), Balance>> =
+                RefCell::new(HashMap::new());
+            static STABLE_DEBTS: RefCell<HashMap<(AccountId, AccountId), Balance>> =
+                RefCell::new(HashMap::new());
+            static STABLE_RATES: RefCell<HashMap<(AccountId, AccountId), Balance>> =
+                RefCell::new(HashMap::new());
+            static DECIMALS: RefCell<HashMap<AccountId, u8>> =
+                RefCell::new(HashMap::new());
+            static PRICES: RefCell<HashMap<(AccountId, AccountId), Balance>> =
+                RefCell::new(HashMap::new());
+            static KYC_TIERS: RefCell<HashMap<(AccountId, AccountId), KycTier>> =
+                RefCell::new(HashMap::new());
+            static RATES: RefCell<HashMap<AccountId, (Balance, Balance, Balance)>> =
+                RefCell::new(HashMap::new());
+            static FLASH_LOANS: RefCell<Vec<FlashLoanCall>> = RefCell::new(Vec::new());
+            static FAILING_RECEIVERS: RefCell<HashSet<AccountId>> =
+                RefCell::new(HashSet::new());
+        }
+
+        /// The `(receiver, assets, amounts, premiums, initiator, params)` of a call
+        /// to `execute_operation`.
+        pub type FlashLoanCall = (
+            AccountId,
+            Vec<AccountId>,
+            Vec<Balance>,
+            Vec<Balance>,
+            AccountId,
+            Vec<u8>,
+        );
+
+        /// Returns the `token` balance of `owner`.
+        pub fn balance_of(token: AccountId, owner: AccountId) -> Balance {
+            BALANCES.with(|balances| {
+                balances.borrow().get(&(token, owner)).copied().unwrap_or(0)
+            })
+        }
+
+        /// Sets the `token` balance of `owner` to `value`.
+        pub fn set_balance(token: AccountId, owner: AccountId, value: Balance) {
+            BALANCES.with(|balances| {
+                balances.borrow_mut().insert((token, owner), value);
+            })
+        }
+
+        pub fn transfer_from(
+            token: AccountId,
+            from: AccountId,
+            to: AccountId,
+            value: Balance,
+        ) -> Result<()> {
+            let from_balance = balance_of(token, from);
+            if from_balance < value {
+                return Err(Error::TransferFailed)
+            }
+            set_balance(token, from, from_balance - value);
+            set_balance(token, to, balance_of(token, to) + value);
+            Ok(())
+        }
+
+        pub fn transfer(token: AccountId, to: AccountId, value: Balance) -> Result<()> {
+            let pool = ink_env::account_id::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get contract address");
+            transfer_from(token, pool, to, value)
+        }
+
+        /// Sets the number of decimals of `token`.
+        pub fn set_token_decimals(token: AccountId, decimals: u8) {
+            DECIMALS.with(|all| {
+                all.borrow_mut().insert(token, decimals);
+            })
+        }
+
+        /// Returns the number of decimals of `token`, zero unless set.
+        pub fn token_decimals(token: AccountId) -> u8 {
+            DECIMALS.with(|all| all.borrow().get(&token).copied().unwrap_or(0))
+        }
+
+        /// Returns the balance of `owner` in the index-scaled `token`.
+        fn scaled_balance_of(token: AccountId, owner: AccountId) -> Balance {
+            SCALED_BALANCES.with(|balances| {
+                balances.borrow().get(&(token, owner)).copied().unwrap_or(0)
+            })
+        }
+
+        /// Returns the sum of all balances in the index-scaled `token`.
+        fn scaled_total_supply(token: AccountId) -> Balance {
+            SCALED_BALANCES.with(|balances| {
+                balances
+                    .borrow()
+                    .iter()
+                    .filter(|((balance_token, _), _)| *balance_token == token)
+                    .map(|(_, balance)| *balance)
+                    .sum()
+            })
+        }
+
+        /// Sets the balance of `owner` in the index-scaled `token` to `value`.
+        pub fn set_scaled_balance(token: AccountId, owner: AccountId, value: Balance) {
+            SCALED_BALANCES.with(|balances| {
+                balances.borrow_mut().insert((token, owner), value);
+            })
+        }
+
+        /// Mints `value` of the index-scaled `token` like `AToken::mint` and
+        /// `VariableDebtToken::mint`.
+        fn mint(
+            token: AccountId,
+            user: AccountId,
+            value: Balance,
+            index: Balance,
+        ) -> bool {
+            let scaled = ray_div(value, index);
+            if scaled == 0 {
+                return false
+            }
+            set_scaled_balance(token, user, scaled_balance_of(token, user) + scaled);
+            true
+        }
+
+        /// Burns `value` of the index-scaled `token` like `AToken::burn` and
+        /// `VariableDebtToken::burn`.
+        fn burn(
+            token: AccountId,
+            user: AccountId,
+            value: Balance,
+            index: Balance,
+        ) -> bool {
+            let balance = scaled_balance_of(token, user);
+            let held = ray_mul(balance, index);
+            if held < value {
+                return false
+            }
+            let scaled = if value == held {
+                balance
+            } else {
+                core::cmp::min(ray_div(value, index), balance)
+            };
+            if scaled == 0 {
+                return false
+            }
+            set_scaled_balance(token, user, balance - scaled);
+            true
+        }
+
+        pub fn scaled_deposit_of(a_token: AccountId, owner: AccountId) -> Balance {
+            scaled_balance_of(a_token, owner)
+        }
+
+        pub fn scaled_total_deposits(a_token: AccountId) -> Balance {
+            scaled_total_supply(a_token)
+        }
+
+        pub fn mint_deposit(
+            a_token: AccountId,
+            user: AccountId,
+            value: Balance,
+            index: Balance,
+        ) -> bool {
+            mint(a_token, user, value, index)
+        }
+
+        pub fn burn_deposit(
+            a_token: AccountId,
+            user: AccountId,
+            value: Balance,
+            index: Balance,
+        ) -> bool {
+            burn(a_token, user, value, index)
+        }
+
+        pub fn scaled_debt_of(debt_token: AccountId, owner: AccountId) -> Balance {
+            scaled_balance_of(debt_token, owner)
+        }
+
+        pub fn scaled_total_debt(debt_token: AccountId) -> Balance {
+            scaled_total_supply(debt_token)
+        }
+
+        pub fn mint_debt(
+            debt_token: AccountId,
+            user: AccountId,
+            value: Balance,
+            index: Balance,
+        ) -> bool {
+            mint(debt_token, user, value, index)
+        }
+
+        pub fn burn_debt(
+            debt_token: AccountId,
+            user: AccountId,
+            value: Balance,
+            index: Balance,
+        ) -> bool {
+            burn(debt_token, user, value, index)
+        }
+
+        /// Sets the stable rate debt of `owner` recorded by `debt_token`.
+        pub fn set_stable_debt(debt_token: AccountId, owner: AccountId, value: Balance) {
+            STABLE_DEBTS.with(|debts| {
+                debts.borrow_mut().insert((debt_token, owner), value);
+            })
+        }
+
+        pub fn stable_debt_of(debt_token: AccountId, owner: AccountId) -> Balance {
+            STABLE_DEBTS.with(|debts| {
+                debts
+                    .borrow()
+                    .get(&(debt_token, owner))
+                    .copied()
+                    .unwrap_or(0)
+            })
+        }
+
+        /// Returns the stable rate `owner` pays on its debt in `debt_token`.
+        pub fn stable_rate_of(debt_token: AccountId, owner: AccountId) -> Balance {
+            STABLE_RATES.with(|rates| {
+                rates
+                    .borrow()
+                    .get(&(debt_token, owner))
+                    .copied()
+                    .unwrap_or(0)
+            })
+        }
+
+        pub fn stable_total_debt(debt_token: AccountId) -> Balance {
+            STABLE_DEBTS.with(|debts| {
+                debts
+                    .borrow()
+                    .iter()
+                    .filter(|((token, _), _)| *token == debt_token)
+                    .map(|(_, debt)| *debt)
+                    .sum()
+            })
+        }
+
+        /// Returns the average rate of all debt in `debt_token`, weighted by
+        /// amount.
+        pub fn average_stable_rate(debt_token: AccountId) -> Balance {
+            let total = stable_total_debt(debt_token);
+            if total == 0 {
+                return 0
+            }
+            let weighted: Balance = STABLE_DEBTS.with(|debts| {
+                debts
+                    .borrow()
+                    .iter()
+                    .filter(|((token, _), _)| *token == debt_token)
+                    .map(|((_, owner), debt)| {
+                        ray_mul(stable_rate_of(debt_token, *owner), *debt)
+                    })
+                    .sum()
+            });
+            ray_div(weighted, total)
+        }
+
+        /// Adds debt like `StableDebtToken::mint`, without compounding interest.
+        pub fn mint_stable_debt(
+            debt_token: AccountId,
+            user: AccountId,
+            value: Balance,
+            rate: Balance,
+        ) -> bool {
+            if value == 0 {
+                return false
+            }
+            let debt = stable_debt_of(debt_token, user);
+            let user_rate = ray_div(
+                ray_mul(stable_rate_of(debt_token, user), debt) + ray_mul(rate, value),
+                debt + value,
+            );
+            STABLE_RATES.with(|rates| {
+                rates.borrow_mut().insert((debt_token, user), user_rate);
+            });
+            set_stable_debt(debt_token, user, debt + value);
+            true
+        }
+
+        pub fn burn_stable_debt(
+            debt_token: AccountId,
+            user: AccountId,
+            value: Balance,
+        ) -> bool {
+            let debt = stable_debt_of(debt_token, user);
+            if debt < value {
+                return false
+            }
+            set_stable_debt(debt_token, user, debt - value);
+            true
+        }
+
+        /// Moves the rate of `user` to `rate` if it owes debt at another rate.
+        pub fn rebalance_stable_rate(
+            debt_token: AccountId,
+            user: AccountId,
+            rate: Balance,
+        ) -> bool {
+            if stable_debt_of(debt_token, user) == 0
+                || stable_rate_of(debt_token, user) == rate
+            {
+                return false
+            }
+            STABLE_RATES.with(|rates| {
+                rates.borrow_mut().insert((debt_token, user), rate);
+            });
+            true
+        }
+
+        /// Sets the price of `asset` reported by `oracle`.
+        pub fn set_price(oracle: AccountId, asset: AccountId, price: Balance) {
+            PRICES.with(|prices| {
+                prices.borrow_mut().insert((oracle, asset), price);
+            })
+        }
+
+        /// Makes `oracle` stop reporting a price for `asset`.
+        pub fn remove_price(oracle: AccountId, asset: AccountId) {
+            PRICES.with(|prices| {
+                prices.borrow_mut().remove(&(oracle, asset));
+            })
+        }
+
+        pub fn price(oracle: AccountId, asset: AccountId) -> Result<Balance> {
+            PRICES.with(|prices| {
+                prices
+                    .borrow()
+                    .get(&(oracle, asset))
+                    .copied()
+                    .ok_or(Error::PriceUnavailable)
+            })
+        }
+
+        /// Sets the KYC tier of `account` in `registry`.
+        pub fn set_kyc_tier(registry: AccountId, account: AccountId, tier: KycTier) {
+            KYC_TIERS.with(|tiers| {
+                tiers.borrow_mut().insert((registry, account), tier);
+            })
+        }
+
+        pub fn kyc_tier(registry: AccountId, account: AccountId) -> KycTier {
+            KYC_TIERS.with(|tiers| {
+                tiers
+                    .borrow()
+                    .get(&(registry, account))
+                    .copied()
+                    .unwrap_or(KycTier::None)
+            })
+        }
+
+        /// Sets the `(liquidity_rate, variable_borrow_rate, stable_borrow_rate)`
+        /// `strategy` returns whatever the utilization.
+        pub fn set_interest_rates(
+            strategy: AccountId,
+            rates: (Balance, Balance, Balance),
+        ) {
+            RATES.with(|all| {
+                all.borrow_mut().insert(strategy, rates);
+            })
+        }
+
+        /// Returns the rates set for `strategy`, all zero unless set.
+        pub fn calculate_interest_rates(
+            strategy: AccountId,
+            _available_liquidity: Balance,
+            _total_debt: Balance,
+        ) -> (Balance, Balance, Balance) {
+            RATES.with(|all| all.borrow().get(&strategy).copied().unwrap_or_default())
+        }
+
+        /// Makes `execute_operation` on `receiver` return `false`.
+        pub fn fail_flash_loans(receiver: AccountId) {
+            FAILING_RECEIVERS.with(|all| {
+                all.borrow_mut().insert(receiver);
+            })
+        }
+
+        pub fn execute_operation(
+            receiver: AccountId,
+            assets: &[AccountId],
+            amounts: &[Balance],
+            premiums: &[Balance],
+            initiator: AccountId,
+            params: &[u8],
+        ) -> bool {
+            FLASH_LOANS.with(|all| {
+                all.borrow_mut().push((
+                    receiver,
+                    assets.to_vec(),
+                    amounts.to_vec(),
+                    premiums.to_vec(),
+                    initiator,
+                    params.to_vec(),
+                ))
+            });
+            !FAILING_RECEIVERS.with(|all| all.borrow().contains(&receiver))
+        }
+
+        /// Returns every call to `execute_operation` made so far.
+        pub fn flash_loans() -> Vec<FlashLoanCall> {
+            FLASH_LOANS.with(|all| all.borrow().clone())
+        }
+    }
+
+    /// Unit tests.
+    #[cfg(test)]
+    mod tests {
+        /// Imports all the definitions from the outer scope so we can use them here.
+        use super::*;
+
+        type Event = <LendingPool as ::ink_lang::BaseEvent>::Type;
+
+        use ink_lang as ink;
+
+        const DOT: [u8; 32] = [0xAA; 32];
+        const USDT: [u8; 32] = [0xCC; 32];
+        const ORACLE: [u8; 32] = [0xDD; 32];
+        const KYC_REGISTRY: [u8; 32] = [0xBB; 32];
+        /// The first byte of the token contracts of a reserve, whose other bytes
+        /// are those of the reserve, so that every reserve has its own tokens.
+        const A_TOKEN: u8 = 0x01;
+        const STABLE_DEBT_TOKEN: u8 = 0x02;
+        const VARIABLE_DEBT_TOKEN: u8 = 0x03;
+        const RATE_STRATEGY: [u8; 32] = [0x04; 32];
+
+        fn default_accounts(
+        ) -> ink_env::test::DefaultAccounts<ink_env::DefaultEnvironment> {
+            ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts")
+        }
+
+        fn contract_id() -> AccountId {
+            ink_env::account_id::<ink_env::DefaultEnvironment>()
+                .unwrap_or([0x0; 32].into())
+        }
+
+        /// Pushes a new execution context so that `caller` calls the pool.
+        fn set_caller(caller: AccountId) {
+            // Create call.
+            let mut data =
+                ink_env::test::CallData::new(ink_env::call::Selector::new([0x00; 4]));
+            data.push_arg(&caller);
+            ink_env::test::push_execution_context::<ink_env::DefaultEnvironment>(
+                caller,
+                contract_id(),
+                1000000,
+                1000000,
+                data,
+            );
+        }
+
+        /// Returns the address of the token contract of `kind` serving the reserve
+        /// at `asset`.
+        fn token_of(kind: u8, asset: [u8; 32]) -> AccountId {
+            let mut address = asset;
+            address[0] = kind;
+            address.into()
+        }
+
+        /// Lists `asset` as a reserve of `pool` with the default configuration.
+        fn init_reserve(pool: &mut LendingPool, asset: [u8; 32]) -> Result<()> {
+            pool.init_reserve(
+                asset.into(),
+                token_of(A_TOKEN, asset),
+                token_of(STABLE_DEBT_TOKEN, asset),
+                token_of(VARIABLE_DEBT_TOKEN, asset),
+                RATE_STRATEGY.into(),
+            )
+        }
+
+        /// Creates a pool with a DOT reserve priced at 10 and a USDT reserve priced
+        /// at 1, and funds Alice and Bob with tokens.
+        fn setup() -> LendingPool {
+            let accounts = default_accounts();
+            ext::set_balance(DOT.into(), accounts.alice, 1000);
+            ext::set_balance(DOT.into(), accounts.bob, 100);
+            ext::set_balance(USDT.into(), accounts.alice, 1000);
+            ext::set_price(ORACLE.into(), DOT.into(), 10);
+            ext::set_price(ORACLE.into(), USDT.into(), 1);
+            let mut pool = LendingPool::new(ORACLE.into());
+            assert_eq!(init_reserve(&mut pool, DOT), Ok(()));
+            assert_eq!(init_reserve(&mut pool, USDT), Ok(()));
+            pool
+        }
+
+        fn now() -> Timestamp {
+            ink_env::block_timestamp::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get block timestamp")
+        }
+
+        fn advance_blocks(count: usize) {
+            for _ in 0..count {
+                ink_env::test::advance_block::<ink_env::DefaultEnvironment>()
+                    .expect("Cannot advance block");
+            }
+        }
+
+        /// Returns how far a block moves the clock.
+        fn block_time() -> Timestamp {
+            let start = now();
+            advance_blocks(1);
+            now() - start
+        }
+
+        /// Returns the APR charging a tenth of the principal over `term`.
+        fn ten_percent_over(term: Timestamp) -> Balance {
+            RAY / 10 * lending_math::MILLISECONDS_PER_YEAR / term as Balance
+        }
+
+        /// Lets Alice, with 1000 DOT deposited, offer Bob a loan of 400 DOT
+        /// costing 40 DOT of interest over four blocks.
+        fn propose_loan(
+            pool: &mut LendingPool,
+            installments: u32,
+            schedule: RepaymentSchedule,
+        ) -> Timestamp {
+            let accounts = default_accounts();
+            let block_time = block_time();
+            let term = 4 * block_time;
+            assert_eq!(pool.deposit(DOT.into(), 1000), Ok(()));
+            assert_eq!(
+                pool.propose_loan(
+                    DOT.into(),
+                    accounts.bob,
+                    400,
+                    ten_percent_over(term),
+                    term,
+                    installments,
+                    schedule,
+                    block_time,
+                ),
+                Ok(0)
+            );
+            block_time
+        }
+
+        /// Lets Alice supply 1000 USDT and Bob borrow 750 USDT against 100 DOT,
+        /// the most the DOT price of 10 allows.
+        fn setup_usdt_loan() -> LendingPool {
+            let mut pool = setup();
+            let accounts = default_accounts();
+            assert_eq!(pool.deposit(USDT.into(), 1000), Ok(()));
+            set_caller(accounts.bob);
+            assert_eq!(pool.deposit(DOT.into(), 100), Ok(()));
+            assert_eq!(pool.borrow(USDT.into(), 750), Ok(()));
+            ink_env::test::pop_execution_context();
+            ext::set_balance(USDT.into(), accounts.charlie, 1000);
+            pool
+        }
+
+        #[ink::test]
+        fn new_works() {
+            let pool = LendingPool::new(ORACLE.into());
+            assert_eq!(pool.oracle(), AccountId::from(ORACLE));
+            assert_eq!(pool.reserves(), Vec::<AccountId>::new());
+            assert_eq!(pool.close_factor(), 50);
+            assert_eq!(pool.pool_admin(), default_accounts().alice);
+            assert_eq!(pool.total_deposits(DOT.into()), 0);
+            assert_eq!(pool.total_borrows(DOT.into()), 0);
+            assert_eq!(ink_env::test::recorded_events().count(), 0);
+        }
+
+        #[ink::test]
+        fn init_reserve_works() {
+            let mut pool = setup();
+            let accounts = default_accounts();
+            assert_eq!(
+                pool.reserves(),
+                vec![AccountId::from(DOT), AccountId::from(USDT)]
+            );
+            let data = pool
+                .reserve_data(DOT.into())
+                .expect("DOT must be a reserve");
+            assert_eq!(data.a_token, token_of(A_TOKEN, DOT));
+            assert_eq!(data.stable_debt_token, token_of(STABLE_DEBT_TOKEN, DOT));
+            assert_eq!(data.variable_debt_token, token_of(VARIABLE_DEBT_TOKEN, DOT));
+            assert_eq!(data.interest_rate_strategy, AccountId::from(RATE_STRATEGY));
+            assert_eq!(data.liquidity_index, RAY);
+            assert_eq!(data.variable_borrow_index, RAY);
+            assert_eq!(data.current_liquidity_rate, 0);
+            assert_eq!(data.current_variable_borrow_rate, 0);
+            assert_eq!(data.last_update_timestamp, now());
+            let configuration = data.configuration;
+            assert_eq!(configuration.ltv(), 75);
+            assert_eq!(configuration.liquidation_threshold(), 80);
+            assert_eq!(configuration.liquidation_bonus(), 5);
+            assert_eq!(configuration.reserve_factor(), 0);
+            assert!(configuration.is_active());
+            assert!(!configuration.is_frozen());
+            assert!(configuration.is_borrowing_enabled());
+            assert!(!configuration.is_stable_rate_enabled());
+            assert_eq!(pool.reserve_data([0xEE; 32].into()), None);
+
+            let emitted_events = ink_env::test::recorded_events().collect::<Vec<_>>();
+            assert_eq!(emitted_events.len(), 2);
+            let decoded =
+                <Event as scale::Decode>::decode(&mut &emitted_events[0].data[..])
+                    .expect("encountered invalid contract event data buffer");
+            match decoded {
+                Event::ReserveInitialized(ReserveInitialized {
+                    asset, a_token, ..
+                }) => {
+                    assert_eq!(asset, AccountId::from(DOT));
+                    assert_eq!(a_token, token_of(A_TOKEN, DOT));
+                }
+                _ => panic!("encountered unexpected event kind"),
+            }
+
+            assert_eq!(
+                init_reserve(&mut pool, DOT),
+                Err(Error::ReserveAlreadyAdded)
+            );
+            set_caller(accounts.bob);
+            assert_eq!(
+                init_reserve(&mut pool, [0xEE; 32]),
+                Err(Error::CallerNotPoolAdmin)
+            );
+            assert_eq!(
+                pool.deposit([0xEE; 32].into(), 1),
+                Err(Error::ReserveNotFound)
+            );
+        }
+
+        #[ink::test]
+        fn reserve_configuration_packs_all_fields() {
+            let mut configuration = ReserveConfiguration::default();
+            configuration.set_ltv(0xFFFF);
+            configuration.set_liquidation_threshold(80);
+            configuration.set_liquidation_bonus(5);
+            configuration.set_decimals(u8::MAX);
+            configuration.set_frozen(true);
+            configuration.set_stable_rate_enabled(true);
+            configuration.set_reserve_factor(10);
+            assert_eq!(configuration.ltv(), 0xFFFF);
+            assert_eq!(configuration.liquidation_threshold(), 80);
+            assert_eq!(configuration.liquidation_bonus(), 5);
+            assert_eq!(configuration.decimals(), u8::MAX);
+            assert!(!configuration.is_active());
+            assert!(configuration.is_frozen());
+            assert!(!configuration.is_borrowing_enabled());
+            assert!(configuration.is_stable_rate_enabled());
+            assert_eq!(configuration.reserve_factor(), 10);
+
+            // Overwriting a field leaves its neighbours alone.
+            configuration.set_ltv(0);
+            configuration.set_frozen(false);
+            configuration.set_active(true);
+            assert_eq!(configuration.ltv(), 0);
+            assert_eq!(configuration.liquidation_threshold(), 80);
+            assert!(configuration.is_active());
+            assert!(!configuration.is_frozen());
+            assert!(configuration.is_stable_rate_enabled());
+        }
+
+        #[ink::test]
+        fn only_pool_admin_configures_reserves() {
+            let mut pool = setup();
+            let accounts = default_accounts();
+            let dot = AccountId::from(DOT);
+
+            set_caller(accounts.bob);
+            assert_eq!(pool.set_ltv(dot, 50), Err(Error::CallerNotPoolAdmin));
+            assert_eq!(
+                pool.set_liquidation_threshold(dot, 60),
+                Err(Error::CallerNotPoolAdmin)
+            );
+            assert_eq!(
+                pool.set_liquidation_bonus(dot, 10),
+                Err(Error::CallerNotPoolAdmin)
+            );
+            assert_eq!(
+                pool.set_reserve_factor(dot, 10),
+                Err(Error::CallerNotPoolAdmin)
+            );
+            assert_eq!(
+                pool.set_borrowing_enabled(dot, false),
+                Err(Error::CallerNotPoolAdmin)
+            );
+            assert_eq!(
+                pool.set_stable_rate_enabled(dot, true),
+                Err(Error::CallerNotPoolAdmin)
+            );
+            assert_eq!(
+                pool.set_reserve_active(dot, false),
+                Err(Error::CallerNotPoolAdmin)
+            );
+            assert_eq!(
+                pool.set_pool_admin(accounts.bob),
+                Err(Error::CallerNotOwner)
+            );
+            ink_env::test::pop_execution_context();
+
+            assert_eq!(pool.set_pool_admin(accounts.bob), Ok(()));
+            assert_eq!(pool.pool_admin(), accounts.bob);
+            assert_eq!(pool.set_ltv(dot, 50), Err(Error::CallerNotPoolAdmin));
+            set_caller(accounts.bob);
+            assert_eq!(pool.set_ltv(dot, 50), Ok(()));
+        }
+
+        #[ink::test]
+        fn reserve_setters_work() {
+            let mut pool = setup();
+            let dot = AccountId::from(DOT);
+            assert_eq!(
+                pool.set_ltv([0xEE; 32].into(), 50),
+                Err(Error::ReserveNotFound)
+            );
+            assert_eq!(pool.set_ltv(dot, 81), Err(Error::InvalidParameter));
+            assert_eq!(
+                pool.set_liquidation_threshold(dot, 74),
+                Err(Error::InvalidParameter)
+            );
+            assert_eq!(
+                pool.set_liquidation_threshold(dot, 101),
+                Err(Error::InvalidParameter)
+            );
+            assert_eq!(
+                pool.set_liquidation_bonus(dot, 101),
+                Err(Error::InvalidParameter)
+            );
+            assert_eq!(
+                pool.set_reserve_factor(dot, 101),
+                Err(Error::InvalidParameter)
+            );
+
+            assert_eq!(pool.set_ltv(dot, 50), Ok(()));
+            assert_eq!(pool.set_liquidation_threshold(dot, 60), Ok(()));
+            assert_eq!(pool.set_liquidation_bonus(dot, 10), Ok(()));
+            assert_eq!(pool.set_reserve_factor(dot, 20), Ok(()));
+            assert_eq!(pool.set_borrowing_enabled(dot, false), Ok(()));
+            assert_eq!(pool.set_stable_rate_enabled(dot, true), Ok(()));
+            assert_eq!(pool.set_reserve_active(dot, false), Ok(()));
+            let configuration = pool
+                .reserve_data(dot)
+                .expect("DOT must be a reserve")
+                .configuration;
+            assert_eq!(configuration.ltv(), 50);
+            assert_eq!(configuration.liquidation_threshold(), 60);
+            assert_eq!(configuration.liquidation_bonus(), 10);
+            assert_eq!(configuration.reserve_factor(), 20);
+            assert!(!configuration.is_borrowing_enabled());
+            assert!(configuration.is_stable_rate_enabled());
+            assert!(!configuration.is_active());
+
+            let emitted_events = ink_env::test::recorded_events()
+                .skip(2)
+                .map(|event| {
+                    <Event as scale::Decode>::decode(&mut &event.data[..])
+                        .expect("encountered invalid contract event data buffer")
+                })
+                .collect::<Vec<_>>();
+            assert_eq!(emitted_events.len(), 7);
+            assert!(matches!(
+                emitted_events[0],
+                Event::LtvChanged(LtvChanged { ltv: 50, .. })
+            ));
+            assert!(matches!(
+                emitted_events[1],
+                Event::LiquidationThresholdChanged(LiquidationThresholdChanged {
+                    liquidation_threshold: 60,
+                    ..
+                })
+            ));
+            assert!(matches!(
+                emitted_events[2],
+                Event::LiquidationBonusChanged(LiquidationBonusChanged {
+                    liquidation_bonus: 10,
+                    ..
+                })
+            ));
+            assert!(matches!(
+                emitted_events[3],
+                Event::ReserveFactorChanged(ReserveFactorChanged {
+                    reserve_factor: 20,
+                    ..
+                })
+            ));
+            assert!(matches!(
+                emitted_events[4],
+                Event::BorrowingEnabledChanged(BorrowingEnabledChanged {
+                    enabled: false,
+                    ..
+                })
+            ));
+            assert!(matches!(
+                emitted_events[5],
+                Event::StableRateEnabledChanged(StableRateEnabledChanged {
+                    enabled: true,
+                    ..
+                })
+            ));
+            assert!(matches!(
+                emitted_events[6],
+                Event::ReserveActiveChanged(ReserveActiveChanged { active: false, .. })
+            ));
+        }
+
+        #[ink::test]
+        fn interest_rate_strategy_can_be_replaced() {
+            let mut pool = setup();
+            let accounts = default_accounts();
+            let dot = AccountId::from(DOT);
+            let strategy = AccountId::from([0x05; 32]);
+            ext::set_interest_rates(RATE_STRATEGY.into(), (0, RAY / 10, RAY / 5));
+            ext::set_interest_rates(strategy, (RAY / 20, RAY / 2, RAY));
+            assert_eq!(pool.deposit(dot, 100), Ok(()));
+
+            set_caller(accounts.bob);
+            assert_eq!(
+                pool.set_reserve_interest_rate_strategy(dot, strategy),
+                Err(Error::CallerNotPoolAdmin)
+            );
+            ink_env::test::pop_execution_context();
+            assert_eq!(
+                pool.set_reserve_interest_rate_strategy([0xBB; 32].into(), strategy),
+                Err(Error::ReserveNotFound)
+            );
+
+            let events_before = ink_env::test::recorded_events().count();
+            assert_eq!(
+                pool.set_reserve_interest_rate_strategy(dot, strategy),
+                Ok(())
+            );
+            let data = pool.reserve_data(dot).expect("DOT must be a reserve");
+            assert_eq!(data.interest_rate_strategy, strategy);
+            assert_eq!(data.current_liquidity_rate, RAY / 20);
+            assert_eq!(data.current_variable_borrow_rate, RAY / 2);
+            assert_eq!(data.current_stable_borrow_rate, RAY);
+
+            let emitted_events = ink_env::test::recorded_events()
+                .skip(events_before)
+                .collect::<Vec<_>>();
+            assert_eq!(emitted_events.len(), 1);
+            let decoded_event =
+                <Event as scale::Decode>::decode(&mut &emitted_events[0].data[..])
+                    .expect("encountered invalid contract event data buffer");
+            assert!(matches!(
+                decoded_event,
+                Event::ReserveInterestRateStrategyChanged(
+                    ReserveInterestRateStrategyChanged { asset, strategy: changed }
+                ) if asset == dot && changed == strategy
+            ));
+        }
+
+        #[ink::test]
+        fn per_reserve_ltv_limits_borrows() {
+            let mut pool = setup();
+            let accounts = default_accounts();
+            assert_eq!(pool.deposit(USDT.into(), 1000), Ok(()));
+            assert_eq!(pool.set_ltv(DOT.into(), 50), Ok(()));
+
+            // 100 DOT are worth 1000 USDT, of which half can be borrowed.
+            set_caller(accounts.bob);
+            assert_eq!(pool.deposit(DOT.into(), 100), Ok(()));
+            assert_eq!(
+                pool.borrow(USDT.into(), 501),
+                Err(Error::InsufficientCollateral)
+            );
+            assert_eq!(pool.borrow(USDT.into(), 500), Ok(()));
+            assert_eq!(
+                pool.withdraw(DOT.into(), 1),
+                Err(Error::InsufficientCollateral)
+            );
+            let data = pool
+                .get_user_account_data(accounts.bob)
+                .expect("reserves must be priced");
+            assert_eq!(data.ltv, 50);
+            assert_eq!(data.available_borrows, 0);
+        }
+
+        #[ink::test]
+        fn disabled_and_inactive_reserves_are_rejected() {
+            let mut pool = setup();
+            let dot = AccountId::from(DOT);
+            assert_eq!(pool.deposit(dot, 100), Ok(()));
+
+            assert_eq!(pool.set_borrowing_enabled(dot, false), Ok(()));
+            assert_eq!(pool.borrow(dot, 10), Err(Error::BorrowingNotEnabled));
+            assert_eq!(pool.set_borrowing_enabled(dot, true), Ok(()));
+            assert_eq!(pool.borrow(dot, 10), Ok(()));
+
+            assert_eq!(pool.set_reserve_active(dot, false), Ok(()));
+            assert_eq!(pool.deposit(dot, 1), Err(Error::ReserveInactive));
+            assert_eq!(pool.withdraw(dot, 1), Err(Error::ReserveInactive));
+            assert_eq!(pool.borrow(dot, 1), Err(Error::ReserveInactive));
+            assert_eq!(pool.repay(dot, 1), Err(Error::ReserveInactive));
+            assert_eq!(pool.set_reserve_active(dot, true), Ok(()));
+            assert_eq!(pool.repay(dot, 10), Ok(()));
+        }
+
+        #[ink::test]
+        fn deposit_works() {
+            let mut pool = setup();
+            let accounts = default_accounts();
+
+            assert_eq!(pool.deposit(DOT.into(), 400), Ok(()));
+            assert_eq!(pool.deposit_of(DOT.into(), accounts.alice), 400);
+            assert_eq!(pool.total_deposits(DOT.into()), 400);
+            assert_eq!(ext::balance_of(DOT.into(), accounts.alice), 600);
+            assert_eq!(ext::balance_of(DOT.into(), contract_id()), 400);
+            // Two `ReserveInitialized` and a `Deposit`.
+            assert_eq!(ink_env::test::recorded_events().count(), 3);
+        }
+
+        #[ink::test]
+        fn deposit_without_funds_fails() {
+            let mut pool = setup();
+            let accounts = default_accounts();
+
+            assert_eq!(pool.deposit(DOT.into(), 1001), Err(Error::TransferFailed));
+            assert_eq!(pool.deposit_of(DOT.into(), accounts.alice), 0);
+            // Only the two `ReserveInitialized`.
+            assert_eq!(ink_env::test::recorded_events().count(), 2);
+        }
+
+        #[ink::test]
+        fn dust_amounts_are_rejected_or_skipped() {
+            let mut pool = setup();
+            let accounts = default_accounts();
+            let dot = AccountId::from(DOT);
+            assert_eq!(pool.deposit(dot, 600), Ok(()));
+
+            // At an index of three rays a single token scales down to nothing.
+            if let Some(data) = pool.reserve_data.get_mut(&dot) {
+                data.liquidity_index = 3 * RAY;
+                data.variable_borrow_index = 3 * RAY;
+            }
+            assert_eq!(pool.deposit(dot, 1), Err(Error::InvalidAmount));
+            assert_eq!(pool.withdraw(dot, 1), Err(Error::InvalidAmount));
+            assert_eq!(pool.borrow(dot, 1), Err(Error::InvalidAmount));
+            assert_eq!(ext::balance_of(dot, accounts.alice), 400);
+
+            // The pool's own bookkeeping skips such amounts instead of failing.
+            pool.increase_deposit(dot, accounts.bob, 1);
+            pool.decrease_deposit(dot, accounts.alice, 1);
+            pool.increase_debt(dot, accounts.bob, 1);
+            assert_eq!(pool.deposit_of(dot, accounts.bob), 0);
+            assert_eq!(pool.deposit_of(dot, accounts.alice), 1800);
+            assert_eq!(pool.debt_of(dot, accounts.bob), 0);
+
+            // A whole balance is still cleared, however small.
+            pool.increase_debt(dot, accounts.bob, 3);
+            assert_eq!(pool.debt_of(dot, accounts.bob), 3);
+            pool.decrease_debt(dot, accounts.bob, 1);
+            assert_eq!(pool.debt_of(dot, accounts.bob), 3);
+            pool.decrease_debt(dot, accounts.bob, 3);
+            assert_eq!(pool.debt_of(dot, accounts.bob), 0);
+        }
+
+        #[ink::test]
+        fn borrow_is_limited_by_collateral() {
+            let mut pool = setup();
+            let accounts = default_accounts();
+            assert_eq!(pool.deposit(DOT.into(), 1000), Ok(()));
+
+            set_caller(accounts.bob);
+            assert_eq!(
+                pool.borrow(DOT.into(), 1),
+                Err(Error::InsufficientCollateral)
+            );
+            assert_eq!(pool.deposit(DOT.into(), 100), Ok(()));
+            assert_eq!(
+                pool.borrow(DOT.into(), 76),
+                Err(Error::InsufficientCollateral)
+            );
+            assert_eq!(pool.borrow(DOT.into(), 75), Ok(()));
+            assert_eq!(pool.debt_of(DOT.into(), accounts.bob), 75);
+        }
+
+        #[ink::test]
+        fn borrow_against_other_reserve_uses_prices() {
+            let mut pool = setup();
+            let accounts = default_accounts();
+            assert_eq!(pool.deposit(USDT.into(), 1000), Ok(()));
+
+            // 100 DOT are worth 1000 USDT.
+            set_caller(accounts.bob);
+            assert_eq!(pool.deposit(DOT.into(), 100), Ok(()));
+            assert_eq!(
+                pool.borrow(USDT.into(), 751),
+                Err(Error::InsufficientCollateral)
+            );
+            assert_eq!(pool.borrow(USDT.into(), 750), Ok(()));
+            assert_eq!(ext::balance_of(USDT.into(), accounts.bob), 750);
+            assert_eq!(pool.available_liquidity(USDT.into()), 250);
+        }
+
+        #[ink::test]
+        fn withdraw_must_keep_debt_covered() {
+            let mut pool = setup();
+            let accounts = default_accounts();
+
+            set_caller(accounts.bob);
+            assert_eq!(pool.deposit(DOT.into(), 100), Ok(()));
+            assert_eq!(pool.borrow(DOT.into(), 30), Ok(()));
+            assert_eq!(
+                pool.withdraw(DOT.into(), 61),
+                Err(Error::InsufficientCollateral)
+            );
+            assert_eq!(pool.withdraw(DOT.into(), 60), Ok(()));
+            assert_eq!(pool.deposit_of(DOT.into(), accounts.bob), 40);
+        }
+
+        #[ink::test]
+        fn deposit_transfers_must_keep_debt_covered() {
+            let mut pool = setup();
+            let accounts = default_accounts();
+
+            set_caller(accounts.bob);
+            assert_eq!(pool.deposit(DOT.into(), 100), Ok(()));
+            assert!(pool.can_transfer_deposit(DOT.into(), accounts.bob, 100, 100));
+            assert_eq!(pool.borrow(DOT.into(), 30), Ok(()));
+            assert!(!pool.can_transfer_deposit(DOT.into(), accounts.bob, 61, 100));
+            assert!(pool.can_transfer_deposit(DOT.into(), accounts.bob, 60, 100));
+
+            // The balance passed by the token is used instead of asking it.
+            let a_token = token_of(A_TOKEN, DOT);
+            ext::set_scaled_balance(a_token, accounts.bob, 0);
+            assert!(pool.can_transfer_deposit(DOT.into(), accounts.bob, 60, 100));
+            assert!(!pool.can_transfer_deposit(DOT.into(), accounts.bob, 1, 40));
+
+            set_caller(accounts.alice);
+            assert_eq!(pool.pause(), Ok(()));
+            assert!(!pool.can_transfer_deposit(DOT.into(), accounts.bob, 1, 100));
+        }
+
+        #[ink::test]
+        fn repay_takes_at_most_the_debt() {
+            let mut pool = setup();
+            let accounts = default_accounts();
+
+            set_caller(accounts.bob);
+            assert_eq!(pool.deposit(DOT.into(), 100), Ok(()));
+            assert_eq!(pool.borrow(DOT.into(), 50), Ok(()));
+            let debt_token = token_of(VARIABLE_DEBT_TOKEN, DOT);
+            assert_eq!(ext::scaled_debt_of(debt_token, accounts.bob), 50);
+            assert_eq!(pool.repay(DOT.into(), 80), Ok(()));
+            assert_eq!(pool.debt_of(DOT.into(), accounts.bob), 0);
+            assert_eq!(ext::scaled_debt_of(debt_token, accounts.bob), 0);
+            assert_eq!(ext::balance_of(DOT.into(), accounts.bob), 50);
+        }
+
+        #[ink::test]
+        fn borrow_stable_works() {
+            let mut pool = setup();
+            let accounts = default_accounts();
+            let dot = AccountId::from(DOT);
+            let debt_token = token_of(STABLE_DEBT_TOKEN, DOT);
+            ext::set_interest_rates(RATE_STRATEGY.into(), (0, RAY / 10, RAY / 5));
+            assert_eq!(pool.deposit(dot, 1000), Ok(()));
+
+            set_caller(accounts.bob);
+            assert_eq!(pool.deposit(dot, 100), Ok(()));
+            assert_eq!(
+                pool.borrow_stable(dot, 50),
+                Err(Error::StableBorrowingNotEnabled)
+            );
+            ink_env::test::pop_execution_context();
+            assert_eq!(pool.set_stable_rate_enabled(dot, true), Ok(()));
+
+            set_caller(accounts.bob);
+            assert_eq!(pool.borrow_stable(dot, 50), Ok(()));
+            assert_eq!(pool.stable_debt_of(dot, accounts.bob), 50);
+            assert_eq!(pool.debt_of(dot, accounts.bob), 0);
+            assert_eq!(ext::stable_rate_of(debt_token, accounts.bob), RAY / 5);
+            assert_eq!(pool.total_borrows(dot), 50);
+            assert_eq!(pool.available_liquidity(dot), 1050);
+            assert_eq!(ext::balance_of(dot, accounts.bob), 50);
+
+            // Depositors earn the stable rate on the stable share of the reserve.
+            let stable_share = ray_div(50, 1100);
+            let data = pool.reserve_data(dot).expect("DOT is a reserve");
+            assert_eq!(
+                data.current_liquidity_rate,
+                ray_mul(stable_share, RAY / 5) - ray_mul(stable_share, RAY / 10)
+            );
+
+            assert_eq!(pool.repay_stable(dot, 80), Ok(()));
+            assert_eq!(pool.stable_debt_of(dot, accounts.bob), 0);
+            assert_eq!(ext::balance_of(dot, accounts.bob), 0);
+            assert_eq!(pool.total_borrows(dot), 0);
+        }
+
+        #[ink::test]
+        fn rebalance_stable_borrow_rate_works() {
+            let mut pool = setup();
+            let accounts = default_accounts();
+            let dot = AccountId::from(DOT);
+            let debt_token = token_of(STABLE_DEBT_TOKEN, DOT);
+            ext::set_interest_rates(RATE_STRATEGY.into(), (0, RAY / 10, RAY / 5));
+            assert_eq!(pool.set_stable_rate_enabled(dot, true), Ok(()));
+            assert_eq!(pool.deposit(dot, 500), Ok(()));
+
+            set_caller(accounts.bob);
+            assert_eq!(pool.deposit(dot, 100), Ok(()));
+            assert_eq!(pool.borrow_stable(dot, 50), Ok(()));
+            assert_eq!(
+                pool.rebalance_stable_borrow_rate(dot, accounts.bob),
+                Err(Error::RebalanceNotNeeded)
+            );
+
+            // The market rate moves once the rates are updated.
+            ext::set_interest_rates(RATE_STRATEGY.into(), (0, RAY / 10, RAY / 2));
+            ink_env::test::pop_execution_context();
+            assert_eq!(pool.deposit(dot, 100), Ok(()));
+            assert_eq!(
+                pool.rebalance_stable_borrow_rate(dot, accounts.charlie),
+                Err(Error::RebalanceNotNeeded)
+            );
+            assert_eq!(pool.rebalance_stable_borrow_rate(dot, accounts.bob), Ok(()));
+            assert_eq!(ext::stable_rate_of(debt_token, accounts.bob), RAY / 2);
+            assert_eq!(pool.stable_debt_of(dot, accounts.bob), 50);
+            assert_eq!(
+                pool.rebalance_stable_borrow_rate(dot, accounts.bob),
+                Err(Error::RebalanceNotNeeded)
+            );
+        }
+
+        #[ink::test]
+        fn delegate_credit_works() {
+            let mut pool = setup();
+            let accounts = default_accounts();
+            let dot = AccountId::from(DOT);
+
+            assert_eq!(pool.credit_allowance(dot, accounts.alice, accounts.bob), 0);
+            assert_eq!(pool.delegate_credit(dot, accounts.bob, 300), Ok(()));
+            assert_eq!(
+                pool.credit_allowance(dot, accounts.alice, accounts.bob),
+                300
+            );
+            // Delegating again overwrites the allowance.
+            assert_eq!(pool.delegate_credit(dot, accounts.bob, 200), Ok(()));
+            assert_eq!(
+                pool.credit_allowance(dot, accounts.alice, accounts.bob),
+                200
+            );
+            // Credit is delegated per reserve.
+            assert_eq!(
+                pool.credit_allowance(USDT.into(), accounts.alice, accounts.bob),
+                0
+            );
+            // Two `ReserveInitialized` and two `CreditDelegated`.
+            assert_eq!(ink_env::test::recorded_events().count(), 4);
+        }
+
+        #[ink::test]
+        fn borrow_on_behalf_draws_down_allowance() {
+            let mut pool = setup();
+            let accounts = default_accounts();
+            let dot = AccountId::from(DOT);
+            assert_eq!(pool.deposit(dot, 1000), Ok(()));
+            assert_eq!(pool.delegate_credit(dot, accounts.charlie, 300), Ok(()));
+
+            // Charlie has no deposit but may borrow against Alice's.
+            set_caller(accounts.charlie);
+            assert_eq!(pool.borrow(dot, 1), Err(Error::InsufficientCollateral));
+            assert_eq!(pool.borrow_on_behalf(dot, accounts.alice, 200), Ok(()));
+            assert_eq!(ext::balance_of(dot, accounts.charlie), 200);
+            assert_eq!(
+                pool.credit_allowance(dot, accounts.alice, accounts.charlie),
+                100
+            );
+            assert_eq!(
+                pool.delegated_debt(dot, accounts.alice, accounts.charlie),
+                200
+            );
+            assert_eq!(pool.total_delegated_debt(dot, accounts.alice), 200);
+            // The debt is owed by the delegator.
+            assert_eq!(pool.debt_of(dot, accounts.alice), 200);
+            assert_eq!(pool.debt_of(dot, accounts.charlie), 0);
+            assert_eq!(pool.total_borrows(dot), 200);
+
+            assert_eq!(
+                pool.borrow_on_behalf(dot, accounts.alice, 101),
+                Err(Error::InsufficientCreditAllowance)
+            );
+            assert_eq!(
+                pool.borrow_on_behalf(dot, accounts.bob, 1),
+                Err(Error::InsufficientCreditAllowance)
+            );
+        }
+
+        #[ink::test]
+        fn revoked_credit_cannot_be_used() {
+            let mut pool = setup();
+            let accounts = default_accounts();
+            let dot = AccountId::from(DOT);
+            assert_eq!(pool.deposit(dot, 1000), Ok(()));
+            assert_eq!(pool.delegate_credit(dot, accounts.charlie, 300), Ok(()));
+
+            set_caller(accounts.charlie);
+            assert_eq!(pool.borrow_on_behalf(dot, accounts.alice, 100), Ok(()));
+
+            ink_env::test::pop_execution_context();
+            assert_eq!(pool.delegate_credit(dot, accounts.charlie, 0), Ok(()));
+
+            set_caller(accounts.charlie);
+            assert_eq!(
+                pool.borrow_on_behalf(dot, accounts.alice, 1),
+                Err(Error::InsufficientCreditAllowance)
+            );
+            // Revoking does not forgive what was already drawn.
+            assert_eq!(
+                pool.delegated_debt(dot, accounts.alice, accounts.charlie),
+                100
+            );
+        }
+
+        #[ink::test]
+        fn delegated_borrows_are_limited_by_delegator_collateral() {
+            let mut pool = setup();
+            let accounts = default_accounts();
+            let dot = AccountId::from(DOT);
+            assert_eq!(pool.deposit(dot, 400), Ok(()));
+            assert_eq!(pool.delegate_credit(dot, accounts.bob, 1000), Ok(()));
+            assert_eq!(pool.delegate_credit(dot, accounts.charlie, 1000), Ok(()));
+            assert_eq!(pool.borrow(dot, 100), Ok(()));
+
+            set_caller(accounts.bob);
+            assert_eq!(pool.borrow_on_behalf(dot, accounts.alice, 150), Ok(()));
+            ink_env::test::pop_execution_context();
+
+            set_caller(accounts.charlie);
+            assert_eq!(
+                pool.borrow_on_behalf(dot, accounts.alice, 51),
+                Err(Error::InsufficientCollateral)
+            );
+            assert_eq!(pool.borrow_on_behalf(dot, accounts.alice, 50), Ok(()));
+            ink_env::test::pop_execution_context();
+
+            // Alice cannot pull the deposit backing the delegated debt.
+            assert_eq!(pool.withdraw(dot, 1), Err(Error::InsufficientCollateral));
+        }
+
+        #[ink::test]
+        fn repay_on_behalf_works() {
+            let mut pool = setup();
+            let accounts = default_accounts();
+            let dot = AccountId::from(DOT);
+            assert_eq!(pool.deposit(dot, 1000), Ok(()));
+            assert_eq!(pool.delegate_credit(dot, accounts.bob, 300), Ok(()));
+
+            set_caller(accounts.bob);
+            assert_eq!(pool.borrow_on_behalf(dot, accounts.alice, 300), Ok(()));
+            assert_eq!(pool.repay_on_behalf(dot, accounts.alice, 500), Ok(()));
+            assert_eq!(ext::balance_of(dot, accounts.bob), 100);
+            assert_eq!(pool.delegated_debt(dot, accounts.alice, accounts.bob), 0);
+            assert_eq!(pool.total_delegated_debt(dot, accounts.alice), 0);
+            assert_eq!(pool.credit_allowance(dot, accounts.alice, accounts.bob), 0);
+            assert_eq!(pool.debt_of(dot, accounts.alice), 0);
+            assert_eq!(pool.total_borrows(dot), 0);
+            ink_env::test::pop_execution_context();
+
+            // With the debt repaid Alice can withdraw everything.
+            assert_eq!(pool.withdraw(dot, 1000), Ok(()));
+        }
+
+        #[ink::test]
+        fn only_owner_changes_kyc_settings() {
+            let mut pool = setup();
+            let accounts = default_accounts();
+            assert_eq!(pool.owner(), accounts.alice);
+            assert_eq!(pool.kyc_registry(), None);
+
+            set_caller(accounts.bob);
+            assert_eq!(
+                pool.set_kyc_registry(Some(KYC_REGISTRY.into())),
+                Err(Error::CallerNotOwner)
+            );
+            assert_eq!(
+                pool.set_borrow_cap(KycTier::L1, 100),
+                Err(Error::CallerNotOwner)
+            );
+            ink_env::test::pop_execution_context();
+
+            assert_eq!(pool.set_kyc_registry(Some(KYC_REGISTRY.into())), Ok(()));
+            assert_eq!(pool.set_borrow_cap(KycTier::L1, 100), Ok(()));
+            assert_eq!(pool.kyc_registry(), Some(AccountId::from(KYC_REGISTRY)));
+            assert_eq!(pool.borrow_cap(KycTier::L1), 100);
+            assert_eq!(pool.borrow_cap(KycTier::L2), 0);
+        }
+
+        #[ink::test]
+        fn delegated_borrows_are_capped_by_kyc_tier() {
+            let mut pool = setup();
+            let accounts = default_accounts();
+            let dot = AccountId::from(DOT);
+            assert_eq!(pool.deposit(dot, 1000), Ok(()));
+            assert_eq!(pool.delegate_credit(dot, accounts.bob, 500), Ok(()));
+            assert_eq!(pool.delegate_credit(dot, accounts.charlie, 500), Ok(()));
+            assert_eq!(pool.set_kyc_registry(Some(KYC_REGISTRY.into())), Ok(()));
+            // Caps are in the base currency; one DOT is worth 10.
+            assert_eq!(pool.set_borrow_cap(KycTier::L1, 1000), Ok(()));
+            assert_eq!(pool.set_borrow_cap(KycTier::L2, 4000), Ok(()));
+            ext::set_kyc_tier(KYC_REGISTRY.into(), accounts.bob, KycTier::L1);
+            ext::set_kyc_tier(KYC_REGISTRY.into(), accounts.charlie, KycTier::L2);
+
+            // Unverified borrowers cannot borrow unsecured.
+            assert_eq!(pool.delegate_credit(dot, accounts.django, 500), Ok(()));
+            set_caller(accounts.django);
+            assert_eq!(
+                pool.borrow_on_behalf(dot, accounts.alice, 1),
+                Err(Error::BorrowCapExceeded)
+            );
+            ink_env::test::pop_execution_context();
+
+            set_caller(accounts.bob);
+            assert_eq!(pool.borrow_on_behalf(dot, accounts.alice, 60), Ok(()));
+            assert_eq!(
+                pool.borrow_on_behalf(dot, accounts.alice, 41),
+                Err(Error::BorrowCapExceeded)
+            );
+            assert_eq!(pool.borrow_on_behalf(dot, accounts.alice, 40), Ok(()));
+            assert_eq!(pool.delegated_borrows_of(dot, accounts.bob), 100);
+
+            // Repaying frees room under the cap.
+            assert_eq!(pool.repay_on_behalf(dot, accounts.alice, 30), Ok(()));
+            assert_eq!(pool.delegated_borrows_of(dot, accounts.bob), 70);
+            assert_eq!(pool.borrow_on_behalf(dot, accounts.alice, 30), Ok(()));
+            ink_env::test::pop_execution_context();
+
+            set_caller(accounts.charlie);
+            assert_eq!(pool.borrow_on_behalf(dot, accounts.alice, 400), Ok(()));
+        }
+
+        #[ink::test]
+        fn own_collateral_borrows_are_not_capped() {
+            let mut pool = setup();
+            let accounts = default_accounts();
+            assert_eq!(pool.set_kyc_registry(Some(KYC_REGISTRY.into())), Ok(()));
+
+            set_caller(accounts.bob);
+            assert_eq!(pool.deposit(DOT.into(), 100), Ok(()));
+            assert_eq!(pool.borrow(DOT.into(), 75), Ok(()));
+        }
+
+        #[ink::test]
+        fn only_owner_changes_liquidation_settings() {
+            let mut pool = setup();
+            let accounts = default_accounts();
+
+            assert_eq!(pool.set_close_factor(0), Err(Error::InvalidParameter));
+            assert_eq!(pool.set_close_factor(101), Err(Error::InvalidParameter));
+            assert_eq!(pool.set_close_factor(100), Ok(()));
+            assert_eq!(pool.close_factor(), 100);
+
+            set_caller(accounts.bob);
+            assert_eq!(pool.set_close_factor(50), Err(Error::CallerNotOwner));
+        }
+
+        #[ink::test]
+        fn healthy_position_cannot_be_liquidated() {
+            let mut pool = setup_usdt_loan();
+            let accounts = default_accounts();
+            // 1000 DOT value at an 80% threshold over 750 USDT of debt.
+            assert_eq!(pool.health_factor(accounts.bob), Ok(RAY * 800 / 750));
+            assert_eq!(pool.health_factor(accounts.charlie), Ok(Balance::MAX));
+
+            set_caller(accounts.charlie);
+            assert_eq!(
+                pool.liquidation_call(DOT.into(), USDT.into(), accounts.bob, 100, true),
+                Err(Error::HealthFactorNotBelowThreshold)
+            );
+        }
+
+        #[ink::test]
+        fn liquidation_call_works_after_price_drop() {
+            let mut pool = setup_usdt_loan();
+            let accounts = default_accounts();
+            let (dot, usdt) = (AccountId::from(DOT), AccountId::from(USDT));
+
+            // 900 DOT value at an 80% threshold no longer covers 750 USDT.
+            ext::set_price(ORACLE.into(), dot, 9);
+            assert!(pool.health_factor(accounts.bob).unwrap() < RAY);
+
+            set_caller(accounts.charlie);
+            assert_eq!(
+                pool.liquidation_call(usdt, usdt, accounts.bob, 100, true),
+                Err(Error::NoCollateralToSeize)
+            );
+            assert_eq!(
+                pool.liquidation_call(dot, dot, accounts.bob, 100, true),
+                Err(Error::NoDebtToCover)
+            );
+            // 300 USDT plus a 5% bonus buy 35 DOT.
+            assert_eq!(
+                pool.liquidation_call(dot, usdt, accounts.bob, 300, true),
+                Ok(())
+            );
+            assert_eq!(pool.debt_of(usdt, accounts.bob), 450);
+            assert_eq!(pool.deposit_of(dot, accounts.bob), 65);
+            assert_eq!(pool.total_deposits(dot), 65);
+            assert_eq!(pool.total_borrows(usdt), 450);
+            assert_eq!(ext::balance_of(usdt, accounts.charlie), 700);
+            assert_eq!(ext::balance_of(dot, accounts.charlie), 35);
+
+            // The position is healthy again.
+            assert!(pool.health_factor(accounts.bob).unwrap() >= RAY);
+            assert_eq!(
+                pool.liquidation_call(dot, usdt, accounts.bob, 100, true),
+                Err(Error::HealthFactorNotBelowThreshold)
+            );
+
+            let emitted_events = ink_env::test::recorded_events().collect::<Vec<_>>();
+            let decoded_event = <Event as scale::Decode>::decode(
+                &mut &emitted_events.last().unwrap().data[..],
+            )
+            .expect("encountered invalid contract event data buffer");
+            if let Event::LiquidationCall(event) = decoded_event {
+                assert_eq!(event.user, accounts.bob);
+                assert_eq!(event.debt_to_cover, 300);
+                assert_eq!(event.liquidated_collateral, 35);
+                assert_eq!(event.liquidator, accounts.charlie);
+            } else {
+                panic!(
+                    "encountered unexpected event kind: expected a LiquidationCall event"
+                )
+            }
+        }
+
+        #[ink::test]
+        fn liquidation_is_limited_by_close_factor() {
+            let mut pool = setup_usdt_loan();
+            let accounts = default_accounts();
+            let (dot, usdt) = (AccountId::from(DOT), AccountId::from(USDT));
+            ext::set_price(ORACLE.into(), dot, 9);
+
+            // Only half of the 750 USDT debt is covered. The seized DOT stay in
+            // the pool as Charlie's deposit.
+            set_caller(accounts.charlie);
+            assert_eq!(
+                pool.liquidation_call(dot, usdt, accounts.bob, 1000, false),
+                Ok(())
+            );
+            assert_eq!(pool.debt_of(usdt, accounts.bob), 375);
+            assert_eq!(ext::balance_of(usdt, accounts.charlie), 625);
+            assert_eq!(pool.deposit_of(dot, accounts.bob), 57);
+            assert_eq!(pool.deposit_of(dot, accounts.charlie), 43);
+            assert_eq!(pool.total_deposits(dot), 100);
+            assert_eq!(ext::balance_of(dot, accounts.charlie), 0);
+        }
+
+        #[ink::test]
+        fn liquidation_is_limited_by_collateral() {
+            let mut pool = setup_usdt_loan();
+            let accounts = default_accounts();
+            let (dot, usdt) = (AccountId::from(DOT), AccountId::from(USDT));
+            ext::set_price(ORACLE.into(), dot, 1);
+
+            // All 100 DOT are seized for the debt they are worth without the bonus.
+            set_caller(accounts.charlie);
+            assert_eq!(
+                pool.liquidation_call(dot, usdt, accounts.bob, 375, true),
+                Ok(())
+            );
+            assert_eq!(pool.deposit_of(dot, accounts.bob), 0);
+            assert_eq!(pool.debt_of(usdt, accounts.bob), 655);
+            assert_eq!(ext::balance_of(dot, accounts.charlie), 100);
+            assert_eq!(ext::balance_of(usdt, accounts.charlie), 905);
+        }
+
+        #[ink::test]
+        fn refused_liquidation_payout_returns_covered_debt() {
+            let mut pool = setup_usdt_loan();
+            let accounts = default_accounts();
+            let (dot, usdt) = (AccountId::from(DOT), AccountId::from(USDT));
+            ext::set_price(ORACLE.into(), dot, 9);
+
+            // The pool's DOT are gone, so the seized collateral cannot be sent.
+            ext::set_balance(dot, contract_id(), 0);
+            set_caller(accounts.charlie);
+            assert_eq!(
+                pool.liquidation_call(dot, usdt, accounts.bob, 300, true),
+                Err(Error::TransferFailed)
+            );
+            assert_eq!(ext::balance_of(usdt, accounts.charlie), 1000);
+            assert_eq!(pool.debt_of(usdt, accounts.bob), 750);
+            assert_eq!(pool.deposit_of(dot, accounts.bob), 100);
+        }
+
+        #[ink::test]
+        fn liquidation_covers_variable_debt_first() {
+            let mut pool = setup();
+            let accounts = default_accounts();
+            let (dot, usdt) = (AccountId::from(DOT), AccountId::from(USDT));
+            assert_eq!(pool.set_stable_rate_enabled(usdt, true), Ok(()));
+            assert_eq!(pool.deposit(usdt, 1000), Ok(()));
+            ext::set_balance(usdt, accounts.charlie, 1000);
+
+            set_caller(accounts.bob);
+            assert_eq!(pool.deposit(dot, 100), Ok(()));
+            assert_eq!(pool.borrow(usdt, 300), Ok(()));
+            assert_eq!(pool.borrow_stable(usdt, 450), Ok(()));
+            ext::set_price(ORACLE.into(), dot, 9);
+
+            // Half of the 750 USDT debt clears all 300 variable and 75 stable.
+            set_caller(accounts.charlie);
+            assert_eq!(
+                pool.liquidation_call(dot, usdt, accounts.bob, 1000, true),
+                Ok(())
+            );
+            assert_eq!(pool.debt_of(usdt, accounts.bob), 0);
+            assert_eq!(pool.stable_debt_of(usdt, accounts.bob), 375);
+            assert_eq!(pool.total_borrows(usdt), 375);
+            assert_eq!(ext::balance_of(usdt, accounts.charlie), 625);
+        }
+
+        /// Creates a pool with a DOT reserve of 10 decimals priced at 10 and a
+        /// USDT reserve of 6 decimals priced at 1.
+        fn setup_with_decimals() -> LendingPool {
+            let (dot, usdt) = (AccountId::from(DOT), AccountId::from(USDT));
+            ext::set_token_decimals(dot, 10);
+            ext::set_token_decimals(usdt, 6);
+            ext::set_price(ORACLE.into(), dot, 10);
+            ext::set_price(ORACLE.into(), usdt, 1);
+            let mut pool = LendingPool::new(ORACLE.into());
+            assert_eq!(init_reserve(&mut pool, DOT), Ok(()));
+            assert_eq!(init_reserve(&mut pool, USDT), Ok(()));
+            pool
+        }
+
+        #[ink::test]
+        fn values_are_normalized_by_decimals() {
+            const DOT_UNIT: Balance = 10_000_000_000;
+            const USDT_UNIT: Balance = 1_000_000;
+            let mut pool = setup_with_decimals();
+            let accounts = default_accounts();
+            let (dot, usdt) = (AccountId::from(DOT), AccountId::from(USDT));
+            assert_eq!(pool.reserve_decimals(dot), 10);
+            assert_eq!(pool.reserve_decimals(usdt), 6);
+
+            ext::set_balance(usdt, accounts.alice, 1000 * USDT_UNIT);
+            ext::set_balance(dot, accounts.bob, 100 * DOT_UNIT);
+            assert_eq!(pool.deposit(usdt, 1000 * USDT_UNIT), Ok(()));
+
+            // 100 DOT are worth 1000, which covers a debt of 750 USDT.
+            set_caller(accounts.bob);
+            assert_eq!(pool.deposit(dot, 100 * DOT_UNIT), Ok(()));
+            assert_eq!(
+                pool.borrow(usdt, 751 * USDT_UNIT),
+                Err(Error::InsufficientCollateral)
+            );
+            assert_eq!(pool.borrow(usdt, 750 * USDT_UNIT), Ok(()));
+            assert_eq!(pool.health_factor(accounts.bob), Ok(RAY * 800 / 750));
+            ink_env::test::pop_execution_context();
+
+            // At a price of 9 a liquidator covering 375 USDT seizes 43.75 DOT.
+            ext::set_price(ORACLE.into(), dot, 9);
+            ext::set_balance(usdt, accounts.charlie, 375 * USDT_UNIT);
+            set_caller(accounts.charlie);
+            assert_eq!(
+                pool.liquidation_call(dot, usdt, accounts.bob, 375 * USDT_UNIT, false),
+                Ok(())
+            );
+            assert_eq!(pool.debt_of(usdt, accounts.bob), 375 * USDT_UNIT);
+            assert_eq!(
+                pool.deposit_of(dot, accounts.charlie),
+                4375 * DOT_UNIT / 100
+            );
+            assert_eq!(
+                pool.deposit_of(dot, accounts.bob),
+                100 * DOT_UNIT - 4375 * DOT_UNIT / 100
+            );
+        }
+
+        #[ink::test]
+        fn get_user_account_data_works() {
+            let mut pool = setup();
+            let accounts = default_accounts();
+            assert_eq!(
+                pool.get_user_account_data(accounts.bob),
+                Ok(UserAccountData {
+                    total_collateral: 0,
+                    total_debt: 0,
+                    available_borrows: 0,
+                    current_liquidation_threshold: 0,
+                    ltv: 0,
+                    health_factor: Balance::MAX,
+                })
+            );
+            assert_eq!(pool.deposit(USDT.into(), 1000), Ok(()));
+
+            // 100 DOT are worth 1000 USDT, of which 400 are borrowed.
+            set_caller(accounts.bob);
+            assert_eq!(pool.deposit(DOT.into(), 100), Ok(()));
+            assert_eq!(pool.borrow(USDT.into(), 400), Ok(()));
+            assert_eq!(
+                pool.get_user_account_data(accounts.bob),
+                Ok(UserAccountData {
+                    total_collateral: 1000,
+                    total_debt: 400,
+                    available_borrows: 350,
+                    current_liquidation_threshold: 80,
+                    ltv: 75,
+                    health_factor: RAY * 2,
+                })
+            );
+            assert_eq!(
+                pool.health_factor(accounts.bob),
+                pool.get_user_account_data(accounts.bob)
+                    .map(|data| data.health_factor)
+            );
+        }
+
+        #[ink::test]
+        fn get_user_account_data_includes_stable_debt() {
+            let mut pool = setup();
+            let accounts = default_accounts();
+            assert_eq!(pool.deposit(USDT.into(), 1000), Ok(()));
+
+            set_caller(accounts.bob);
+            assert_eq!(pool.deposit(DOT.into(), 100), Ok(()));
+            assert_eq!(pool.borrow(USDT.into(), 300), Ok(()));
+            ext::set_stable_debt(token_of(STABLE_DEBT_TOKEN, USDT), accounts.bob, 100);
+            assert_eq!(pool.stable_debt_of(USDT.into(), accounts.bob), 100);
+            let data = pool
+                .get_user_account_data(accounts.bob)
+                .expect("reserves must be priced");
+            assert_eq!(data.total_debt, 400);
+            assert_eq!(data.available_borrows, 350);
+            assert_eq!(
+                pool.borrow(USDT.into(), 351),
+                Err(Error::InsufficientCollateral)
+            );
+        }
+
+        #[ink::test]
+        fn get_user_account_data_normalizes_decimals() {
+            const DOT_UNIT: Balance = 10_000_000_000;
+            const USDT_UNIT: Balance = 1_000_000;
+            let mut pool = setup_with_decimals();
+            let accounts = default_accounts();
+            let (dot, usdt) = (AccountId::from(DOT), AccountId::from(USDT));
+            ext::set_balance(usdt, accounts.alice, 1000 * USDT_UNIT);
+            ext::set_balance(dot, accounts.alice, 50 * DOT_UNIT);
+            assert_eq!(pool.deposit(usdt, 1000 * USDT_UNIT), Ok(()));
+            assert_eq!(pool.deposit(dot, 50 * DOT_UNIT), Ok(()));
+            assert_eq!(pool.borrow(usdt, 300 * USDT_UNIT), Ok(()));
+
+            let data = pool
+                .get_user_account_data(accounts.alice)
+                .expect("reserves must be priced");
+            assert_eq!(data.total_collateral, 1500);
+            assert_eq!(data.total_debt, 300);
+            assert_eq!(data.available_borrows, 825);
+            assert_eq!(data.health_factor, RAY * 4);
+        }
+
+        #[ink::test]
+        fn get_user_account_data_requires_prices() {
+            let mut pool = setup();
+            let accounts = default_accounts();
+            assert_eq!(pool.deposit(DOT.into(), 100), Ok(()));
+            ext::remove_price(ORACLE.into(), DOT.into());
+            assert_eq!(
+                pool.get_user_account_data(accounts.alice),
+                Err(Error::PriceUnavailable)
+            );
+        }
+
+        #[ink::test]
+        fn add_reserve_rejects_too_many_decimals() {
+            let mut pool = LendingPool::new(ORACLE.into());
+            ext::set_token_decimals(DOT.into(), 39);
+            assert_eq!(init_reserve(&mut pool, DOT), Err(Error::InvalidParameter));
+            assert_eq!(pool.reserves(), Vec::<AccountId>::new());
+        }
+
+        #[ink::test]
+        fn pause_stops_the_pool() {
+            let mut pool = setup_usdt_loan();
+            let accounts = default_accounts();
+            let (dot, usdt) = (AccountId::from(DOT), AccountId::from(USDT));
+            assert_eq!(pool.guardian(), accounts.alice);
+
+            assert_eq!(pool.pause(), Ok(()));
+            assert!(pool.paused());
+            assert_eq!(pool.pause(), Err(Error::Paused));
+            assert_eq!(pool.deposit(dot, 10), Err(Error::Paused));
+            assert_eq!(pool.withdraw(usdt, 10), Err(Error::Paused));
+            assert_eq!(pool.borrow(dot, 10), Err(Error::Paused));
+            set_caller(accounts.bob);
+            assert_eq!(pool.repay(usdt, 10), Err(Error::Paused));
+            ink_env::test::pop_execution_context();
+            ext::set_price(ORACLE.into(), dot, 9);
+            set_caller(accounts.charlie);
+            assert_eq!(
+                pool.liquidation_call(dot, usdt, accounts.bob, 100, true),
+                Err(Error::Paused)
+            );
+            ink_env::test::pop_execution_context();
+
+            assert_eq!(pool.unpause(), Ok(()));
+            assert!(!pool.paused());
+            assert_eq!(pool.unpause(), Err(Error::NotPaused));
+            set_caller(accounts.charlie);
+            assert_eq!(
+                pool.liquidation_call(dot, usdt, accounts.bob, 100, true),
+                Ok(())
+            );
+        }
+
+        #[ink::test]
+        fn freeze_stops_a_single_reserve() {
+            let mut pool = setup_usdt_loan();
+            let accounts = default_accounts();
+            let (dot, usdt) = (AccountId::from(DOT), AccountId::from(USDT));
+
+            assert_eq!(pool.freeze_reserve(usdt), Ok(()));
+            assert!(pool.is_frozen(usdt));
+            assert_eq!(pool.freeze_reserve(usdt), Err(Error::ReserveFrozen));
+            assert_eq!(pool.deposit(usdt, 10), Err(Error::ReserveFrozen));
+            assert_eq!(pool.delegate_credit(usdt, accounts.charlie, 10), Ok(()));
+            set_caller(accounts.charlie);
+            assert_eq!(
+                pool.borrow_on_behalf(usdt, accounts.alice, 10),
+                Err(Error::ReserveFrozen)
+            );
+            ink_env::test::pop_execution_context();
+
+            // Bob can still repay USDT and use the DOT reserve.
+            set_caller(accounts.bob);
+            assert_eq!(pool.borrow(usdt, 1), Err(Error::ReserveFrozen));
+            assert_eq!(pool.repay(usdt, 50), Ok(()));
+            assert_eq!(pool.borrow(dot, 1), Ok(()));
+            ink_env::test::pop_execution_context();
+            assert_eq!(pool.withdraw(usdt, 10), Ok(()));
+
+            assert_eq!(pool.unfreeze_reserve(usdt), Ok(()));
+            assert_eq!(pool.unfreeze_reserve(usdt), Err(Error::ReserveNotFrozen));
+            assert_eq!(pool.deposit(usdt, 10), Ok(()));
+        }
+
+        #[ink::test]
+        fn transfer_ownership_works() {
+            let mut pool = setup();
+            let accounts = default_accounts();
+
+            set_caller(accounts.bob);
+            assert_eq!(
+                pool.transfer_ownership(accounts.bob),
+                Err(Error::CallerNotOwner)
+            );
+            ink_env::test::pop_execution_context();
+
+            assert_eq!(pool.transfer_ownership(accounts.bob), Ok(()));
+            assert_eq!(pool.owner(), accounts.bob);
+            assert_eq!(pool.set_close_factor(40), Err(Error::CallerNotOwner));
+            set_caller(accounts.bob);
+            assert_eq!(pool.set_close_factor(40), Ok(()));
+        }
+
+        #[ink::test]
+        fn only_the_guardian_pauses() {
+            let mut pool = setup();
+            let accounts = default_accounts();
+
+            set_caller(accounts.bob);
+            assert_eq!(pool.pause(), Err(Error::CallerNotGuardian));
+            assert_eq!(
+                pool.freeze_reserve(DOT.into()),
+                Err(Error::CallerNotGuardian)
+            );
+            assert_eq!(pool.set_guardian(accounts.bob), Err(Error::CallerNotOwner));
+            ink_env::test::pop_execution_context();
+
+            assert_eq!(pool.set_guardian(accounts.bob), Ok(()));
+            assert_eq!(pool.pause(), Err(Error::CallerNotGuardian));
+            set_caller(accounts.bob);
+            assert_eq!(pool.pause(), Ok(()));
+            assert_eq!(
+                pool.freeze_reserve([0xEE; 32].into()),
+                Err(Error::ReserveNotFound)
+            );
+        }
+
+        #[ink::test]
+        fn delegators_are_liquidated_for_delegated_debt() {
+            let mut pool = setup();
+            let accounts = default_accounts();
+            let (dot, usdt) = (AccountId::from(DOT), AccountId::from(USDT));
+            assert_eq!(pool.deposit(usdt, 1000), Ok(()));
+
+            // Bob backs Charlie's loan with a DOT deposit.
+            set_caller(accounts.bob);
+            assert_eq!(pool.deposit(dot, 100), Ok(()));
+            assert_eq!(pool.delegate_credit(usdt, accounts.charlie, 750), Ok(()));
+            ink_env::test::pop_execution_context();
+            set_caller(accounts.charlie);
+            assert_eq!(pool.borrow_on_behalf(usdt, accounts.bob, 750), Ok(()));
+            ink_env::test::pop_execution_context();
+
+            ext::set_price(ORACLE.into(), dot, 9);
+            ext::set_balance(usdt, accounts.django, 300);
+            set_caller(accounts.django);
+            assert_eq!(
+                pool.liquidation_call(dot, usdt, accounts.bob, 300, true),
+                Ok(())
+            );
+            assert_eq!(pool.debt_of(usdt, accounts.bob), 450);
+            assert_eq!(pool.deposit_of(dot, accounts.bob), 65);
+            // Charlie still owes the full amount to Bob.
+            assert_eq!(
+                pool.delegated_debt(usdt, accounts.bob, accounts.charlie),
+                750
+            );
+        }
+
+        #[ink::test]
+        fn propose_and_accept_loan_works() {
+            let mut pool = setup();
+            let accounts = default_accounts();
+            let dot = AccountId::from(DOT);
+            propose_loan(&mut pool, 4, RepaymentSchedule::EqualInstallment);
+
+            assert_eq!(pool.loans_of(accounts.bob), vec![0]);
+            let loan = pool.get_loan(0).unwrap();
+            assert_eq!(loan.delegator, accounts.alice);
+            assert_eq!(loan.interest, 40);
+            assert_eq!(loan.status, LoanStatus::Proposed);
+            // Nothing is borrowed before the borrower accepts.
+            assert_eq!(pool.debt_of(dot, accounts.alice), 0);
+
+            set_caller(accounts.charlie);
+            assert_eq!(pool.accept_loan(0), Err(Error::CallerNotBorrower));
+            assert_eq!(pool.accept_loan(1), Err(Error::LoanNotFound));
+            ink_env::test::pop_execution_context();
+
+            set_caller(accounts.bob);
+            assert_eq!(pool.accept_loan(0), Ok(()));
+            assert_eq!(pool.accept_loan(0), Err(Error::InvalidLoanStatus));
+            let loan = pool.get_loan(0).unwrap();
+            assert_eq!(loan.start, now());
+            assert_eq!(loan.status, LoanStatus::Active);
+            assert_eq!(ext::balance_of(dot, accounts.bob), 500);
+            assert_eq!(pool.debt_of(dot, accounts.alice), 400);
+            assert_eq!(pool.delegated_debt(dot, accounts.alice, accounts.bob), 400);
+        }
+
+        #[ink::test]
+        fn invalid_loan_terms_fail() {
+            let mut pool = setup();
+            let accounts = default_accounts();
+            let schedule = RepaymentSchedule::Bullet;
+
+            assert_eq!(
+                pool.propose_loan(DOT.into(), accounts.bob, 0, 0, 1000, 1, schedule, 0),
+                Err(Error::InvalidLoanTerms)
+            );
+            assert_eq!(
+                pool.propose_loan(DOT.into(), accounts.bob, 100, 0, 0, 1, schedule, 0),
+                Err(Error::InvalidLoanTerms)
+            );
+            assert_eq!(
+                pool.propose_loan(DOT.into(), accounts.bob, 100, 0, 1000, 0, schedule, 0),
+                Err(Error::InvalidLoanTerms)
+            );
+            assert_eq!(
+                pool.propose_loan(
+                    DOT.into(),
+                    accounts.bob,
+                    100,
+                    0,
+                    1000,
+                    121,
+                    schedule,
+                    0
+                ),
+                Err(Error::InvalidLoanTerms)
+            );
+            assert_eq!(
+                pool.propose_loan(
+                    [0xEE; 32].into(),
+                    accounts.bob,
+                    100,
+                    0,
+                    1000,
+                    1,
+                    schedule,
+                    0
+                ),
+                Err(Error::ReserveNotFound)
+            );
+            assert_eq!(pool.get_loan(0), None);
+            assert_eq!(pool.loans_of(accounts.bob), Vec::<LoanId>::new());
+        }
+
+        #[ink::test]
+        fn cancel_loan_works() {
+            let mut pool = setup();
+            let accounts = default_accounts();
+            propose_loan(&mut pool, 1, RepaymentSchedule::Bullet);
+
+            set_caller(accounts.bob);
+            assert_eq!(pool.cancel_loan(0), Err(Error::CallerNotDelegator));
+            ink_env::test::pop_execution_context();
+
+            assert_eq!(pool.cancel_loan(0), Ok(()));
+            assert_eq!(pool.get_loan(0).unwrap().status, LoanStatus::Cancelled);
+            assert_eq!(pool.cancel_loan(0), Err(Error::InvalidLoanStatus));
+
+            set_caller(accounts.bob);
+            assert_eq!(pool.accept_loan(0), Err(Error::InvalidLoanStatus));
+            assert_eq!(pool.repay_loan(0, 100), Err(Error::InvalidLoanStatus));
+        }
+
+        #[ink::test]
+        fn repayment_schedules_work() {
+            let accounts = default_accounts();
+            let mut loan = LoanAgreement {
+                reserve: DOT.into(),
+                delegator: accounts.alice,
+                borrower: accounts.bob,
+                principal: 1000,
+                apr: 0,
+                interest: 100,
+                term: 4000,
+                installments: 4,
+                schedule: RepaymentSchedule::EqualInstallment,
+                grace_period: 0,
+                start: 1000,
+                repaid: 0,
+                status: LoanStatus::Active,
+            };
+            assert_eq!(loan.due_date(1), 2000);
+            assert_eq!(loan.due_date(4), 5000);
+            assert_eq!(loan.due_by(1), 275);
+            assert_eq!(loan.due_by(4), 1100);
+
+            loan.schedule = RepaymentSchedule::InterestOnly;
+            assert_eq!(loan.due_by(1), 25);
+            assert_eq!(loan.due_by(3), 75);
+            assert_eq!(loan.due_by(4), 1100);
+
+            loan.schedule = RepaymentSchedule::Bullet;
+            assert_eq!(loan.due_by(3), 0);
+            assert_eq!(loan.due_by(4), 1100);
+            assert_eq!(loan.status_at(5000), LoanStatus::Active);
+            assert_eq!(loan.status_at(5001), LoanStatus::Defaulted);
+
+            // Payments are split between principal and interest pro rata.
+            assert_eq!(loan.principal_part(550), 500);
+            assert_eq!(loan.principal_part(1100), 1000);
+        }
+
+        #[ink::test]
+        fn loans_become_late_and_defaulted() {
+            let mut pool = setup();
+            let accounts = default_accounts();
+            let dot = AccountId::from(DOT);
+            propose_loan(&mut pool, 4, RepaymentSchedule::EqualInstallment);
+            set_caller(accounts.bob);
+            assert_eq!(pool.accept_loan(0), Ok(()));
+
+            // The first installment of 110 DOT falls due after one block.
+            advance_blocks(1);
+            assert_eq!(pool.get_loan(0).unwrap().status, LoanStatus::Active);
+            advance_blocks(1);
+            assert_eq!(pool.get_loan(0).unwrap().status, LoanStatus::Late);
+
+            // 100 DOT repay Alice's debt and 10 DOT of interest go to Alice.
+            assert_eq!(pool.repay_loan(0, 110), Ok(()));
+            assert_eq!(pool.get_loan(0).unwrap().status, LoanStatus::Active);
+            assert_eq!(pool.debt_of(dot, accounts.alice), 300);
+            assert_eq!(pool.delegated_debt(dot, accounts.alice, accounts.bob), 300);
+            assert_eq!(ext::balance_of(dot, accounts.alice), 10);
+
+            // The second installment is missed for longer than the grace period.
+            advance_blocks(2);
+            assert_eq!(pool.get_loan(0).unwrap().status, LoanStatus::Defaulted);
+
+            assert_eq!(pool.repay_loan(0, 1000), Ok(()));
+            let loan = pool.get_loan(0).unwrap();
+            assert_eq!(loan.repaid, 440);
+            assert_eq!(loan.status, LoanStatus::Repaid);
+            assert_eq!(pool.debt_of(dot, accounts.alice), 0);
+            assert_eq!(ext::balance_of(dot, accounts.alice), 40);
+            assert_eq!(ext::balance_of(dot, accounts.bob), 60);
+            assert_eq!(pool.repay_loan(0, 1), Err(Error::InvalidLoanStatus));
+        }
+
+        #[ink::test]
+        fn defaulted_loans_can_be_liquidated() {
+            let mut pool = setup();
+            let accounts = default_accounts();
+            let dot = AccountId::from(DOT);
+            propose_loan(&mut pool, 1, RepaymentSchedule::Bullet);
+            ext::set_balance(dot, accounts.bob, 1000);
+            ext::set_balance(dot, accounts.charlie, 1000);
+
+            set_caller(accounts.bob);
+            assert_eq!(pool.deposit(dot, 300), Ok(()));
+            assert_eq!(pool.accept_loan(0), Ok(()));
+            ink_env::test::pop_execution_context();
+
+            // Bob owes nothing to the pool directly, so only a default counts.
+            set_caller(accounts.charlie);
+            assert_eq!(
+                pool.liquidation_call(dot, dot, accounts.bob, 1000, true),
+                Err(Error::HealthFactorNotBelowThreshold)
+            );
+            advance_blocks(6);
+            assert_eq!(pool.get_loan(0).unwrap().status, LoanStatus::Defaulted);
+
+            // Half of the 440 DOT owed is covered for 231 DOT of Bob's deposit.
+            assert_eq!(
+                pool.liquidation_call(dot, dot, accounts.bob, 1000, true),
+                Ok(())
+            );
+            assert_eq!(pool.get_loan(0).unwrap().repaid, 220);
+            assert_eq!(pool.deposit_of(dot, accounts.bob), 69);
+            assert_eq!(ext::balance_of(dot, accounts.charlie), 1011);
+            assert_eq!(pool.debt_of(dot, accounts.alice), 200);
+            assert_eq!(ext::balance_of(dot, accounts.alice), 20);
+        }
+
+        #[ink::test]
+        fn deposit_borrow_repay_withdraw_cycle_works() {
+            let mut pool = setup();
+            let accounts = default_accounts();
+            let dot = AccountId::from(DOT);
+
+            // Alice supplies the liquidity.
+            assert_eq!(pool.deposit(dot, 1000), Ok(()));
+
+            // Bob deposits collateral and borrows against it.
+            set_caller(accounts.bob);
+            assert_eq!(pool.deposit(dot, 100), Ok(()));
+            assert_eq!(pool.borrow(dot, 75), Ok(()));
+            assert_eq!(ext::balance_of(dot, accounts.bob), 75);
+            assert_eq!(pool.total_borrows(dot), 75);
+            assert_eq!(pool.available_liquidity(dot), 1025);
+
+            // Bob cannot withdraw the collateral while the debt is open.
+            assert_eq!(pool.withdraw(dot, 100), Err(Error::InsufficientCollateral));
+
+            // Bob repays and gets the collateral back.
+            assert_eq!(pool.repay(dot, 75), Ok(()));
+            assert_eq!(pool.total_borrows(dot), 0);
+            assert_eq!(pool.withdraw(dot, 100), Ok(()));
+            assert_eq!(ext::balance_of(dot, accounts.bob), 100);
+
+            // Alice takes the liquidity back.
+            ink_env::test::pop_execution_context();
+            assert_eq!(pool.withdraw(dot, 1000), Ok(()));
+            assert_eq!(ext::balance_of(dot, accounts.alice), 1000);
+            assert_eq!(ext::balance_of(dot, contract_id()), 0);
+            assert_eq!(pool.total_deposits(dot), 0);
+
+            // ReserveInitialized, ReserveInitialized, Deposit, Deposit, Borrow, Repay,
+            // Withdraw, Withdraw.
+            assert_eq!(ink_env::test::recorded_events().count(), 8);
+        }
+
+        /// Lets Alice supply a million USDT and Bob borrow a tenth of it against
+        /// DOT, at a variable rate of 50% and a liquidity rate of 5%.
+        fn setup_accruing_loan() -> (LendingPool, Balance) {
+            let mut pool = setup();
+            let accounts = default_accounts();
+            let supplied: Balance = 1_000_000 * RAY;
+            ext::set_balance(USDT.into(), accounts.alice, supplied);
+            ext::set_balance(DOT.into(), accounts.bob, supplied);
+            ext::set_interest_rates(RATE_STRATEGY.into(), (RAY / 20, RAY / 2, RAY));
+            assert_eq!(pool.deposit(USDT.into(), supplied), Ok(()));
+            set_caller(accounts.bob);
+            assert_eq!(pool.deposit(DOT.into(), supplied), Ok(()));
+            assert_eq!(pool.borrow(USDT.into(), supplied / 10), Ok(()));
+            ink_env::test::pop_execution_context();
+            (pool, supplied)
+        }
+
+        #[ink::test]
+        fn update_state_compounds_indices() {
+            let (mut pool, supplied) = setup_accruing_loan();
+            let accounts = default_accounts();
+            let usdt = AccountId::from(USDT);
+            let borrowed = supplied / 10;
+            let data = pool.reserve_data(usdt).expect("USDT must be a reserve");
+            assert_eq!(data.current_liquidity_rate, RAY / 20);
+            assert_eq!(data.current_variable_borrow_rate, RAY / 2);
+            assert_eq!(data.current_stable_borrow_rate, RAY);
+            let start = data.last_update_timestamp;
+
+            // Interest accrues between updates.
+            advance_blocks(10);
+            let income = calculate_linear_interest(RAY / 20, now() - start);
+            let debt_index = calculate_compounded_interest(RAY / 2, now() - start);
+            assert!(income > RAY && debt_index > income);
+            assert_eq!(pool.reserve_normalized_income(usdt), income);
+            assert_eq!(pool.reserve_normalized_variable_debt(usdt), debt_index);
+            assert_eq!(
+                pool.deposit_of(usdt, accounts.alice),
+                ray_mul(supplied, income)
+            );
+            assert_eq!(
+                pool.debt_of(usdt, accounts.bob),
+                ray_mul(borrowed, debt_index)
+            );
+            assert_eq!(pool.total_borrows(usdt), ray_mul(borrowed, debt_index));
+
+            // Repaying the principal stores the indices and leaves the interest owed.
+            set_caller(accounts.bob);
+            assert_eq!(pool.repay(usdt, borrowed), Ok(()));
+            let data = pool.reserve_data(usdt).expect("USDT must be a reserve");
+            assert_eq!(data.liquidity_index, income);
+            assert_eq!(data.variable_borrow_index, debt_index);
+            assert_eq!(data.last_update_timestamp, now());
+            let interest = ray_mul(borrowed, debt_index) - borrowed;
+            let debt = pool.debt_of(usdt, accounts.bob);
+            assert!(debt + 1 >= interest && debt <= interest + 1);
+
+            // The next period compounds on top of the stored indices.
+            let start = now();
+            advance_blocks(10);
+            let elapsed = now() - start;
+            assert_eq!(
+                pool.reserve_normalized_income(usdt),
+                ray_mul(calculate_linear_interest(RAY / 20, elapsed), income)
+            );
+            assert_eq!(
+                pool.reserve_normalized_variable_debt(usdt),
+                ray_mul(calculate_compounded_interest(RAY / 2, elapsed), debt_index)
+            );
+            assert!(pool.debt_of(usdt, accounts.bob) > debt);
+        }
+
+        #[ink::test]
+        fn withdraw_pays_out_accrued_interest() {
+            let (mut pool, supplied) = setup_accruing_loan();
+            let accounts = default_accounts();
+            let usdt = AccountId::from(USDT);
+            let borrowed = supplied / 10;
+            advance_blocks(10);
+
+            // Bob repays everything he owes, leaving no dust behind.
+            set_caller(accounts.bob);
+            ext::set_balance(usdt, accounts.bob, supplied);
+            let debt = pool.debt_of(usdt, accounts.bob);
+            assert!(debt > borrowed);
+            assert_eq!(pool.repay(usdt, Balance::MAX), Ok(()));
+            assert_eq!(ext::balance_of(usdt, accounts.bob), supplied - debt);
+            assert_eq!(pool.debt_of(usdt, accounts.bob), 0);
+            assert_eq!(pool.total_borrows(usdt), 0);
+            ink_env::test::pop_execution_context();
+
+            // Alice withdraws her deposit with the interest it earned.
+            let deposit = pool.deposit_of(usdt, accounts.alice);
+            assert!(deposit > supplied);
+            assert_eq!(pool.withdraw(usdt, deposit), Ok(()));
+            assert_eq!(ext::balance_of(usdt, accounts.alice), deposit);
+            assert_eq!(pool.deposit_of(usdt, accounts.alice), 0);
+            assert_eq!(pool.total_deposits(usdt), 0);
+            assert!(ext::balance_of(usdt, contract_id()) > 0);
+        }
+
+        #[ink::test]
+        fn reserve_factor_accrues_to_treasury() {
+            let (mut pool, _) = setup_accruing_loan();
+            let accounts = default_accounts();
+            let usdt = AccountId::from(USDT);
+            let treasury = accounts.django;
+            set_caller(accounts.bob);
+            assert_eq!(
+                pool.set_treasury(Some(treasury)),
+                Err(Error::CallerNotOwner)
+            );
+            ink_env::test::pop_execution_context();
+            assert_eq!(pool.set_treasury(Some(treasury)), Ok(()));
+            assert_eq!(pool.treasury(), Some(treasury));
+            assert_eq!(pool.set_reserve_factor(usdt, 20), Ok(()));
+
+            // Depositors earn the liquidity rate less the reserve factor.
+            let data = pool.reserve_data(usdt).expect("USDT must be a reserve");
+            assert_eq!(data.current_liquidity_rate, RAY / 20 * 80 / 100);
+            assert_eq!(data.current_variable_borrow_rate, RAY / 2);
+
+            // The next update deposits a fifth of the accrued interest for the
+            // treasury.
+            let debt = pool.total_borrows(usdt);
+            advance_blocks(10);
+            let share = (pool.total_borrows(usdt) - debt) * 20 / 100;
+            assert!(share > 0);
+            set_caller(accounts.bob);
+            let events_before = ink_env::test::recorded_events().count();
+            assert_eq!(pool.repay(usdt, 1), Ok(()));
+            let deposit = pool.deposit_of(usdt, treasury);
+            assert!(deposit + 1 >= share && deposit <= share + 1);
+            assert!(ext::scaled_deposit_of(token_of(A_TOKEN, USDT), treasury) > 0);
+            assert_eq!(
+                treasury_accruals(events_before),
+                vec![(usdt, treasury, share)]
+            );
+
+            // Nothing accrues within the same block or without debt.
+            let events_before = ink_env::test::recorded_events().count();
+            assert_eq!(pool.repay(usdt, 1), Ok(()));
+            ink_env::test::pop_execution_context();
+            assert_eq!(pool.deposit(DOT.into(), 1), Ok(()));
+            assert!(treasury_accruals(events_before).is_empty());
+        }
+
+        #[ink::test]
+        fn reserve_factor_accrues_on_stable_debt() {
+            let (mut pool, supplied) = setup_accruing_loan();
+            let accounts = default_accounts();
+            let usdt = AccountId::from(USDT);
+            let treasury = accounts.django;
+            assert_eq!(pool.set_treasury(Some(treasury)), Ok(()));
+            assert_eq!(pool.set_reserve_factor(usdt, 20), Ok(()));
+            assert_eq!(pool.set_stable_rate_enabled(usdt, true), Ok(()));
+
+            // Charlie borrows at the stable rate of 100% the strategy quotes.
+            let stable_debt = supplied / 100;
+            ext::set_balance(DOT.into(), accounts.charlie, supplied);
+            set_caller(accounts.charlie);
+            assert_eq!(pool.deposit(DOT.into(), supplied), Ok(()));
+            assert_eq!(pool.borrow_stable(usdt, stable_debt), Ok(()));
+            assert_eq!(
+                ext::average_stable_rate(token_of(STABLE_DEBT_TOKEN, USDT)),
+                RAY
+            );
+            ink_env::test::pop_execution_context();
+
+            // The share covers the interest on both the variable and the stable
+            // debt.
+            let start = now();
+            let variable_debt = pool.total_borrows(usdt) - stable_debt;
+            advance_blocks(10);
+            let variable_accrued = pool.total_borrows(usdt) - stable_debt - variable_debt;
+            let stable_accrued = stable_debt
+                - ray_div(
+                    stable_debt,
+                    calculate_compounded_interest(RAY, now() - start),
+                );
+            assert!(stable_accrued > 0);
+            ext::set_balance(usdt, accounts.alice, 1);
+            let events_before = ink_env::test::recorded_events().count();
+            assert_eq!(pool.deposit(usdt, 1), Ok(()));
+            assert_eq!(
+                treasury_accruals(events_before),
+                vec![(
+                    usdt,
+                    treasury,
+                    (variable_accrued + stable_accrued) * 20 / 100
+                )]
+            );
+        }
+
+        /// Returns the `(reserve, treasury, value)` of every `TreasuryAccrued`
+        /// event emitted after the first `skip` events.
+        fn treasury_accruals(skip: usize) -> Vec<(AccountId, AccountId, Balance)> {
+            ink_env::test::recorded_events()
+                .skip(skip)
+                .filter_map(|event| {
+                    match <Event as scale::Decode>::decode(&mut &event.data[..]) {
+                        Ok(Event::TreasuryAccrued(TreasuryAccrued {
+                            reserve,
+                            treasury,
+                            value,
+                        })) => Some((reserve, treasury, value)),
+                        _ => None,
+                    }
+                })
+                .collect()
+        }
+
+        /// Lets Alice supply a million DOT and funds Eve, the flash loan receiver,
+        /// with enough DOT to pay the premium.
+        fn setup_flash_loan() -> (LendingPool, AccountId) {
+            let mut pool = setup();
+            let accounts = default_accounts();
+            ext::set_balance(DOT.into(), accounts.alice, 1_000_000);
+            ext::set_balance(DOT.into(), accounts.eve, 1_000);
+            assert_eq!(pool.deposit(DOT.into(), 1_000_000), Ok(()));
+            (pool, accounts.eve)
+        }
+
+        #[ink::test]
+        fn flash_loan_works() {
+            let (mut pool, receiver) = setup_flash_loan();
+            let accounts = default_accounts();
+            let dot = AccountId::from(DOT);
+            assert_eq!(pool.flash_loan_premium(), 9);
+
+            assert_eq!(
+                pool.flash_loan(
+                    receiver,
+                    vec![dot],
+                    vec![1_000_000],
+                    vec![FlashLoanMode::NoDebt],
+                    vec![1, 2, 3],
+                ),
+                Ok(())
+            );
+            assert_eq!(
+                ext::flash_loans(),
+                vec![(
+                    receiver,
+                    vec![dot],
+                    vec![1_000_000],
+                    vec![900],
+                    accounts.alice,
+                    vec![1, 2, 3]
+                )]
+            );
+            assert_eq!(ext::balance_of(dot, receiver), 100);
+            assert_eq!(ext::balance_of(dot, contract_id()), 1_000_900);
+
+            // The premium goes to the depositors.
+            assert_eq!(pool.deposit_of(dot, accounts.alice), 1_000_900);
+            assert_eq!(pool.available_liquidity(dot), 1_000_900);
+
+            // ReserveInitialized, ReserveInitialized, Deposit, FlashLoan.
+            let emitted_events = ink_env::test::recorded_events().collect::<Vec<_>>();
+            assert_eq!(emitted_events.len(), 4);
+            let decoded =
+                <Event as scale::Decode>::decode(&mut &emitted_events[3].data[..])
+                    .expect("encountered invalid contract event data buffer");
+            match decoded {
+                Event::FlashLoan(FlashLoan {
+                    target,
+                    initiator,
+                    asset,
+                    amount,
+                    premium,
+                    mode,
+                }) => {
+                    assert_eq!(target, receiver);
+                    assert_eq!(initiator, accounts.alice);
+                    assert_eq!(asset, dot);
+                    assert_eq!(amount, 1_000_000);
+                    assert_eq!(premium, 900);
+                    assert_eq!(mode, FlashLoanMode::NoDebt);
+                }
+                _ => panic!("encountered unexpected event kind"),
+            }
+        }
+
+        #[ink::test]
+        fn flash_loan_can_open_debt() {
+            let mut pool = setup();
+            let accounts = default_accounts();
+            let usdt = AccountId::from(USDT);
+            assert_eq!(pool.deposit(usdt, 1000), Ok(()));
+            set_caller(accounts.bob);
+            assert_eq!(pool.deposit(DOT.into(), 100), Ok(()));
+
+            // Bob cannot be left with more debt than his collateral covers.
+            assert_eq!(
+                pool.flash_loan(
+                    accounts.eve,
+                    vec![usdt, usdt],
+                    vec![700, 51],
+                    vec![FlashLoanMode::VariableDebt, FlashLoanMode::VariableDebt],
+                    Vec::new(),
+                ),
+                Err(Error::InsufficientCollateral)
+            );
+            assert!(ext::flash_loans().is_empty());
+
+            assert_eq!(
+                pool.flash_loan(
+                    accounts.eve,
+                    vec![usdt],
+                    vec![750],
+                    vec![FlashLoanMode::VariableDebt],
+                    Vec::new(),
+                ),
+                Ok(())
+            );
+            assert_eq!(ext::flash_loans()[0].3, vec![0]);
+            assert_eq!(ext::balance_of(usdt, accounts.eve), 750);
+            assert_eq!(pool.debt_of(usdt, accounts.bob), 750);
+            assert_eq!(pool.total_borrows(usdt), 750);
+
+            // ReserveInitialized, ReserveInitialized, Deposit, Deposit, Borrow,
+            // FlashLoan.
+            let emitted_events = ink_env::test::recorded_events().collect::<Vec<_>>();
+            assert_eq!(emitted_events.len(), 6);
+            let decoded =
+                <Event as scale::Decode>::decode(&mut &emitted_events[4].data[..])
+                    .expect("encountered invalid contract event data buffer");
+            match decoded {
+                Event::Borrow(Borrow {
+                    reserve,
+                    user,
+                    value,
+                }) => {
+                    assert_eq!(reserve, usdt);
+                    assert_eq!(user, accounts.bob);
+                    assert_eq!(value, 750);
+                }
+                _ => panic!("encountered unexpected event kind"),
+            }
+        }
+
+        #[ink::test]
+        fn flash_loan_rejects_invalid_calls() {
+            let (mut pool, receiver) = setup_flash_loan();
+            let dot = AccountId::from(DOT);
+            let no_debt = FlashLoanMode::NoDebt;
+            assert_eq!(
+                pool.flash_loan(receiver, vec![], vec![], vec![], Vec::new()),
+                Err(Error::InconsistentFlashLoanParams)
+            );
+            assert_eq!(
+                pool.flash_loan(
+                    receiver,
+                    vec![dot],
+                    vec![1, 2],
+                    vec![no_debt],
+                    Vec::new()
+                ),
+                Err(Error::InconsistentFlashLoanParams)
+            );
+            assert_eq!(
+                pool.flash_loan(receiver, vec![dot], vec![1], vec![], Vec::new()),
+                Err(Error::InconsistentFlashLoanParams)
+            );
+            // Listing an asset twice must not split an amount above the liquidity.
+            assert_eq!(
+                pool.flash_loan(
+                    receiver,
+                    vec![dot, dot],
+                    vec![600_000, 600_000],
+                    vec![no_debt, no_debt],
+                    Vec::new()
+                ),
+                Err(Error::InconsistentFlashLoanParams)
+            );
+            assert_eq!(
+                pool.flash_loan(
+                    receiver,
+                    vec![[0xEE; 32].into()],
+                    vec![1],
+                    vec![no_debt],
+                    Vec::new()
+                ),
+                Err(Error::ReserveNotFound)
+            );
+            assert_eq!(
+                pool.flash_loan(
+                    receiver,
+                    vec![dot],
+                    vec![1_000_001],
+                    vec![no_debt],
+                    Vec::new()
+                ),
+                Err(Error::InsufficientLiquidity)
+            );
+            assert_eq!(pool.set_borrowing_enabled(dot, false), Ok(()));
+            assert_eq!(
+                pool.flash_loan(
+                    receiver,
+                    vec![dot],
+                    vec![1],
+                    vec![FlashLoanMode::VariableDebt],
+                    Vec::new()
+                ),
+                Err(Error::BorrowingNotEnabled)
+            );
+            assert_eq!(pool.pause(), Ok(()));
+            assert_eq!(
+                pool.flash_loan(receiver, vec![dot], vec![1], vec![no_debt], Vec::new()),
+                Err(Error::Paused)
+            );
+            assert!(ext::flash_loans().is_empty());
+        }
+
+        #[ink::test]
+        #[should_panic(expected = "flash loan receiver failed")]
+        fn failing_flash_loan_receiver_panics() {
+            let (mut pool, receiver) = setup_flash_loan();
+            ext::fail_flash_loans(receiver);
+            let _ = pool.flash_loan(
+                receiver,
+                vec![DOT.into()],
+                vec![1_000],
+                vec![FlashLoanMode::NoDebt],
+                Vec::new(),
+            );
+        }
+
+        #[ink::test]
+        #[should_panic(expected = "flash loan was not repaid")]
+        fn unpaid_flash_loan_panics() {
+            let (mut pool, receiver) = setup_flash_loan();
+            ext::set_balance(DOT.into(), receiver, 0);
+            let _ = pool.flash_loan(
+                receiver,
+                vec![DOT.into()],
+                vec![1_000_000],
+                vec![FlashLoanMode::NoDebt],
+                Vec::new(),
+            );
+        }
+
+        #[ink::test]
+        fn set_flash_loan_premium_works() {
+            let (mut pool, receiver) = setup_flash_loan();
+            let accounts = default_accounts();
+            assert_eq!(
+                pool.set_flash_loan_premium(10_001),
+                Err(Error::InvalidParameter)
+            );
+            assert_eq!(pool.set_flash_loan_premium(10), Ok(()));
+            assert_eq!(pool.flash_loan_premium(), 10);
+            set_caller(accounts.bob);
+            assert_eq!(pool.set_flash_loan_premium(0), Err(Error::CallerNotOwner));
+            ink_env::test::pop_execution_context();
+
+            assert_eq!(
+                pool.flash_loan(
+                    receiver,
+                    vec![DOT.into()],
+                    vec![1_000_000],
+                    vec![FlashLoanMode::NoDebt],
+                    Vec::new(),
+                ),
+                Ok(())
+            );
+            assert_eq!(ext::balance_of(DOT.into(), receiver), 0);
+        }
+    }
+}
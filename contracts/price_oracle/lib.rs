@@ -0,0 +1,466 @@
+// Copyright 2021 Bandot Developers.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+use ink_lang as ink;
+
+pub use self::price_oracle::PriceOracle;
+
+#[ink::contract]
+pub mod price_oracle {
+    use ink_prelude::vec::Vec;
+    #[cfg(not(feature = "ink-as-dependency"))]
+    use ink_storage::collections::{
+        HashMap as StorageHashMap,
+        Vec as StorageVec,
+    };
+    use ink_storage::traits::{
+        PackedLayout,
+        SpreadLayout,
+    };
+
+    /// A price submitted by a feeder.
+    #[derive(
+        Debug,
+        Copy,
+        Clone,
+        PartialEq,
+        Eq,
+        scale::Encode,
+        scale::Decode,
+        SpreadLayout,
+        PackedLayout,
+    )]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink_storage::traits::StorageLayout)
+    )]
+    pub struct PriceReport {
+        /// The price of one whole token of the asset in the base currency.
+        pub price: Balance,
+        /// The time the price was submitted.
+        pub timestamp: Timestamp,
+    }
+
+    /// An oracle aggregating asset prices submitted by whitelisted feeders.
+    ///
+    /// Assets are identified by the address of their `Erc20` contract.
+    #[ink(storage)]
+    pub struct PriceOracle {
+        /// The account allowed to manage feeders and settings.
+        admin: AccountId,
+        /// The accounts allowed to submit prices.
+        feeders: StorageVec<AccountId>,
+        /// Mapping of the latest price report of a feeder for an asset.
+        reports: StorageHashMap<(AccountId, AccountId), PriceReport>,
+        /// How old, in milliseconds, a report may be to count as fresh.
+        max_age: Timestamp,
+        /// How many fresh reports are needed to serve a price.
+        min_reporters: u32,
+    }
+
+    /// Event emitted when the admin whitelists a feeder.
+    #[ink(event)]
+    pub struct FeederAdded {
+        #[ink(topic)]
+        feeder: AccountId,
+    }
+
+    /// Event emitted when the admin removes a feeder.
+    #[ink(event)]
+    pub struct FeederRemoved {
+        #[ink(topic)]
+        feeder: AccountId,
+    }
+
+    /// Event emitted when a feeder submits the `price` of `asset`.
+    #[ink(event)]
+    pub struct PriceSubmitted {
+        #[ink(topic)]
+        asset: AccountId,
+        #[ink(topic)]
+        feeder: AccountId,
+        price: Balance,
+    }
+
+    /// The price oracle error types.
+    #[derive(Debug, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+    pub enum Error {
+        /// Returned if the caller is not the admin.
+        CallerNotAdmin,
+        /// Returned if the caller is not a whitelisted feeder.
+        CallerNotFeeder,
+        /// Returned if a submitted price is zero.
+        InvalidPrice,
+        /// Returned if enough feeders reported the asset but too many reports are
+        /// older than `max_age`.
+        StalePrice,
+        /// Returned if fewer than `min_reporters` feeders reported the asset.
+        NotEnoughReporters,
+    }
+
+    /// The price oracle result type.
+    pub type Result<T> = core::result::Result<T, Error>;
+
+    impl PriceOracle {
+        /// Creates a new oracle administered by the caller.
+        ///
+        /// Prices are served once `min_reporters` feeders have reported within
+        /// `max_age` milliseconds.
+        #[ink(constructor)]
+        pub fn new(max_age: Timestamp, min_reporters: u32) -> Self {
+            Self {
+                admin: Self::env().caller(),
+                feeders: StorageVec::new(),
+                reports: StorageHashMap::new(),
+                max_age,
+                min_reporters,
+            }
+        }
+
+        /// Returns the account allowed to manage feeders and settings.
+        #[ink(message)]
+        pub fn admin(&self) -> AccountId {
+            self.admin
+        }
+
+        /// Returns how old, in milliseconds, a report may be to count as fresh.
+        #[ink(message)]
+        pub fn max_age(&self) -> Timestamp {
+            self.max_age
+        }
+
+        /// Returns how many fresh reports are needed to serve a price.
+        #[ink(message)]
+        pub fn min_reporters(&self) -> u32 {
+            self.min_reporters
+        }
+
+        /// Returns `true` if `account` may submit prices.
+        #[ink(message)]
+        pub fn is_feeder(&self, account: AccountId) -> bool {
+            self.feeders.iter().any(|feeder| *feeder == account)
+        }
+
+        /// Returns the latest report of `feeder` for `asset`, fresh or not.
+        #[ink(message)]
+        pub fn report_of(
+            &self,
+            asset: AccountId,
+            feeder: AccountId,
+        ) -> Option<PriceReport> {
+            self.reports.get(&(asset, feeder)).copied()
+        }
+
+        /// Returns the median of the fresh prices reported for `asset` and the time
+        /// of the oldest report it was computed from.
+        ///
+        /// Only reports of current feeders are taken into account.
+        ///
+        /// # Errors
+        ///
+        /// Returns `NotEnoughReporters` error if fewer than `min_reporters` feeders
+        /// have reported the asset.
+        ///
+        /// Returns `StalePrice` error if fewer than `min_reporters` of the reports
+        /// are at most `max_age` old.
+        #[ink(message)]
+        pub fn get_price(&self, asset: AccountId) -> Result<(Balance, Timestamp)> {
+            let now = self.env().block_timestamp();
+            let mut reporters = 0;
+            let mut prices = Vec::new();
+            let mut oldest = now;
+            for feeder in self.feeders.iter() {
+                if let Some(report) = self.reports.get(&(asset, *feeder)) {
+                    reporters += 1;
+                    if now.saturating_sub(report.timestamp) <= self.max_age {
+                        prices.push(report.price);
+                        oldest = core::cmp::min(oldest, report.timestamp);
+                    }
+                }
+            }
+            if reporters < self.min_reporters || reporters == 0 {
+                return Err(Error::NotEnoughReporters)
+            }
+            if (prices.len() as u32) < self.min_reporters || prices.is_empty() {
+                return Err(Error::StalePrice)
+            }
+            prices.sort_unstable();
+            let middle = prices.len() / 2;
+            let median = if prices.len() % 2 == 0 {
+                let (low, high) = (prices[middle - 1], prices[middle]);
+                low + (high - low) / 2
+            } else {
+                prices[middle]
+            };
+            Ok((median, oldest))
+        }
+
+        /// Submits the `price` of one whole token of `asset` in the base currency.
+        ///
+        /// On success a `PriceSubmitted` event is emitted.
+        ///
+        /// # Errors
+        ///
+        /// Returns `CallerNotFeeder` error if the caller is not a whitelisted feeder.
+        ///
+        /// Returns `InvalidPrice` error if `price` is zero.
+        #[ink(message)]
+        pub fn submit_price(&mut self, asset: AccountId, price: Balance) -> Result<()> {
+            let feeder = self.env().caller();
+            if !self.is_feeder(feeder) {
+                return Err(Error::CallerNotFeeder)
+            }
+            if price == 0 {
+                return Err(Error::InvalidPrice)
+            }
+            let timestamp = self.env().block_timestamp();
+            self.reports
+                .insert((asset, feeder), PriceReport { price, timestamp });
+            self.env().emit_event(PriceSubmitted {
+                asset,
+                feeder,
+                price,
+            });
+            Ok(())
+        }
+
+        /// Whitelists `feeder` to submit prices.
+        ///
+        /// On success a `FeederAdded` event is emitted.
+        ///
+        /// # Errors
+        ///
+        /// Returns `CallerNotAdmin` error if the caller is not the admin.
+        #[ink(message)]
+        pub fn add_feeder(&mut self, feeder: AccountId) -> Result<()> {
+            self.ensure_admin()?;
+            if !self.is_feeder(feeder) {
+                self.feeders.push(feeder);
+            }
+            self.env().emit_event(FeederAdded { feeder });
+            Ok(())
+        }
+
+        /// Removes `feeder` from the whitelist; its reports are no longer used.
+        ///
+        /// On success a `FeederRemoved` event is emitted.
+        ///
+        /// # Errors
+        ///
+        /// Returns `CallerNotAdmin` error if the caller is not the admin.
+        #[ink(message)]
+        pub fn remove_feeder(&mut self, feeder: AccountId) -> Result<()> {
+            self.ensure_admin()?;
+            if let Some(index) = self.feeders.iter().position(|f| *f == feeder) {
+                self.feeders.swap_remove_drop(index as u32);
+            }
+            self.env().emit_event(FeederRemoved { feeder });
+            Ok(())
+        }
+
+        /// Sets how old, in milliseconds, a report may be to count as fresh.
+        ///
+        /// # Errors
+        ///
+        /// Returns `CallerNotAdmin` error if the caller is not the admin.
+        #[ink(message)]
+        pub fn set_max_age(&mut self, max_age: Timestamp) -> Result<()> {
+            self.ensure_admin()?;
+            self.max_age = max_age;
+            Ok(())
+        }
+
+        /// Sets how many fresh reports are needed to serve a price.
+        ///
+        /// # Errors
+        ///
+        /// Returns `CallerNotAdmin` error if the caller is not the admin.
+        #[ink(message)]
+        pub fn set_min_reporters(&mut self, min_reporters: u32) -> Result<()> {
+            self.ensure_admin()?;
+            self.min_reporters = min_reporters;
+            Ok(())
+        }
+
+        /// Returns `CallerNotAdmin` error if the caller is not the admin.
+        fn ensure_admin(&self) -> Result<()> {
+            if self.env().caller() != self.admin {
+                return Err(Error::CallerNotAdmin)
+            }
+            Ok(())
+        }
+    }
+
+    /// Unit tests.
+    #[cfg(test)]
+    mod tests {
+        /// Imports all the definitions from the outer scope so we can use them here.
+        use super::*;
+
+        use ink_lang as ink;
+
+        /// Address of an `Erc20` token used as the asset key.
+        const TOKEN: [u8; 32] = [0xAA; 32];
+
+        fn default_accounts(
+        ) -> ink_env::test::DefaultAccounts<ink_env::DefaultEnvironment> {
+            ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts")
+        }
+
+        /// Pushes a new execution context so that `caller` calls the oracle.
+        fn set_caller(caller: AccountId) {
+            let callee = ink_env::account_id::<ink_env::DefaultEnvironment>()
+                .unwrap_or([0x0; 32].into());
+            // Create call.
+            let mut data =
+                ink_env::test::CallData::new(ink_env::call::Selector::new([0x00; 4]));
+            data.push_arg(&caller);
+            ink_env::test::push_execution_context::<ink_env::DefaultEnvironment>(
+                caller, callee, 1000000, 1000000, data,
+            );
+        }
+
+        fn now() -> Timestamp {
+            ink_env::block_timestamp::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get block timestamp")
+        }
+
+        /// Lets `feeder` submit `price` for the test token.
+        fn submit_as(oracle: &mut PriceOracle, feeder: AccountId, price: Balance) {
+            set_caller(feeder);
+            assert_eq!(oracle.submit_price(TOKEN.into(), price), Ok(()));
+            ink_env::test::pop_execution_context();
+        }
+
+        /// Creates an oracle needing two reporters with Bob, Charlie and Django as
+        /// feeders.
+        fn new_oracle(max_age: Timestamp) -> PriceOracle {
+            let accounts = default_accounts();
+            let mut oracle = PriceOracle::new(max_age, 2);
+            assert_eq!(oracle.add_feeder(accounts.bob), Ok(()));
+            assert_eq!(oracle.add_feeder(accounts.charlie), Ok(()));
+            assert_eq!(oracle.add_feeder(accounts.django), Ok(()));
+            oracle
+        }
+
+        #[ink::test]
+        fn new_works() {
+            let oracle = PriceOracle::new(60_000, 3);
+            assert_eq!(oracle.admin(), default_accounts().alice);
+            assert_eq!(oracle.max_age(), 60_000);
+            assert_eq!(oracle.min_reporters(), 3);
+        }
+
+        #[ink::test]
+        fn only_feeders_submit_prices() {
+            let mut oracle = new_oracle(60_000);
+            let accounts = default_accounts();
+
+            assert_eq!(
+                oracle.submit_price(TOKEN.into(), 100),
+                Err(Error::CallerNotFeeder)
+            );
+            set_caller(accounts.bob);
+            assert_eq!(
+                oracle.submit_price(TOKEN.into(), 0),
+                Err(Error::InvalidPrice)
+            );
+            assert_eq!(oracle.add_feeder(accounts.bob), Err(Error::CallerNotAdmin));
+            assert_eq!(oracle.submit_price(TOKEN.into(), 100), Ok(()));
+            assert_eq!(
+                oracle.report_of(TOKEN.into(), accounts.bob),
+                Some(PriceReport {
+                    price: 100,
+                    timestamp: now()
+                })
+            );
+        }
+
+        #[ink::test]
+        fn median_of_odd_reports_works() {
+            let mut oracle = new_oracle(60_000);
+            let accounts = default_accounts();
+            submit_as(&mut oracle, accounts.bob, 105);
+            submit_as(&mut oracle, accounts.charlie, 1000);
+            submit_as(&mut oracle, accounts.django, 100);
+
+            assert_eq!(oracle.get_price(TOKEN.into()), Ok((105, now())));
+        }
+
+        #[ink::test]
+        fn median_of_even_reports_works() {
+            let mut oracle = new_oracle(60_000);
+            let accounts = default_accounts();
+            submit_as(&mut oracle, accounts.bob, 101);
+            submit_as(&mut oracle, accounts.charlie, 110);
+
+            assert_eq!(oracle.get_price(TOKEN.into()), Ok((105, now())));
+        }
+
+        #[ink::test]
+        fn too_few_reporters_fail() {
+            let mut oracle = new_oracle(60_000);
+            let accounts = default_accounts();
+            assert_eq!(
+                oracle.get_price(TOKEN.into()),
+                Err(Error::NotEnoughReporters)
+            );
+            submit_as(&mut oracle, accounts.bob, 100);
+            assert_eq!(
+                oracle.get_price(TOKEN.into()),
+                Err(Error::NotEnoughReporters)
+            );
+        }
+
+        #[ink::test]
+        fn removed_feeders_are_ignored() {
+            let mut oracle = new_oracle(60_000);
+            let accounts = default_accounts();
+            submit_as(&mut oracle, accounts.bob, 100);
+            submit_as(&mut oracle, accounts.charlie, 100);
+
+            assert_eq!(oracle.remove_feeder(accounts.charlie), Ok(()));
+            assert!(!oracle.is_feeder(accounts.charlie));
+            assert_eq!(
+                oracle.get_price(TOKEN.into()),
+                Err(Error::NotEnoughReporters)
+            );
+        }
+
+        #[ink::test]
+        fn stale_reports_fail() {
+            // Only reports from the current block are fresh.
+            let mut oracle = new_oracle(0);
+            let accounts = default_accounts();
+            submit_as(&mut oracle, accounts.bob, 100);
+            submit_as(&mut oracle, accounts.charlie, 100);
+            assert_eq!(oracle.get_price(TOKEN.into()), Ok((100, now())));
+
+            ink_env::test::advance_block::<ink_env::DefaultEnvironment>()
+                .expect("Cannot advance block");
+            assert_eq!(oracle.get_price(TOKEN.into()), Err(Error::StalePrice));
+
+            // A single fresh report is not enough either.
+            submit_as(&mut oracle, accounts.django, 120);
+            assert_eq!(oracle.get_price(TOKEN.into()), Err(Error::StalePrice));
+
+            submit_as(&mut oracle, accounts.bob, 110);
+            assert_eq!(oracle.get_price(TOKEN.into()), Ok((115, now())));
+        }
+    }
+}
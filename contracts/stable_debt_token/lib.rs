@@ -0,0 +1,690 @@
+// Copyright 2021 Bandot Developers.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+use ink_lang as ink;
+
+pub use self::stable_debt_token::StableDebtToken;
+
+#[ink::contract]
+pub mod stable_debt_token {
+    #[cfg(not(feature = "ink-as-dependency"))]
+    use ink_storage::{
+        collections::HashMap as StorageHashMap,
+        lazy::Lazy,
+    };
+    use lending_math::{
+        calculate_compounded_interest,
+        ray_div,
+        ray_mul,
+        RAY,
+    };
+
+    /// Minimum distance, in rays, between a borrower's locked rate and the market
+    /// stable rate before the pool may rebalance the borrower.
+    const REBALANCE_THRESHOLD: Balance = RAY / 20;
+
+    /// A non-transferable token recording the stable-rate debt of each borrower.
+    ///
+    /// Every borrower keeps the rate locked in when borrowing. Its debt compounds at
+    /// that rate from the timestamp of its last update.
+    #[ink(storage)]
+    pub struct StableDebtToken {
+        /// The lending pool allowed to mint and burn debt.
+        pool: AccountId,
+        /// The `Erc20` contract of the underlying reserve asset.
+        underlying_asset: AccountId,
+        /// The market stable rate last reported by the pool.
+        current_stable_rate: Lazy<Balance>,
+        /// The average stable rate of all outstanding debt.
+        average_stable_rate: Lazy<Balance>,
+        /// Total debt as of `total_supply_timestamp`.
+        total_supply: Lazy<Balance>,
+        /// The time `total_supply` was last updated.
+        total_supply_timestamp: Lazy<Timestamp>,
+        /// Mapping from borrower to its debt as of its last update.
+        balances: StorageHashMap<AccountId, Balance>,
+        /// Mapping from borrower to its locked stable rate.
+        user_rates: StorageHashMap<AccountId, Balance>,
+        /// Mapping from borrower to the time of its last update.
+        timestamps: StorageHashMap<AccountId, Timestamp>,
+    }
+
+    /// Event emitted when debt is created or cleared.
+    #[ink(event)]
+    pub struct Transfer {
+        #[ink(topic)]
+        from: Option<AccountId>,
+        #[ink(topic)]
+        to: Option<AccountId>,
+        #[ink(topic)]
+        value: Balance,
+    }
+
+    /// Event emitted when the pool mints `value` debt to `user`.
+    #[ink(event)]
+    pub struct Mint {
+        #[ink(topic)]
+        user: AccountId,
+        value: Balance,
+        /// Interest accrued by `user` since its last update.
+        balance_increase: Balance,
+        /// The resulting stable rate of `user`.
+        rate: Balance,
+        /// The resulting average stable rate of the reserve.
+        average_stable_rate: Balance,
+    }
+
+    /// Event emitted when the pool burns `value` debt of `user`.
+    #[ink(event)]
+    pub struct Burn {
+        #[ink(topic)]
+        user: AccountId,
+        value: Balance,
+        /// Interest accrued by `user` since its last update.
+        balance_increase: Balance,
+        /// The resulting average stable rate of the reserve.
+        average_stable_rate: Balance,
+    }
+
+    /// Event emitted when the locked rate of `user` is moved to the market rate.
+    #[ink(event)]
+    pub struct RebalanceStableRate {
+        #[ink(topic)]
+        user: AccountId,
+        old_rate: Balance,
+        new_rate: Balance,
+    }
+
+    /// The stable debt token error types.
+    #[derive(Debug, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+    pub enum Error {
+        /// Returned if not enough debt to fulfill a request is recorded.
+        InsufficientBalance,
+        /// Returned if the caller is not the lending pool.
+        CallerNotPool,
+        /// Returned by `transfer`, `transfer_from` and `approve`, since debt cannot
+        /// change hands.
+        TransferNotSupported,
+        /// Returned if the borrower's rate is too close to the market rate to be
+        /// rebalanced.
+        RebalanceNotNeeded,
+        /// Returned if the amount of new debt is zero.
+        InvalidAmount,
+    }
+
+    /// The stable debt token result type.
+    pub type Result<T> = core::result::Result<T, Error>;
+
+    impl StableDebtToken {
+        /// Creates a new stable debt token for `underlying_asset` managed by `pool`.
+        #[ink(constructor)]
+        pub fn new(pool: AccountId, underlying_asset: AccountId) -> Self {
+            Self {
+                pool,
+                underlying_asset,
+                current_stable_rate: Lazy::new(0),
+                average_stable_rate: Lazy::new(0),
+                total_supply: Lazy::new(0),
+                total_supply_timestamp: Lazy::new(0),
+                balances: StorageHashMap::new(),
+                user_rates: StorageHashMap::new(),
+                timestamps: StorageHashMap::new(),
+            }
+        }
+
+        /// Returns the lending pool allowed to mint and burn debt.
+        #[ink(message)]
+        pub fn pool(&self) -> AccountId {
+            self.pool
+        }
+
+        /// Returns the `Erc20` contract of the underlying reserve asset.
+        #[ink(message)]
+        pub fn underlying_asset(&self) -> AccountId {
+            self.underlying_asset
+        }
+
+        /// Returns the market stable rate last reported by the pool.
+        #[ink(message)]
+        pub fn current_stable_rate(&self) -> Balance {
+            *self.current_stable_rate
+        }
+
+        /// Returns the average stable rate of all outstanding debt.
+        #[ink(message)]
+        pub fn average_stable_rate(&self) -> Balance {
+            *self.average_stable_rate
+        }
+
+        /// Returns the total debt, including interest compounded at the average rate.
+        #[ink(message)]
+        pub fn total_supply(&self) -> Balance {
+            self.compound(
+                *self.total_supply,
+                *self.average_stable_rate,
+                *self.total_supply_timestamp,
+            )
+        }
+
+        /// Returns the debt owed by `owner`, including interest compounded at its
+        /// locked rate.
+        ///
+        /// Returns `0` if the account is non-existent.
+        #[ink(message)]
+        pub fn balance_of(&self, owner: AccountId) -> Balance {
+            self.compound(
+                self.principal_balance_of(owner),
+                self.user_stable_rate(owner),
+                self.user_last_updated(owner),
+            )
+        }
+
+        /// Returns the debt of `owner` as of its last update.
+        #[ink(message)]
+        pub fn principal_balance_of(&self, owner: AccountId) -> Balance {
+            self.balances.get(&owner).copied().unwrap_or(0)
+        }
+
+        /// Returns the stable rate locked in by `owner`.
+        #[ink(message)]
+        pub fn user_stable_rate(&self, owner: AccountId) -> Balance {
+            self.user_rates.get(&owner).copied().unwrap_or(0)
+        }
+
+        /// Returns the time the debt of `owner` was last updated.
+        #[ink(message)]
+        pub fn user_last_updated(&self, owner: AccountId) -> Timestamp {
+            self.timestamps.get(&owner).copied().unwrap_or(0)
+        }
+
+        /// Always returns `0`, since debt cannot be approved.
+        #[ink(message)]
+        pub fn allowance(&self, _owner: AccountId, _spender: AccountId) -> Balance {
+            0
+        }
+
+        /// Debt cannot be transferred.
+        ///
+        /// # Errors
+        ///
+        /// Always returns `TransferNotSupported` error.
+        #[ink(message)]
+        pub fn transfer(&mut self, _to: AccountId, _value: Balance) -> Result<()> {
+            Err(Error::TransferNotSupported)
+        }
+
+        /// Debt cannot be approved.
+        ///
+        /// # Errors
+        ///
+        /// Always returns `TransferNotSupported` error.
+        #[ink(message)]
+        pub fn approve(&mut self, _spender: AccountId, _value: Balance) -> Result<()> {
+            Err(Error::TransferNotSupported)
+        }
+
+        /// Debt cannot be transferred.
+        ///
+        /// # Errors
+        ///
+        /// Always returns `TransferNotSupported` error.
+        #[ink(message)]
+        pub fn transfer_from(
+            &mut self,
+            _from: AccountId,
+            _to: AccountId,
+            _value: Balance,
+        ) -> Result<()> {
+            Err(Error::TransferNotSupported)
+        }
+
+        /// Records the market stable rate of the reserve.
+        ///
+        /// # Errors
+        ///
+        /// Returns `CallerNotPool` error if the caller is not the lending pool.
+        #[ink(message)]
+        pub fn update_stable_rate(&mut self, rate: Balance) -> Result<()> {
+            self.ensure_pool()?;
+            *self.current_stable_rate = rate;
+            Ok(())
+        }
+
+        /// Records `value` of new debt for `user` at the market stable `rate`.
+        ///
+        /// The user's rate becomes the average of its current debt and the new debt,
+        /// weighted by amount.
+        ///
+        /// On success a `Transfer` and a `Mint` event are emitted.
+        ///
+        /// # Errors
+        ///
+        /// Returns `CallerNotPool` error if the caller is not the lending pool.
+        ///
+        /// Returns `InvalidAmount` error if `value` is zero.
+        #[ink(message)]
+        pub fn mint(
+            &mut self,
+            user: AccountId,
+            value: Balance,
+            rate: Balance,
+        ) -> Result<()> {
+            self.ensure_pool()?;
+            // An empty account minting nothing would divide by a zero balance.
+            if value == 0 {
+                return Err(Error::InvalidAmount)
+            }
+            *self.current_stable_rate = rate;
+            let (balance, balance_increase) = self.accrue(user);
+            let new_balance = balance + value;
+            let user_rate = ray_div(
+                ray_mul(self.user_stable_rate(user), balance) + ray_mul(rate, value),
+                new_balance,
+            );
+            self.user_rates.insert(user, user_rate);
+            self.balances.insert(user, new_balance);
+
+            let total_supply = self.total_supply();
+            let new_total_supply = total_supply + value;
+            *self.average_stable_rate = ray_div(
+                ray_mul(*self.average_stable_rate, total_supply) + ray_mul(rate, value),
+                new_total_supply,
+            );
+            self.set_total_supply(new_total_supply);
+
+            self.env().emit_event(Transfer {
+                from: None,
+                to: Some(user),
+                value: value + balance_increase,
+            });
+            self.env().emit_event(Mint {
+                user,
+                value,
+                balance_increase,
+                rate: user_rate,
+                average_stable_rate: *self.average_stable_rate,
+            });
+            Ok(())
+        }
+
+        /// Clears `value` of the debt of `user`.
+        ///
+        /// On success a `Transfer` and a `Burn` event are emitted.
+        ///
+        /// # Errors
+        ///
+        /// Returns `CallerNotPool` error if the caller is not the lending pool.
+        ///
+        /// Returns `InsufficientBalance` error if `user` owes less than `value`.
+        #[ink(message)]
+        pub fn burn(&mut self, user: AccountId, value: Balance) -> Result<()> {
+            self.ensure_pool()?;
+            if self.balance_of(user) < value {
+                return Err(Error::InsufficientBalance)
+            }
+            let user_rate = self.user_stable_rate(user);
+            let (balance, balance_increase) = self.accrue(user);
+            let new_balance = balance - value;
+            self.balances.insert(user, new_balance);
+            if new_balance == 0 {
+                self.user_rates.insert(user, 0);
+            }
+
+            let total_supply = self.total_supply();
+            // The compounded average can drift below the sum of user balances.
+            let new_total_supply = total_supply.saturating_sub(value);
+            *self.average_stable_rate = if new_total_supply == 0 {
+                0
+            } else {
+                ray_div(
+                    ray_mul(*self.average_stable_rate, total_supply)
+                        .saturating_sub(ray_mul(user_rate, value)),
+                    new_total_supply,
+                )
+            };
+            self.set_total_supply(new_total_supply);
+
+            // Report the net change of the balance, so that wallets summing up
+            // transfers show the right debt.
+            if balance_increase > value {
+                self.env().emit_event(Transfer {
+                    from: None,
+                    to: Some(user),
+                    value: balance_increase - value,
+                });
+            } else {
+                self.env().emit_event(Transfer {
+                    from: Some(user),
+                    to: None,
+                    value: value - balance_increase,
+                });
+            }
+            self.env().emit_event(Burn {
+                user,
+                value,
+                balance_increase,
+                average_stable_rate: *self.average_stable_rate,
+            });
+            Ok(())
+        }
+
+        /// Moves the locked rate of `user` to the market stable rate.
+        ///
+        /// Interest accrued so far is kept at the old rate.
+        ///
+        /// On success a `RebalanceStableRate` event is emitted.
+        ///
+        /// # Errors
+        ///
+        /// Returns `CallerNotPool` error if the caller is not the lending pool.
+        ///
+        /// Returns `RebalanceNotNeeded` error if the locked rate is within
+        /// `REBALANCE_THRESHOLD` of the market stable rate.
+        #[ink(message)]
+        pub fn rebalance_stable_rate(&mut self, user: AccountId) -> Result<()> {
+            self.ensure_pool()?;
+            let old_rate = self.user_stable_rate(user);
+            let new_rate = *self.current_stable_rate;
+            let distance = if old_rate > new_rate {
+                old_rate - new_rate
+            } else {
+                new_rate - old_rate
+            };
+            if self.principal_balance_of(user) == 0 || distance < REBALANCE_THRESHOLD {
+                return Err(Error::RebalanceNotNeeded)
+            }
+            let (balance, balance_increase) = self.accrue(user);
+            self.user_rates.insert(user, new_rate);
+
+            let total_supply = self.total_supply();
+            *self.average_stable_rate = ray_div(
+                (ray_mul(*self.average_stable_rate, total_supply)
+                    + ray_mul(new_rate, balance))
+                .saturating_sub(ray_mul(old_rate, balance)),
+                total_supply,
+            );
+            self.set_total_supply(total_supply);
+
+            if balance_increase > 0 {
+                self.env().emit_event(Transfer {
+                    from: None,
+                    to: Some(user),
+                    value: balance_increase,
+                });
+            }
+            self.env().emit_event(RebalanceStableRate {
+                user,
+                old_rate,
+                new_rate,
+            });
+            Ok(())
+        }
+
+        /// Returns `principal` compounded at `rate` from `since` until now.
+        fn compound(
+            &self,
+            principal: Balance,
+            rate: Balance,
+            since: Timestamp,
+        ) -> Balance {
+            if principal == 0 {
+                return 0
+            }
+            let elapsed = self.env().block_timestamp().saturating_sub(since);
+            ray_mul(principal, calculate_compounded_interest(rate, elapsed))
+        }
+
+        /// Adds the interest accrued by `user` to its principal and restarts its
+        /// compounding period.
+        ///
+        /// Returns the new principal and the interest that was added.
+        fn accrue(&mut self, user: AccountId) -> (Balance, Balance) {
+            let principal = self.principal_balance_of(user);
+            let balance = self.balance_of(user);
+            self.balances.insert(user, balance);
+            self.timestamps.insert(user, self.env().block_timestamp());
+            (balance, balance - principal)
+        }
+
+        /// Stores `value` as the total debt as of now.
+        fn set_total_supply(&mut self, value: Balance) {
+            *self.total_supply = value;
+            *self.total_supply_timestamp = self.env().block_timestamp();
+        }
+
+        /// Returns `CallerNotPool` error if the caller is not the lending pool.
+        fn ensure_pool(&self) -> Result<()> {
+            if self.env().caller() != self.pool {
+                return Err(Error::CallerNotPool)
+            }
+            Ok(())
+        }
+    }
+
+    /// Unit tests.
+    #[cfg(test)]
+    mod tests {
+        /// Imports all the definitions from the outer scope so we can use them here.
+        use super::*;
+
+        use ink_lang as ink;
+
+        const UNDERLYING: [u8; 32] = [0xAA; 32];
+
+        /// One whole token with 18 decimals.
+        const UNIT: Balance = 1_000_000_000_000_000_000;
+
+        fn default_accounts(
+        ) -> ink_env::test::DefaultAccounts<ink_env::DefaultEnvironment> {
+            ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts")
+        }
+
+        /// Pushes a new execution context so that `caller` calls the token.
+        fn set_caller(caller: AccountId) {
+            let callee = ink_env::account_id::<ink_env::DefaultEnvironment>()
+                .unwrap_or([0x0; 32].into());
+            // Create call.
+            let mut data =
+                ink_env::test::CallData::new(ink_env::call::Selector::new([0x00; 4]));
+            data.push_arg(&caller);
+            ink_env::test::push_execution_context::<ink_env::DefaultEnvironment>(
+                caller, callee, 1000000, 1000000, data,
+            );
+        }
+
+        fn now() -> Timestamp {
+            ink_env::block_timestamp::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get block timestamp")
+        }
+
+        fn advance_blocks(count: usize) {
+            for _ in 0..count {
+                ink_env::test::advance_block::<ink_env::DefaultEnvironment>()
+                    .expect("Cannot advance block");
+            }
+        }
+
+        /// Creates a debt token whose pool is Alice.
+        fn new_token() -> StableDebtToken {
+            StableDebtToken::new(default_accounts().alice, UNDERLYING.into())
+        }
+
+        #[ink::test]
+        fn mint_locks_rate() {
+            let mut token = new_token();
+            let accounts = default_accounts();
+
+            assert_eq!(token.mint(accounts.bob, 100 * UNIT, RAY / 10), Ok(()));
+            assert_eq!(token.balance_of(accounts.bob), 100 * UNIT);
+            assert_eq!(token.user_stable_rate(accounts.bob), RAY / 10);
+            assert_eq!(token.user_last_updated(accounts.bob), now());
+            assert_eq!(token.average_stable_rate(), RAY / 10);
+            assert_eq!(token.total_supply(), 100 * UNIT);
+            assert_eq!(ink_env::test::recorded_events().count(), 2);
+        }
+
+        #[ink::test]
+        fn debt_compounds_over_time() {
+            let mut token = new_token();
+            let accounts = default_accounts();
+            assert_eq!(token.mint(accounts.bob, 100 * UNIT, RAY / 10), Ok(()));
+            let start = now();
+
+            advance_blocks(10);
+            let interest = calculate_compounded_interest(RAY / 10, now() - start);
+            assert!(interest > RAY);
+            assert_eq!(
+                token.balance_of(accounts.bob),
+                ray_mul(100 * UNIT, interest)
+            );
+            assert_eq!(token.total_supply(), ray_mul(100 * UNIT, interest));
+            // The principal only moves when the borrower is updated.
+            assert_eq!(token.principal_balance_of(accounts.bob), 100 * UNIT);
+        }
+
+        #[ink::test]
+        fn mint_rejects_zero_value() {
+            let mut token = new_token();
+            let accounts = default_accounts();
+
+            assert_eq!(
+                token.mint(accounts.bob, 0, RAY / 10),
+                Err(Error::InvalidAmount)
+            );
+            assert_eq!(token.balance_of(accounts.bob), 0);
+            assert_eq!(token.total_supply(), 0);
+            assert_eq!(ink_env::test::recorded_events().count(), 0);
+        }
+
+        #[ink::test]
+        fn mint_averages_user_rate() {
+            let mut token = new_token();
+            let accounts = default_accounts();
+
+            assert_eq!(token.mint(accounts.bob, 100 * UNIT, RAY / 10), Ok(()));
+            assert_eq!(token.mint(accounts.bob, 100 * UNIT, RAY / 5), Ok(()));
+            assert_eq!(token.balance_of(accounts.bob), 200 * UNIT);
+            assert_eq!(token.user_stable_rate(accounts.bob), 3 * RAY / 20);
+        }
+
+        #[ink::test]
+        fn average_stable_rate_is_weighted_by_debt() {
+            let mut token = new_token();
+            let accounts = default_accounts();
+
+            assert_eq!(token.mint(accounts.bob, 300 * UNIT, RAY / 10), Ok(()));
+            assert_eq!(
+                token.mint(accounts.charlie, 100 * UNIT, 3 * RAY / 10),
+                Ok(())
+            );
+            assert_eq!(token.average_stable_rate(), 3 * RAY / 20);
+            assert_eq!(token.total_supply(), 400 * UNIT);
+
+            assert_eq!(token.burn(accounts.charlie, 100 * UNIT), Ok(()));
+            assert_eq!(token.average_stable_rate(), RAY / 10);
+            assert_eq!(token.user_stable_rate(accounts.charlie), 0);
+            assert_eq!(token.total_supply(), 300 * UNIT);
+
+            assert_eq!(token.burn(accounts.bob, 300 * UNIT), Ok(()));
+            assert_eq!(token.average_stable_rate(), 0);
+            assert_eq!(token.total_supply(), 0);
+        }
+
+        #[ink::test]
+        fn burn_accrues_interest_first() {
+            let mut token = new_token();
+            let accounts = default_accounts();
+            assert_eq!(token.mint(accounts.bob, 100 * UNIT, RAY / 10), Ok(()));
+
+            advance_blocks(10);
+            let debt = token.balance_of(accounts.bob);
+            assert_eq!(
+                token.burn(accounts.bob, debt + 1),
+                Err(Error::InsufficientBalance)
+            );
+            assert_eq!(token.burn(accounts.bob, 100 * UNIT), Ok(()));
+            assert_eq!(token.balance_of(accounts.bob), debt - 100 * UNIT);
+            assert_eq!(token.user_last_updated(accounts.bob), now());
+        }
+
+        #[ink::test]
+        fn rebalance_moves_rate_to_market() {
+            let mut token = new_token();
+            let accounts = default_accounts();
+            assert_eq!(token.mint(accounts.bob, 100 * UNIT, RAY / 10), Ok(()));
+            assert_eq!(token.mint(accounts.charlie, 100 * UNIT, RAY / 10), Ok(()));
+
+            // Within the threshold nothing happens.
+            assert_eq!(token.update_stable_rate(RAY / 10 + RAY / 50), Ok(()));
+            assert_eq!(
+                token.rebalance_stable_rate(accounts.bob),
+                Err(Error::RebalanceNotNeeded)
+            );
+
+            assert_eq!(token.update_stable_rate(RAY / 5), Ok(()));
+            assert_eq!(token.rebalance_stable_rate(accounts.bob), Ok(()));
+            assert_eq!(token.user_stable_rate(accounts.bob), RAY / 5);
+            assert_eq!(token.user_stable_rate(accounts.charlie), RAY / 10);
+            assert_eq!(token.average_stable_rate(), 3 * RAY / 20);
+            assert_eq!(
+                token.rebalance_stable_rate(accounts.eve),
+                Err(Error::RebalanceNotNeeded)
+            );
+        }
+
+        #[ink::test]
+        fn only_pool_can_change_debt() {
+            let mut token = new_token();
+            let accounts = default_accounts();
+            assert_eq!(token.mint(accounts.bob, 100 * UNIT, RAY / 10), Ok(()));
+
+            set_caller(accounts.bob);
+            assert_eq!(
+                token.mint(accounts.bob, UNIT, RAY / 10),
+                Err(Error::CallerNotPool)
+            );
+            assert_eq!(token.burn(accounts.bob, UNIT), Err(Error::CallerNotPool));
+            assert_eq!(token.update_stable_rate(RAY), Err(Error::CallerNotPool));
+            assert_eq!(
+                token.rebalance_stable_rate(accounts.bob),
+                Err(Error::CallerNotPool)
+            );
+        }
+
+        #[ink::test]
+        fn debt_cannot_be_transferred() {
+            let mut token = new_token();
+            let accounts = default_accounts();
+            assert_eq!(token.mint(accounts.alice, 100 * UNIT, RAY / 10), Ok(()));
+
+            assert_eq!(
+                token.transfer(accounts.bob, UNIT),
+                Err(Error::TransferNotSupported)
+            );
+            assert_eq!(
+                token.approve(accounts.bob, UNIT),
+                Err(Error::TransferNotSupported)
+            );
+            assert_eq!(
+                token.transfer_from(accounts.alice, accounts.bob, UNIT),
+                Err(Error::TransferNotSupported)
+            );
+            assert_eq!(token.balance_of(accounts.bob), 0);
+        }
+    }
+}
@@ -0,0 +1,393 @@
+// Copyright 2021 Bandot Developers.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+use ink_lang as ink;
+
+pub use self::treasury::Treasury;
+
+#[ink::contract]
+pub mod treasury {
+    /// Holds the share of the interest the `LendingPool` reserves for the
+    /// protocol.
+    ///
+    /// The pool mints the share to the treasury as `AToken`s of each reserve and
+    /// emits a `TreasuryAccrued` event for it, without calling the treasury, so
+    /// that the treasury's own withdrawals from the pool can accrue as well.
+    /// Governance, typically a `Governor` contract, decides where it goes.
+    #[ink(storage)]
+    pub struct Treasury {
+        /// The `LendingPool` contract accruing to the treasury.
+        pool: AccountId,
+        /// The account allowed to spend the treasury.
+        governance: AccountId,
+    }
+
+    /// Event emitted when governance sends `amount` tokens of `asset` to
+    /// `recipient`.
+    #[ink(event)]
+    pub struct Withdrawn {
+        #[ink(topic)]
+        asset: AccountId,
+        #[ink(topic)]
+        recipient: AccountId,
+        amount: Balance,
+    }
+
+    /// The treasury error types.
+    #[derive(Debug, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+    pub enum Error {
+        /// Returned if the caller is not governance.
+        CallerNotGovernance,
+        /// Returned if the lending pool refused to pay out the deposit.
+        WithdrawFailed,
+        /// Returned if the reserve `Erc20` refused a transfer.
+        TransferFailed,
+    }
+
+    /// The treasury result type.
+    pub type Result<T> = core::result::Result<T, Error>;
+
+    impl Treasury {
+        /// Creates a new treasury for the `LendingPool` at `pool`, spent by
+        /// `governance`.
+        #[ink(constructor)]
+        pub fn new(pool: AccountId, governance: AccountId) -> Self {
+            Self { pool, governance }
+        }
+
+        /// Returns the `LendingPool` contract accruing to the treasury.
+        #[ink(message)]
+        pub fn pool(&self) -> AccountId {
+            self.pool
+        }
+
+        /// Returns the account allowed to spend the treasury.
+        #[ink(message)]
+        pub fn governance(&self) -> AccountId {
+            self.governance
+        }
+
+        /// Returns the treasury's deposit of `asset` in the pool, including the
+        /// interest it earned.
+        #[ink(message)]
+        pub fn balance_of(&self, asset: AccountId) -> Balance {
+            ext::deposit_of(self.pool, asset)
+        }
+
+        /// Withdraws `amount` tokens of `asset` from the treasury's deposit in the
+        /// pool and sends them to `recipient`.
+        ///
+        /// On success a `Withdrawn` event is emitted.
+        ///
+        /// # Errors
+        ///
+        /// Returns `CallerNotGovernance` error if the caller is not governance.
+        ///
+        /// Returns `WithdrawFailed` error if the pool refused to pay out `amount`.
+        ///
+        /// # Panics
+        ///
+        /// Panics if the reserve refuses to move the withdrawn tokens, so that the
+        /// withdrawal from the pool is reverted as well.
+        #[ink(message)]
+        pub fn withdraw_to(
+            &mut self,
+            recipient: AccountId,
+            asset: AccountId,
+            amount: Balance,
+        ) -> Result<()> {
+            self.ensure_governance()?;
+            ext::withdraw(self.pool, asset, amount)?;
+            ext::transfer(asset, recipient, amount).expect("treasury transfer failed");
+            self.env().emit_event(Withdrawn {
+                asset,
+                recipient,
+                amount,
+            });
+            Ok(())
+        }
+
+        /// Hands control of the treasury over to `new_governance`.
+        ///
+        /// # Errors
+        ///
+        /// Returns `CallerNotGovernance` error if the caller is not governance.
+        #[ink(message)]
+        pub fn transfer_governance(&mut self, new_governance: AccountId) -> Result<()> {
+            self.ensure_governance()?;
+            self.governance = new_governance;
+            Ok(())
+        }
+
+        /// Returns `CallerNotGovernance` error if the caller is not governance.
+        fn ensure_governance(&self) -> Result<()> {
+            if self.env().caller() != self.governance {
+                return Err(Error::CallerNotGovernance)
+            }
+            Ok(())
+        }
+    }
+
+    /// Calls into the `LendingPool` and the reserve `Erc20` contracts.
+    #[cfg(not(test))]
+    mod ext {
+        use super::{
+            AccountId,
+            Balance,
+            Error,
+            Result,
+        };
+        use erc20::Erc20;
+        use ink_env::call::FromAccountId;
+        use lending_pool::LendingPool;
+
+        /// Returns the treasury's deposit of `asset` in `pool`.
+        pub fn deposit_of(pool: AccountId, asset: AccountId) -> Balance {
+            let pool: LendingPool = FromAccountId::from_account_id(pool);
+            let treasury = ink_env::account_id::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get contract address");
+            pool.deposit_of(asset, treasury)
+        }
+
+        /// Withdraws `amount` tokens of `asset` from the treasury's deposit in
+        /// `pool`.
+        pub fn withdraw(
+            pool: AccountId,
+            asset: AccountId,
+            amount: Balance,
+        ) -> Result<()> {
+            let mut pool: LendingPool = FromAccountId::from_account_id(pool);
+            pool.withdraw(asset, amount)
+                .map_err(|_| Error::WithdrawFailed)
+        }
+
+        /// Moves `amount` tokens of `token` from the treasury to `to`.
+        pub fn transfer(token: AccountId, to: AccountId, amount: Balance) -> Result<()> {
+            let mut token: Erc20 = FromAccountId::from_account_id(token);
+            token
+                .transfer(to, amount)
+                .map_err(|_| Error::TransferFailed)
+        }
+    }
+
+    /// In-memory stand-ins for the `LendingPool` and the reserve `Erc20`
+    /// contracts.
+    ///
+    /// The off-chain environment cannot dispatch calls to other contracts, so
+    /// unit tests keep the treasury's pool deposits and token balances here
+    /// instead.
+    #[cfg(test)]
+    mod ext {
+        use super::{
+            AccountId,
+            Balance,
+            Error,
+            Result,
+        };
+        use std::{
+            cell::RefCell,
+            collections::HashMap,
+        };
+
+        thread_local! {
+            static DEPOSITS: RefCell<HashMap<(AccountId, AccountId), Balance>> =
+                RefCell::new(HashMap::new());
+            static BALANCES: RefCell<HashMap<(AccountId, AccountId), Balance>> =
+                RefCell::new(HashMap::new());
+        }
+
+        fn treasury() -> AccountId {
+            ink_env::account_id::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get contract address")
+        }
+
+        /// Returns the treasury's deposit of `asset` in `pool`.
+        pub fn deposit_of(pool: AccountId, asset: AccountId) -> Balance {
+            DEPOSITS.with(|deposits| {
+                deposits.borrow().get(&(pool, asset)).copied().unwrap_or(0)
+            })
+        }
+
+        /// Sets the treasury's deposit of `asset` in `pool` to `amount`.
+        pub fn set_deposit(pool: AccountId, asset: AccountId, amount: Balance) {
+            DEPOSITS.with(|deposits| {
+                deposits.borrow_mut().insert((pool, asset), amount);
+            })
+        }
+
+        /// Returns the `token` balance of `owner`.
+        pub fn balance_of(token: AccountId, owner: AccountId) -> Balance {
+            BALANCES.with(|balances| {
+                balances.borrow().get(&(token, owner)).copied().unwrap_or(0)
+            })
+        }
+
+        fn set_balance(token: AccountId, owner: AccountId, amount: Balance) {
+            BALANCES.with(|balances| {
+                balances.borrow_mut().insert((token, owner), amount);
+            })
+        }
+
+        pub fn withdraw(
+            pool: AccountId,
+            asset: AccountId,
+            amount: Balance,
+        ) -> Result<()> {
+            let deposit = deposit_of(pool, asset);
+            if deposit < amount {
+                return Err(Error::WithdrawFailed)
+            }
+            set_deposit(pool, asset, deposit - amount);
+            set_balance(asset, treasury(), balance_of(asset, treasury()) + amount);
+            Ok(())
+        }
+
+        pub fn transfer(token: AccountId, to: AccountId, amount: Balance) -> Result<()> {
+            let balance = balance_of(token, treasury());
+            if balance < amount {
+                return Err(Error::TransferFailed)
+            }
+            set_balance(token, treasury(), balance - amount);
+            set_balance(token, to, balance_of(token, to) + amount);
+            Ok(())
+        }
+    }
+
+    /// Unit tests.
+    #[cfg(test)]
+    mod tests {
+        /// Imports all the definitions from the outer scope so we can use them here.
+        use super::*;
+
+        type Event = <Treasury as ::ink_lang::BaseEvent>::Type;
+
+        use ink_lang as ink;
+
+        const POOL: [u8; 32] = [0x01; 32];
+        const DOT: [u8; 32] = [0xAA; 32];
+
+        fn default_accounts(
+        ) -> ink_env::test::DefaultAccounts<ink_env::DefaultEnvironment> {
+            ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts")
+        }
+
+        fn contract_id() -> AccountId {
+            ink_env::account_id::<ink_env::DefaultEnvironment>()
+                .unwrap_or([0x0; 32].into())
+        }
+
+        /// Pushes a new execution context so that `caller` calls the treasury.
+        fn set_caller(caller: AccountId) {
+            // Create call.
+            let mut data =
+                ink_env::test::CallData::new(ink_env::call::Selector::new([0x00; 4]));
+            data.push_arg(&caller);
+            ink_env::test::push_execution_context::<ink_env::DefaultEnvironment>(
+                caller,
+                contract_id(),
+                1000000,
+                1000000,
+                data,
+            );
+        }
+
+        /// Creates a treasury governed by Alice.
+        fn setup() -> Treasury {
+            Treasury::new(POOL.into(), default_accounts().alice)
+        }
+
+        #[ink::test]
+        fn new_works() {
+            let treasury = setup();
+            assert_eq!(treasury.pool(), AccountId::from(POOL));
+            assert_eq!(treasury.governance(), default_accounts().alice);
+            assert_eq!(treasury.balance_of(DOT.into()), 0);
+        }
+
+        #[ink::test]
+        fn balance_of_reads_pool_deposit() {
+            let treasury = setup();
+            ext::set_deposit(POOL.into(), DOT.into(), 12);
+            assert_eq!(treasury.balance_of(DOT.into()), 12);
+            assert_eq!(treasury.balance_of([0xBB; 32].into()), 0);
+        }
+
+        #[ink::test]
+        fn withdraw_to_works() {
+            let mut treasury = setup();
+            let accounts = default_accounts();
+            let dot = AccountId::from(DOT);
+            ext::set_deposit(POOL.into(), dot, 100);
+
+            assert_eq!(treasury.withdraw_to(accounts.charlie, dot, 40), Ok(()));
+            assert_eq!(treasury.balance_of(dot), 60);
+            assert_eq!(ext::balance_of(dot, accounts.charlie), 40);
+            assert_eq!(ext::balance_of(dot, contract_id()), 0);
+            assert_eq!(
+                treasury.withdraw_to(accounts.charlie, dot, 61),
+                Err(Error::WithdrawFailed)
+            );
+
+            let emitted_events = ink_env::test::recorded_events().collect::<Vec<_>>();
+            assert_eq!(emitted_events.len(), 1);
+            let decoded =
+                <Event as scale::Decode>::decode(&mut &emitted_events[0].data[..])
+                    .expect("encountered invalid contract event data buffer");
+            match decoded {
+                Event::Withdrawn(Withdrawn {
+                    asset,
+                    recipient,
+                    amount,
+                }) => {
+                    assert_eq!(asset, dot);
+                    assert_eq!(recipient, accounts.charlie);
+                    assert_eq!(amount, 40);
+                }
+                _ => panic!("encountered unexpected event kind"),
+            }
+        }
+
+        #[ink::test]
+        fn only_governance_withdraws() {
+            let mut treasury = setup();
+            let accounts = default_accounts();
+            ext::set_deposit(POOL.into(), DOT.into(), 100);
+
+            set_caller(accounts.bob);
+            assert_eq!(
+                treasury.withdraw_to(accounts.bob, DOT.into(), 100),
+                Err(Error::CallerNotGovernance)
+            );
+            assert_eq!(
+                treasury.transfer_governance(accounts.bob),
+                Err(Error::CallerNotGovernance)
+            );
+            ink_env::test::pop_execution_context();
+
+            assert_eq!(treasury.transfer_governance(accounts.bob), Ok(()));
+            assert_eq!(treasury.governance(), accounts.bob);
+            assert_eq!(
+                treasury.withdraw_to(accounts.alice, DOT.into(), 100),
+                Err(Error::CallerNotGovernance)
+            );
+            set_caller(accounts.bob);
+            assert_eq!(treasury.withdraw_to(accounts.bob, DOT.into(), 100), Ok(()));
+            assert_eq!(ext::balance_of(DOT.into(), accounts.bob), 100);
+        }
+    }
+}
@@ -0,0 +1,504 @@
+// Copyright 2021 Bandot Developers.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+use ink_lang as ink;
+
+pub use self::variable_debt_token::VariableDebtToken;
+
+#[ink::contract]
+pub mod variable_debt_token {
+    #[cfg(not(feature = "ink-as-dependency"))]
+    use ink_storage::{
+        collections::HashMap as StorageHashMap,
+        lazy::Lazy,
+    };
+    use lending_math::{
+        ray_div,
+        ray_mul,
+    };
+
+    /// A non-transferable token recording the variable-rate debt of each borrower.
+    ///
+    /// Principals are stored scaled down by the reserve's variable borrow index at
+    /// the time they were borrowed, so that debts grow with the index. The current
+    /// index is read from the pool whenever a debt is converted.
+    #[ink(storage)]
+    pub struct VariableDebtToken {
+        /// The lending pool allowed to mint and burn debt.
+        pool: AccountId,
+        /// The `Erc20` contract of the underlying reserve asset.
+        underlying_asset: AccountId,
+        /// Total scaled debt.
+        total_supply: Lazy<Balance>,
+        /// Mapping from borrower to its scaled debt.
+        balances: StorageHashMap<AccountId, Balance>,
+    }
+
+    /// Event emitted when debt is created or cleared.
+    #[ink(event)]
+    pub struct Transfer {
+        #[ink(topic)]
+        from: Option<AccountId>,
+        #[ink(topic)]
+        to: Option<AccountId>,
+        #[ink(topic)]
+        value: Balance,
+    }
+
+    /// Event emitted when the pool mints `value` debt to `user` at borrow `index`.
+    #[ink(event)]
+    pub struct Mint {
+        #[ink(topic)]
+        user: AccountId,
+        value: Balance,
+        index: Balance,
+    }
+
+    /// Event emitted when the pool burns `value` debt of `user` at borrow `index`.
+    #[ink(event)]
+    pub struct Burn {
+        #[ink(topic)]
+        user: AccountId,
+        value: Balance,
+        index: Balance,
+    }
+
+    /// The variable debt token error types.
+    #[derive(Debug, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+    pub enum Error {
+        /// Returned if not enough debt to fulfill a request is recorded.
+        InsufficientBalance,
+        /// Returned if the caller is not the lending pool.
+        CallerNotPool,
+        /// Returned if the amount is too small to be represented as a scaled balance.
+        InvalidAmount,
+        /// Returned by `transfer`, `transfer_from` and `approve`, since debt cannot
+        /// change hands.
+        TransferNotSupported,
+    }
+
+    /// The variable debt token result type.
+    pub type Result<T> = core::result::Result<T, Error>;
+
+    impl VariableDebtToken {
+        /// Creates a new variable debt token for `underlying_asset` managed by `pool`.
+        #[ink(constructor)]
+        pub fn new(pool: AccountId, underlying_asset: AccountId) -> Self {
+            Self {
+                pool,
+                underlying_asset,
+                total_supply: Lazy::new(0),
+                balances: StorageHashMap::new(),
+            }
+        }
+
+        /// Returns the lending pool allowed to mint and burn debt.
+        #[ink(message)]
+        pub fn pool(&self) -> AccountId {
+            self.pool
+        }
+
+        /// Returns the `Erc20` contract of the underlying reserve asset.
+        #[ink(message)]
+        pub fn underlying_asset(&self) -> AccountId {
+            self.underlying_asset
+        }
+
+        /// Returns the variable borrow index of the reserve compounded up to now, as
+        /// reported by the pool.
+        #[ink(message)]
+        pub fn borrow_index(&self) -> Balance {
+            ext::reserve_normalized_variable_debt(self.pool, self.underlying_asset)
+        }
+
+        /// Returns the total debt, including accrued interest.
+        #[ink(message)]
+        pub fn total_supply(&self) -> Balance {
+            ray_mul(*self.total_supply, self.borrow_index())
+        }
+
+        /// Returns the total scaled debt.
+        #[ink(message)]
+        pub fn scaled_total_supply(&self) -> Balance {
+            *self.total_supply
+        }
+
+        /// Returns the principal plus accrued interest owed by `owner`.
+        ///
+        /// Returns `0` if the account is non-existent.
+        #[ink(message)]
+        pub fn balance_of(&self, owner: AccountId) -> Balance {
+            ray_mul(self.scaled_balance_of(owner), self.borrow_index())
+        }
+
+        /// Returns the scaled debt of `owner`.
+        ///
+        /// Returns `0` if the account is non-existent.
+        #[ink(message)]
+        pub fn scaled_balance_of(&self, owner: AccountId) -> Balance {
+            self.balances.get(&owner).copied().unwrap_or(0)
+        }
+
+        /// Always returns `0`, since debt cannot be approved.
+        #[ink(message)]
+        pub fn allowance(&self, _owner: AccountId, _spender: AccountId) -> Balance {
+            0
+        }
+
+        /// Debt cannot be transferred.
+        ///
+        /// # Errors
+        ///
+        /// Always returns `TransferNotSupported` error.
+        #[ink(message)]
+        pub fn transfer(&mut self, _to: AccountId, _value: Balance) -> Result<()> {
+            Err(Error::TransferNotSupported)
+        }
+
+        /// Debt cannot be approved.
+        ///
+        /// # Errors
+        ///
+        /// Always returns `TransferNotSupported` error.
+        #[ink(message)]
+        pub fn approve(&mut self, _spender: AccountId, _value: Balance) -> Result<()> {
+            Err(Error::TransferNotSupported)
+        }
+
+        /// Debt cannot be transferred.
+        ///
+        /// # Errors
+        ///
+        /// Always returns `TransferNotSupported` error.
+        #[ink(message)]
+        pub fn transfer_from(
+            &mut self,
+            _from: AccountId,
+            _to: AccountId,
+            _value: Balance,
+        ) -> Result<()> {
+            Err(Error::TransferNotSupported)
+        }
+
+        /// Records `value` of new debt for `user` at the variable borrow `index`.
+        ///
+        /// On success a `Transfer` and a `Mint` event are emitted.
+        ///
+        /// # Errors
+        ///
+        /// Returns `CallerNotPool` error if the caller is not the lending pool.
+        ///
+        /// Returns `InvalidAmount` error if `value` scales down to zero.
+        #[ink(message)]
+        pub fn mint(
+            &mut self,
+            user: AccountId,
+            value: Balance,
+            index: Balance,
+        ) -> Result<()> {
+            self.ensure_pool()?;
+            let scaled = ray_div(value, index);
+            if scaled == 0 {
+                return Err(Error::InvalidAmount)
+            }
+            let balance = self.scaled_balance_of(user);
+            self.balances.insert(user, balance + scaled);
+            *self.total_supply += scaled;
+            self.env().emit_event(Transfer {
+                from: None,
+                to: Some(user),
+                value,
+            });
+            self.env().emit_event(Mint { user, value, index });
+            Ok(())
+        }
+
+        /// Clears `value` of the debt of `user` at the variable borrow `index`.
+        ///
+        /// If `value` is all `user` owes at `index`, its whole scaled balance is
+        /// burned.
+        ///
+        /// On success a `Transfer` and a `Burn` event are emitted.
+        ///
+        /// # Errors
+        ///
+        /// Returns `CallerNotPool` error if the caller is not the lending pool.
+        ///
+        /// Returns `InvalidAmount` error if `value` scales down to zero.
+        ///
+        /// Returns `InsufficientBalance` error if `user` owes less than `value`.
+        #[ink(message)]
+        pub fn burn(
+            &mut self,
+            user: AccountId,
+            value: Balance,
+            index: Balance,
+        ) -> Result<()> {
+            self.ensure_pool()?;
+            let balance = self.scaled_balance_of(user);
+            let owed = ray_mul(balance, index);
+            if owed < value {
+                return Err(Error::InsufficientBalance)
+            }
+            // Clearing all `user` owes leaves no dust behind after rounding.
+            let scaled = if value == owed {
+                balance
+            } else {
+                core::cmp::min(ray_div(value, index), balance)
+            };
+            if scaled == 0 {
+                return Err(Error::InvalidAmount)
+            }
+            self.balances.insert(user, balance - scaled);
+            *self.total_supply -= scaled;
+            self.env().emit_event(Transfer {
+                from: Some(user),
+                to: None,
+                value,
+            });
+            self.env().emit_event(Burn { user, value, index });
+            Ok(())
+        }
+
+        /// Returns `CallerNotPool` error if the caller is not the lending pool.
+        fn ensure_pool(&self) -> Result<()> {
+            if self.env().caller() != self.pool {
+                return Err(Error::CallerNotPool)
+            }
+            Ok(())
+        }
+    }
+
+    /// Calls into the `LendingPool` contract.
+    #[cfg(not(test))]
+    mod ext {
+        use super::{
+            AccountId,
+            Balance,
+        };
+        use ink_env::call::{
+            build_call,
+            utils::ReturnType,
+            ExecutionInput,
+            Selector,
+        };
+
+        /// The selector of `LendingPool::reserve_normalized_variable_debt`, which is
+        /// fixed so that the token can call it without depending on the pool crate.
+        const RESERVE_NORMALIZED_VARIABLE_DEBT_SELECTOR: [u8; 4] =
+            [0xA7, 0x04, 0x00, 0x03];
+
+        /// Returns the variable borrow index of `asset` compounded up to now by
+        /// `pool`.
+        ///
+        /// # Panics
+        ///
+        /// If the pool cannot be asked, since no debt can be told without it.
+        pub fn reserve_normalized_variable_debt(
+            pool: AccountId,
+            asset: AccountId,
+        ) -> Balance {
+            build_call::<ink_env::DefaultEnvironment>()
+                .callee(pool)
+                .exec_input(
+                    ExecutionInput::new(Selector::new(
+                        RESERVE_NORMALIZED_VARIABLE_DEBT_SELECTOR,
+                    ))
+                    .push_arg(asset),
+                )
+                .returns::<ReturnType<Balance>>()
+                .fire()
+                .expect("pool did not report the variable borrow index")
+        }
+    }
+
+    /// In-memory stand-in for the `LendingPool` contract.
+    ///
+    /// The off-chain environment cannot dispatch calls to other contracts, so
+    /// unit tests keep the variable borrow index here instead.
+    #[cfg(test)]
+    mod ext {
+        use super::{
+            AccountId,
+            Balance,
+        };
+        use lending_math::RAY;
+        use std::cell::Cell;
+
+        thread_local! {
+            static INDEX: Cell<Balance> = Cell::new(RAY);
+        }
+
+        /// Sets the variable borrow index the pool reports, one ray unless set.
+        pub fn set_borrow_index(index: Balance) {
+            INDEX.with(|current| current.set(index))
+        }
+
+        pub fn reserve_normalized_variable_debt(
+            _pool: AccountId,
+            _asset: AccountId,
+        ) -> Balance {
+            INDEX.with(|current| current.get())
+        }
+    }
+
+    /// Unit tests.
+    #[cfg(test)]
+    mod tests {
+        /// Imports all the definitions from the outer scope so we can use them here.
+        use super::*;
+
+        type Event = <VariableDebtToken as ::ink_lang::BaseEvent>::Type;
+
+        use ink_lang as ink;
+        use lending_math::RAY;
+
+        const UNDERLYING: [u8; 32] = [0xAA; 32];
+
+        fn default_accounts(
+        ) -> ink_env::test::DefaultAccounts<ink_env::DefaultEnvironment> {
+            ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts")
+        }
+
+        /// Pushes a new execution context so that `caller` calls the token.
+        fn set_caller(caller: AccountId) {
+            let callee = ink_env::account_id::<ink_env::DefaultEnvironment>()
+                .unwrap_or([0x0; 32].into());
+            // Create call.
+            let mut data =
+                ink_env::test::CallData::new(ink_env::call::Selector::new([0x00; 4]));
+            data.push_arg(&caller);
+            ink_env::test::push_execution_context::<ink_env::DefaultEnvironment>(
+                caller, callee, 1000000, 1000000, data,
+            );
+        }
+
+        /// Creates a debt token whose pool is Alice.
+        fn new_token() -> VariableDebtToken {
+            VariableDebtToken::new(default_accounts().alice, UNDERLYING.into())
+        }
+
+        #[ink::test]
+        fn mint_records_debt_and_index() {
+            let mut token = new_token();
+            let accounts = default_accounts();
+            let index = RAY + RAY / 4;
+
+            assert_eq!(token.mint(accounts.bob, 125, index), Ok(()));
+            assert_eq!(token.scaled_balance_of(accounts.bob), 100);
+
+            ext::set_borrow_index(index);
+            assert_eq!(token.borrow_index(), index);
+            assert_eq!(token.balance_of(accounts.bob), 125);
+
+            let emitted_events = ink_env::test::recorded_events().collect::<Vec<_>>();
+            assert_eq!(emitted_events.len(), 2);
+            let decoded_event =
+                <Event as scale::Decode>::decode(&mut &emitted_events[1].data[..])
+                    .expect("encountered invalid contract event data buffer");
+            if let Event::Mint(Mint { user, value, index }) = decoded_event {
+                assert_eq!(user, accounts.bob);
+                assert_eq!(value, 125);
+                assert_eq!(index, RAY + RAY / 4);
+            } else {
+                panic!("encountered unexpected event kind: expected a Mint event")
+            }
+        }
+
+        #[ink::test]
+        fn debt_accrues_with_borrow_index() {
+            let mut token = new_token();
+            let accounts = default_accounts();
+            assert_eq!(token.mint(accounts.bob, 100, RAY), Ok(()));
+
+            // The debt has grown by 20% when Charlie borrows.
+            let index = RAY + RAY / 5;
+            ext::set_borrow_index(index);
+            assert_eq!(token.balance_of(accounts.bob), 120);
+            assert_eq!(token.total_supply(), 120);
+
+            assert_eq!(token.mint(accounts.charlie, 60, index), Ok(()));
+            assert_eq!(token.balance_of(accounts.bob), 120);
+            assert_eq!(token.balance_of(accounts.charlie), 60);
+            assert_eq!(token.total_supply(), 180);
+        }
+
+        #[ink::test]
+        fn burn_works() {
+            let mut token = new_token();
+            let accounts = default_accounts();
+            assert_eq!(token.mint(accounts.bob, 100, RAY), Ok(()));
+
+            let index = 2 * RAY;
+            ext::set_borrow_index(index);
+            assert_eq!(
+                token.burn(accounts.bob, 201, index),
+                Err(Error::InsufficientBalance)
+            );
+            assert_eq!(token.burn(accounts.bob, 150, index), Ok(()));
+            assert_eq!(token.balance_of(accounts.bob), 50);
+            assert_eq!(ink_env::test::recorded_events().count(), 4);
+
+            assert_eq!(token.burn(accounts.bob, 50, index), Ok(()));
+            assert_eq!(token.scaled_balance_of(accounts.bob), 0);
+            assert_eq!(token.scaled_total_supply(), 0);
+        }
+
+        #[ink::test]
+        fn only_pool_can_mint_and_burn() {
+            let mut token = new_token();
+            let accounts = default_accounts();
+            assert_eq!(token.mint(accounts.bob, 100, RAY), Ok(()));
+
+            set_caller(accounts.bob);
+            assert_eq!(
+                token.mint(accounts.bob, 100, RAY),
+                Err(Error::CallerNotPool)
+            );
+            assert_eq!(
+                token.burn(accounts.bob, 100, RAY),
+                Err(Error::CallerNotPool)
+            );
+            assert_eq!(token.balance_of(accounts.bob), 100);
+        }
+
+        #[ink::test]
+        fn debt_cannot_be_transferred() {
+            let mut token = new_token();
+            let accounts = default_accounts();
+            assert_eq!(token.mint(accounts.alice, 100, RAY), Ok(()));
+
+            assert_eq!(
+                token.transfer(accounts.bob, 10),
+                Err(Error::TransferNotSupported)
+            );
+            assert_eq!(
+                token.approve(accounts.bob, 10),
+                Err(Error::TransferNotSupported)
+            );
+            assert_eq!(
+                token.transfer_from(accounts.alice, accounts.bob, 10),
+                Err(Error::TransferNotSupported)
+            );
+            assert_eq!(token.allowance(accounts.alice, accounts.bob), 0);
+            assert_eq!(token.balance_of(accounts.alice), 100);
+            assert_eq!(token.balance_of(accounts.bob), 0);
+            assert_eq!(ink_env::test::recorded_events().count(), 2);
+        }
+    }
+}
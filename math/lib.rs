@@ -0,0 +1,196 @@
+// Copyright 2021 Bandot Developers.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Fixed-point math shared by the lending contracts.
+//!
+//! Rates and indices are expressed in rays, i.e. with 27 decimals, and stored in
+//! the same `u128` used for token balances. Products are computed with a 256-bit
+//! intermediate so that multiplying a balance by a ray does not overflow.
+
+#![no_std]
+
+/// One ray, i.e. `1.0` with 27 decimals.
+pub const RAY: u128 = 1_000_000_000_000_000_000_000_000_000;
+
+/// Half a ray, used for rounding.
+pub const HALF_RAY: u128 = RAY / 2;
+
+/// Milliseconds in a 365-day year; block timestamps are in milliseconds.
+pub const MILLISECONDS_PER_YEAR: u128 = 365 * 24 * 60 * 60 * 1000;
+
+/// Returns the 256-bit product of `a` and `b` as `(high, low)` words.
+fn full_mul(a: u128, b: u128) -> (u128, u128) {
+    const MASK: u128 = u64::MAX as u128;
+    let (a_hi, a_lo) = (a >> 64, a & MASK);
+    let (b_hi, b_lo) = (b >> 64, b & MASK);
+    let lo_lo = a_lo * b_lo;
+    let lo_hi = a_lo * b_hi;
+    let hi_lo = a_hi * b_lo;
+    let hi_hi = a_hi * b_hi;
+    let mid = (lo_lo >> 64) + (lo_hi & MASK) + (hi_lo & MASK);
+    let low = (lo_lo & MASK) | (mid << 64);
+    let high = hi_hi + (lo_hi >> 64) + (hi_lo >> 64) + (mid >> 64);
+    (high, low)
+}
+
+/// Divides the 256-bit value `(high, low)` by `divisor`.
+///
+/// Returns `None` if `divisor` is zero or the quotient does not fit into 128 bits.
+fn div_wide(high: u128, low: u128, divisor: u128) -> Option<u128> {
+    if divisor == 0 || high >= divisor {
+        return None
+    }
+    if high == 0 {
+        return Some(low / divisor)
+    }
+    let mut remainder = high;
+    let mut quotient = 0u128;
+    for bit in (0..128).rev() {
+        let carry = remainder >> 127;
+        remainder = (remainder << 1) | ((low >> bit) & 1);
+        quotient <<= 1;
+        if carry == 1 || remainder >= divisor {
+            remainder = remainder.wrapping_sub(divisor);
+            quotient |= 1;
+        }
+    }
+    Some(quotient)
+}
+
+/// Returns `a * b / c` rounded down.
+///
+/// Returns `None` if `c` is zero or the result does not fit into a `u128`.
+pub fn checked_mul_div(a: u128, b: u128, c: u128) -> Option<u128> {
+    let (high, low) = full_mul(a, b);
+    div_wide(high, low, c)
+}
+
+/// Returns `a * b / c` rounded half up.
+///
+/// Returns `None` if `c` is zero or the result does not fit into a `u128`.
+pub fn checked_mul_div_half_up(a: u128, b: u128, c: u128) -> Option<u128> {
+    let (high, low) = full_mul(a, b);
+    let (low, carry) = low.overflowing_add(c / 2);
+    let high = high.checked_add(carry as u128)?;
+    div_wide(high, low, c)
+}
+
+/// Multiplies `a` by the ray `b`, rounding half up.
+///
+/// # Panics
+///
+/// If the result does not fit into a `u128`.
+pub fn ray_mul(a: u128, b: u128) -> u128 {
+    checked_mul_div_half_up(a, b, RAY).expect("ray multiplication overflow")
+}
+
+/// Divides `a` by the ray `b`, rounding half up.
+///
+/// # Panics
+///
+/// If `b` is zero or the result does not fit into a `u128`.
+pub fn ray_div(a: u128, b: u128) -> u128 {
+    checked_mul_div_half_up(a, RAY, b).expect("ray division overflow")
+}
+
+/// Returns the growth factor, in rays, of a balance earning the yearly `rate`
+/// without compounding for `elapsed` milliseconds.
+///
+/// # Panics
+///
+/// If the result does not fit into a `u128`.
+pub fn calculate_linear_interest(rate: u128, elapsed: u64) -> u128 {
+    let interest = checked_mul_div(rate, elapsed as u128, MILLISECONDS_PER_YEAR)
+        .expect("interest overflow");
+    RAY + interest
+}
+
+/// Returns the growth factor, in rays, of a balance compounding the yearly `rate`
+/// for `elapsed` milliseconds.
+///
+/// Uses the first terms of the Taylor expansion of `e^(rate * t)`, which slightly
+/// undercharges very high rates in exchange for a constant cost.
+///
+/// # Panics
+///
+/// If the result does not fit into a `u128`.
+pub fn calculate_compounded_interest(rate: u128, elapsed: u64) -> u128 {
+    let x = calculate_linear_interest(rate, elapsed) - RAY;
+    let x_squared = ray_mul(x, x);
+    let x_cubed = ray_mul(x_squared, x);
+    RAY + x + x_squared / 2 + x_cubed / 6
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mul_div_matches_native_math() {
+        let values = [0u128, 1, 2, 3, 7, 1_000, u64::MAX as u128, 1 << 100];
+        for &a in values.iter() {
+            for &b in values.iter() {
+                for &c in values.iter().skip(1) {
+                    if let Some(product) = a.checked_mul(b) {
+                        assert_eq!(checked_mul_div(a, b, c), Some(product / c));
+                        let half_up = product.checked_add(c / 2).map(|p| p / c);
+                        if half_up.is_some() {
+                            assert_eq!(checked_mul_div_half_up(a, b, c), half_up);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn mul_div_uses_wide_intermediate() {
+        assert_eq!(checked_mul_div(u128::MAX, u128::MAX, u128::MAX), Some(u128::MAX));
+        assert_eq!(checked_mul_div(u128::MAX, 3, 4), Some(u128::MAX / 4 * 3 + 2));
+        assert_eq!(checked_mul_div(1 << 127, 4, 8), Some(1 << 126));
+        assert_eq!(checked_mul_div(u128::MAX, 2, 1), None);
+        assert_eq!(checked_mul_div(1, 1, 0), None);
+    }
+
+    #[test]
+    fn ray_math_works() {
+        let amount = 1_000_000_000_000_000_000_000u128;
+        assert_eq!(ray_mul(amount, RAY), amount);
+        assert_eq!(ray_div(amount, RAY), amount);
+        assert_eq!(ray_mul(amount, RAY + RAY / 10), amount + amount / 10);
+        assert_eq!(ray_div(ray_mul(amount, 3 * RAY / 2), 3 * RAY / 2), amount);
+        assert_eq!(ray_mul(1, HALF_RAY), 1);
+        assert_eq!(ray_mul(1, HALF_RAY - 1), 0);
+    }
+
+    #[test]
+    fn interest_works() {
+        let year = MILLISECONDS_PER_YEAR as u64;
+        assert_eq!(calculate_linear_interest(RAY / 10, 0), RAY);
+        assert_eq!(calculate_linear_interest(RAY / 10, year), RAY + RAY / 10);
+        assert_eq!(calculate_linear_interest(RAY / 10, year / 2), RAY + RAY / 20);
+        assert_eq!(calculate_compounded_interest(RAY / 10, 0), RAY);
+
+        // 1 + 0.1 + 0.1^2 / 2 + 0.1^3 / 6, just below e^0.1 = 1.105170918...
+        let compounded = calculate_compounded_interest(RAY / 10, year);
+        assert_eq!(compounded, 1_105_166_666_666_666_666_666_666_666);
+        assert!(calculate_compounded_interest(RAY / 10, 1000) > RAY);
+    }
+
+    #[test]
+    #[should_panic(expected = "ray division overflow")]
+    fn ray_div_by_zero_panics() {
+        ray_div(1, 0);
+    }
+}
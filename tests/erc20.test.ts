@@ -17,13 +17,30 @@ describe('ERC20', () => {
     const Alice = signers[0];
     const sender = await getRandomSigner(Alice, one.muln(10000));
     const contractFactory = await getContractFactory('erc20', sender);
-    const contract = await contractFactory.deploy('new', '1000');
+    const contract = await contractFactory.deploy(
+      'new',
+      '1000',
+      'Bandot Token',
+      'BDT',
+      10
+    );
     const abi = artifacts.readArtifact('erc20');
     const receiver = await getRandomSigner();
 
     return { sender, contractFactory, contract, abi, receiver, Alice, one };
   }
 
+  it('Exposes token metadata', async () => {
+    const { contract } = await setup();
+    expect((await contract.query.tokenName()).output?.toString()).to.equal(
+      'Bandot Token'
+    );
+    expect((await contract.query.tokenSymbol()).output?.toString()).to.equal(
+      'BDT'
+    );
+    expect((await contract.query.tokenDecimals()).output).to.equal(10);
+  });
+
   it('Assigns initial balance', async () => {
     const { contract, sender } = await setup();
     const result = await contract.query.balanceOf(sender.address);
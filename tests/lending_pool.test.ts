@@ -0,0 +1,130 @@
+import BN from 'bn.js';
+import { expect } from 'chai';
+import { patract, network } from 'redspot';
+
+const { getContractFactory, getRandomSigner } = patract;
+
+const { api, getSigners } = network;
+
+describe('LendingPool', () => {
+  after(() => {
+    return api.disconnect();
+  });
+
+  async function setup() {
+    const one = new BN(10).pow(new BN(api.registry.chainDecimals[0]));
+    const signers = await getSigners();
+    const Alice = signers[0];
+    const sender = await getRandomSigner(Alice, one.muln(10000));
+    const tokenFactory = await getContractFactory('erc20', sender);
+    const token = await tokenFactory.deploy('new', '1000', null, null, 0);
+    const oracleFactory = await getContractFactory('price_oracle', sender);
+    const oracle = await oracleFactory.deploy('new', 60000, 1);
+    await oracle.tx.addFeeder(sender.address);
+    await oracle.tx.submitPrice(token.address, 1);
+    // A flat curve at zero keeps the balances in these tests free of interest.
+    const strategyFactory = await getContractFactory(
+      'interest_rate_strategy',
+      sender
+    );
+    const strategy = await strategyFactory.deploy(
+      'new',
+      new BN(10).pow(new BN(27)),
+      0,
+      0,
+      0
+    );
+    const poolFactory = await getContractFactory('lending_pool', sender);
+    const pool = await poolFactory.deploy('new', oracle.address);
+    const aTokenFactory = await getContractFactory('a_token', sender);
+    const aToken = await aTokenFactory.deploy(
+      'new',
+      pool.address,
+      token.address
+    );
+    const variableDebtTokenFactory = await getContractFactory(
+      'variable_debt_token',
+      sender
+    );
+    const variableDebtToken = await variableDebtTokenFactory.deploy(
+      'new',
+      pool.address,
+      token.address
+    );
+    const stableDebtTokenFactory = await getContractFactory(
+      'stable_debt_token',
+      sender
+    );
+    const stableDebtToken = await stableDebtTokenFactory.deploy(
+      'new',
+      pool.address,
+      token.address
+    );
+    await pool.tx.initReserve(
+      token.address,
+      aToken.address,
+      stableDebtToken.address,
+      variableDebtToken.address,
+      strategy.address
+    );
+
+    return {
+      sender,
+      token,
+      oracle,
+      pool,
+      aToken,
+      variableDebtToken,
+      Alice,
+      one
+    };
+  }
+
+  it('Deposit moves tokens into the pool', async () => {
+    const { token, pool, aToken, sender } = await setup();
+
+    await token.tx.approve(pool.address, 100);
+    await expect(pool.tx.deposit(token.address, 100))
+      .to.emit(pool, 'Deposit')
+      .withArgs(token.address, sender.address, 100);
+
+    const deposit = await pool.query.depositOf(token.address, sender.address);
+    expect(deposit.output).to.equal(100);
+    const balance = await token.query.balanceOf(pool.address);
+    expect(balance.output).to.equal(100);
+    const aBalance = await aToken.query.balanceOf(sender.address);
+    expect(aBalance.output).to.equal(100);
+  });
+
+  it('Can not borrow without collateral', async () => {
+    const { token, pool, Alice, one } = await setup();
+
+    await token.tx.approve(pool.address, 100);
+    await pool.tx.deposit(token.address, 100);
+
+    const borrower = await getRandomSigner(Alice, one.muln(10000));
+    await expect(
+      pool.tx.borrow(token.address, 10, {
+        signer: borrower
+      })
+    ).to.not.emit(pool, 'Borrow');
+  });
+
+  it('Borrow and repay update the debt', async () => {
+    const { token, pool, variableDebtToken, sender } = await setup();
+
+    await token.tx.approve(pool.address, 200);
+    await pool.tx.deposit(token.address, 100);
+    await expect(pool.tx.borrow(token.address, 75))
+      .to.emit(pool, 'Borrow')
+      .withArgs(token.address, sender.address, 75);
+    const debt = await pool.query.debtOf(token.address, sender.address);
+    expect(debt.output).to.equal(75);
+    const debtBalance = await variableDebtToken.query.balanceOf(sender.address);
+    expect(debtBalance.output).to.equal(75);
+
+    await pool.tx.repay(token.address, 75);
+    const repaid = await pool.query.debtOf(token.address, sender.address);
+    expect(repaid.output).to.equal(0);
+  });
+});